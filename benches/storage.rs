@@ -33,7 +33,8 @@ fn drop_storage(c: &mut Criterion) {
                     HashMapDb::<BasicId>::default(),
                     BonsaiStorageConfig::default(),
                     251,
-                );
+                )
+                .unwrap();
 
                 let mut rng = SmallRng::seed_from_u64(42);
                 let felt = Felt::from_hex("0x66342762FDD54D033c195fec3ce2568b62052e").unwrap();
@@ -71,7 +72,8 @@ fn storage_with_insert(c: &mut Criterion) {
                     HashMapDb::<BasicId>::default(),
                     BonsaiStorageConfig::default(),
                     251,
-                );
+                )
+                .unwrap();
                 bonsai_storage
             },
             |bonsai_storage| {
@@ -101,7 +103,8 @@ fn storage(c: &mut Criterion) {
             HashMapDb::<BasicId>::default(),
             BonsaiStorageConfig::default(),
             251,
-        );
+        )
+        .unwrap();
         let mut rng = SmallRng::seed_from_u64(42);
 
         let felt = Felt::from_hex("0x66342762FDD54D033c195fec3ce2568b62052e").unwrap();
@@ -134,7 +137,8 @@ fn one_update(c: &mut Criterion) {
             HashMapDb::<BasicId>::default(),
             BonsaiStorageConfig::default(),
             251,
-        );
+        )
+        .unwrap();
         let mut rng = SmallRng::seed_from_u64(42);
 
         let felt = Felt::from_hex("0x66342762FDD54D033c195fec3ce2568b62052e").unwrap();
@@ -171,7 +175,8 @@ fn five_updates(c: &mut Criterion) {
             HashMapDb::<BasicId>::default(),
             BonsaiStorageConfig::default(),
             251,
-        );
+        )
+        .unwrap();
         let mut rng = SmallRng::seed_from_u64(42);
 
         let felt = Felt::from_hex("0x66342762FDD54D033c195fec3ce2568b62052e").unwrap();
@@ -221,7 +226,8 @@ fn multiple_contracts(c: &mut Criterion) {
             HashMapDb::<BasicId>::default(),
             BonsaiStorageConfig::default(),
             251,
-        );
+        )
+        .unwrap();
         let mut rng = thread_rng();
 
         let felt = Felt::from_hex("0x66342762FDD54D033c195fec3ce2568b62052e").unwrap();
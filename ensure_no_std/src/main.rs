@@ -1,21 +1,67 @@
-#![no_std]
-#![no_main]
+#![cfg_attr(not(test), no_std)]
+#![cfg_attr(not(test), no_main)]
 
-use core::panic::PanicInfo;
+extern crate alloc;
+
+use alloc::vec;
+use bonsai_trie::{BitVec, MultiProof, Path, ProofNode};
+use starknet_types_core::{felt::Felt, hash::Pedersen};
 
 /// This function is called on panic.
+#[cfg(not(test))]
 #[panic_handler]
-fn panic(_info: &PanicInfo) -> ! {
+fn panic(_info: &core::panic::PanicInfo) -> ! {
     loop {}
 }
 
+#[cfg(not(test))]
 #[no_mangle]
 pub extern "C" fn _start() -> ! {
+    verify_sample_proof();
     loop {}
 }
 
+#[cfg(not(test))]
 #[global_allocator]
 static ALLOC: wee_alloc::WeeAlloc = wee_alloc::WeeAlloc::INIT;
 
-#[allow(unused_imports)]
-use bonsai_trie;
+/// Verifies a proof of both leaves of a trie of height 8, then the same proof against a wrong
+/// root.
+fn verify_sample_proof() -> bool {
+    let keys = [
+        BitVec::from_vec(vec![0b0000_0001]),
+        BitVec::from_vec(vec![0b1000_0000]),
+    ];
+    let values = [Felt::ONE, Felt::TWO];
+    let edges = [0, 1].map(|i| ProofNode::Edge {
+        child: values[i],
+        path: Path(keys[i][1..].to_bitvec()),
+    });
+    let root_node = ProofNode::Binary {
+        left: edges[0].hash::<Pedersen>(),
+        right: edges[1].hash::<Pedersen>(),
+    };
+    let root = root_node.hash::<Pedersen>();
+
+    let mut proof = MultiProof(Default::default());
+    for node in [root_node].into_iter().chain(edges) {
+        proof.0.insert(node.hash::<Pedersen>(), node);
+    }
+
+    let valid = proof
+        .verify_proof::<Pedersen>(root, &keys, 8)
+        .zip(values)
+        .all(|(value, expected)| value.is_ok_and(|value| value == expected));
+    let wrong_root = proof
+        .verify_proof::<Pedersen>(root + Felt::ONE, &keys, 8)
+        .all(|value| value.is_err());
+    valid && wrong_root
+}
+
+#[cfg(test)]
+mod tests {
+    #[test]
+    fn verify_proof() {
+        assert!(super::verify_sample_proof());
+    }
+}
@@ -28,7 +28,8 @@ fn main() {
         HashMapDb::default(),
         BonsaiStorageConfig::default(),
         251,
-    );
+    )
+    .unwrap();
     let mut id_builder = BasicIdBuilder::new();
     let identifier: Vec<u8> = vec![];
     let mut rng = SmallRng::seed_from_u64(42);
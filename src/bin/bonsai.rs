@@ -0,0 +1,247 @@
+//! Inspects the bonsai storage of a RocksDB database, opened read-only so that it can run while
+//! another process writes to the database. Run `bonsai --help` for the commands.
+
+use std::{env, error::Error, fmt::Write as _, process::ExitCode};
+
+use bitvec::{order::Msb0, view::BitView};
+use bonsai_trie::{
+    databases::{
+        open_rocks_db_read_only, RocksDBColumnFamilies, RocksDBConfig, RocksDBOpenOptions,
+        RocksDBReadOnly,
+    },
+    id::{BasicId, Id},
+    BitSlice, BitVec, BonsaiHasher, BonsaiStorage, BonsaiStorageConfig, ProofNode,
+};
+use parity_scale_codec::Decode;
+use starknet_types_core::{
+    felt::Felt,
+    hash::{Pedersen, Poseidon},
+};
+
+type Result<T> = std::result::Result<T, Box<dyn Error>>;
+
+const USAGE: &str = "\
+Usage: bonsai <db-path> [options] <command>
+
+Options:
+  --namespace <namespace>      use the column families of `RocksDBColumnFamilies::with_namespace`
+  --height <height>            height of the tries, 251 by default
+  --hash <pedersen|poseidon>   hash function of the tries, pedersen by default
+
+Commands:
+  identifiers                  list the identifiers of the tries
+  root <identifier>            print the root hash of a trie
+  get <identifier> <key>       print the value of a key
+  dump <identifier>            print the keys and values of a trie, in key order
+  proof <identifier> <key>...  print a multi-proof of keys, one node per line
+  trie-logs                    list the ids of the commits with a trie log and their changes count
+  changes <id>                 print the changes of a commit: key, old value and new value
+
+Identifiers are hex bytes, `0x` for the empty one. Keys are hex felts and ids are integers.
+";
+
+enum Hash {
+    Pedersen,
+    Poseidon,
+}
+
+struct Options {
+    path: String,
+    column_families: RocksDBColumnFamilies,
+    height: u8,
+    hash: Hash,
+    command: Vec<String>,
+}
+
+fn main() -> ExitCode {
+    let result = parse_options(env::args().skip(1)).and_then(|options| match options {
+        Some(options) => match options.hash {
+            Hash::Pedersen => run::<Pedersen>(&options),
+            Hash::Poseidon => run::<Poseidon>(&options),
+        },
+        None => {
+            print!("{USAGE}");
+            Ok(())
+        }
+    });
+    match result {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(err) => {
+            eprintln!("error: {err}");
+            ExitCode::FAILURE
+        }
+    }
+}
+
+/// Returns `None` when the usage was requested.
+fn parse_options(mut args: impl Iterator<Item = String>) -> Result<Option<Options>> {
+    let mut path = None;
+    let mut options = Options {
+        path: String::new(),
+        column_families: RocksDBColumnFamilies::default(),
+        height: 251,
+        hash: Hash::Pedersen,
+        command: Vec::new(),
+    };
+    while let Some(arg) = args.next() {
+        let mut value = || args.next().ok_or_else(|| format!("missing value of {arg}"));
+        match arg.as_str() {
+            "-h" | "--help" => return Ok(None),
+            "--namespace" => {
+                options.column_families = RocksDBColumnFamilies::with_namespace(&value()?)
+            }
+            "--height" => options.height = value()?.parse()?,
+            "--hash" => {
+                options.hash = match value()?.as_str() {
+                    "pedersen" => Hash::Pedersen,
+                    "poseidon" => Hash::Poseidon,
+                    hash => return Err(format!("unknown hash function {hash:?}").into()),
+                }
+            }
+            _ if path.is_none() => path = Some(arg),
+            _ => options.command.push(arg),
+        }
+    }
+    let Some(path) = path else {
+        return Ok(None);
+    };
+    if !(1..=251).contains(&options.height) {
+        return Err(format!("invalid height {}", options.height).into());
+    }
+    options.path = path;
+    Ok(Some(options))
+}
+
+fn run<H: BonsaiHasher + Send + Sync>(options: &Options) -> Result<()> {
+    let config = RocksDBConfig {
+        column_families: options.column_families.clone(),
+        ..Default::default()
+    };
+    let db = open_rocks_db_read_only(
+        &options.path,
+        &RocksDBOpenOptions {
+            storages: vec![config.clone()],
+            ..Default::default()
+        },
+    )?;
+    let storage: BonsaiStorage<BasicId, _, H> = BonsaiStorage::new(
+        RocksDBReadOnly::new(&db, config),
+        BonsaiStorageConfig::default(),
+        options.height,
+    )?;
+    let height = usize::from(options.height);
+
+    let command: Vec<&str> = options.command.iter().map(String::as_str).collect();
+    match command.as_slice() {
+        ["identifiers"] => {
+            for identifier in storage.identifiers()? {
+                println!("{}", to_hex(&identifier));
+            }
+        }
+        ["root", identifier] => {
+            println!("{:#x}", storage.root_hash(&parse_bytes(identifier)?)?);
+        }
+        ["get", identifier, key] => {
+            let value = storage.get(&parse_bytes(identifier)?, &parse_key(key, height)?)?;
+            println!("{}", value_to_string(value));
+        }
+        ["dump", identifier] => {
+            for (key, value) in storage.get_key_value_pairs(&parse_bytes(identifier)?)? {
+                let value = Felt::decode(&mut value.as_slice())?;
+                println!(
+                    "{:#x} {value:#x}",
+                    key_to_felt(BitSlice::from_slice(&key), height)
+                );
+            }
+        }
+        ["proof", identifier, keys @ ..] if !keys.is_empty() => {
+            let mut keys = keys
+                .iter()
+                .map(|key| parse_key(key, height))
+                .collect::<Result<Vec<_>>>()?;
+            // Sorted keys need the least backtracking.
+            keys.sort();
+            let proof = storage.get_multi_proof(&parse_bytes(identifier)?, &keys)?;
+            let mut nodes: Vec<_> = proof.0.into_iter().collect();
+            nodes.sort_by_key(|(hash, _)| *hash);
+            for (hash, node) in nodes {
+                match node {
+                    ProofNode::Binary { left, right } => {
+                        println!("{hash:#x} binary {left:#x} {right:#x}")
+                    }
+                    ProofNode::Edge { child, path } => {
+                        let path: String = path
+                            .iter()
+                            .map(|bit| if *bit { '1' } else { '0' })
+                            .collect();
+                        println!("{hash:#x} edge {path} {child:#x}")
+                    }
+                }
+            }
+        }
+        ["trie-logs"] => {
+            for id in storage.trie_log_ids()? {
+                println!("{} {}", id.as_u64(), storage.get_changes(id)?.len());
+            }
+        }
+        ["changes", id] => {
+            let mut changes: Vec<_> = storage
+                .get_changes(BasicId::from_u64(id.parse()?))?
+                .into_iter()
+                .collect();
+            changes.sort_by(|(a, _), (b, _)| a.cmp(b));
+            for (key, change) in changes {
+                println!(
+                    "{:#x} {} {}",
+                    key_to_felt(&key, height),
+                    value_to_string(change.old_value),
+                    value_to_string(change.new_value)
+                );
+            }
+        }
+        _ => return Err("invalid command, see `bonsai --help`".into()),
+    }
+    Ok(())
+}
+
+fn parse_bytes(hex: &str) -> Result<Vec<u8>> {
+    let digits = hex.strip_prefix("0x").unwrap_or(hex);
+    if !digits.is_ascii() || digits.len() % 2 != 0 {
+        return Err(format!("invalid hex bytes {hex:?}").into());
+    }
+    (0..digits.len())
+        .step_by(2)
+        .map(|i| Ok(u8::from_str_radix(&digits[i..i + 2], 16)?))
+        .collect()
+}
+
+fn to_hex(bytes: &[u8]) -> String {
+    bytes.iter().fold(String::from("0x"), |mut hex, byte| {
+        let _ = write!(hex, "{byte:02x}");
+        hex
+    })
+}
+
+/// The last `height` bits of a felt.
+fn parse_key(key: &str, height: usize) -> Result<BitVec> {
+    let felt = Felt::from_hex(key).map_err(|_| format!("invalid key {key:?}"))?;
+    let bytes = felt.to_bytes_be();
+    let (high, low) = bytes.view_bits::<Msb0>().split_at(256 - height);
+    if high.any() {
+        return Err(format!("key {key} does not fit in {height} bits").into());
+    }
+    Ok(low.to_bitvec())
+}
+
+/// The felt whose last `height` bits are the first `height` bits of `key`, e.g. a key stored
+/// with padding bits.
+fn key_to_felt(key: &BitSlice, height: usize) -> Felt {
+    let key = &key[..height.min(key.len())];
+    let mut bytes = [0u8; 32];
+    bytes.view_bits_mut::<Msb0>()[256 - key.len()..].copy_from_bitslice(key);
+    Felt::from_bytes_be(&bytes)
+}
+
+fn value_to_string(value: Option<Felt>) -> String {
+    value.map_or_else(|| "none".to_string(), |value| format!("{value:#x}"))
+}
@@ -1,4 +1,4 @@
-use crate::{id::Id, ByteVec, Vec};
+use crate::{id::Id, Box, ByteVec, Vec};
 #[cfg(feature = "std")]
 use std::error::Error;
 
@@ -8,6 +8,8 @@ pub enum DatabaseKey<'a> {
     Trie(&'a [u8]),
     Flat(&'a [u8]),
     TrieLog(&'a [u8]),
+    /// Bookkeeping records of the storage itself, such as the schema version.
+    Meta(&'a [u8]),
 }
 
 impl DatabaseKey<'_> {
@@ -16,16 +18,25 @@ impl DatabaseKey<'_> {
             DatabaseKey::Trie(slice) => slice,
             DatabaseKey::Flat(slice) => slice,
             DatabaseKey::TrieLog(slice) => slice,
+            DatabaseKey::Meta(slice) => slice,
         }
     }
 }
 
+/// Iterator over the key-value pairs of a prefix scan, see [`BonsaiDatabase::iter_by_prefix`].
+pub type DatabaseIterator<'a, E> = Box<dyn Iterator<Item = Result<(ByteVec, ByteVec), E>> + 'a>;
+
 #[cfg(feature = "std")]
 pub trait DBError: Error + Send + Sync {}
 
 #[cfg(not(feature = "std"))]
 pub trait DBError: Send + Sync {}
 
+/// Returns true if `key` is within the `seek` and `upper_bound` of a prefix scan.
+pub(crate) fn in_bounds(key: &[u8], seek: Option<&[u8]>, upper_bound: Option<&[u8]>) -> bool {
+    seek.map_or(true, |seek| key >= seek) && upper_bound.map_or(true, |bound| key < bound)
+}
+
 /// Trait to be implemented on any type that can be used as a database.
 pub trait BonsaiDatabase: core::fmt::Debug {
     type Batch: Default;
@@ -40,6 +51,12 @@ pub trait BonsaiDatabase: core::fmt::Debug {
     /// Returns the value of the key if it exists
     fn get(&self, key: &DatabaseKey) -> Result<Option<ByteVec>, Self::DatabaseError>;
 
+    /// Returns the values of the keys, in the same order, looking them up in as few round-trips
+    /// as the database allows
+    fn get_many(&self, keys: &[DatabaseKey]) -> Result<Vec<Option<ByteVec>>, Self::DatabaseError> {
+        keys.iter().map(|key| self.get(key)).collect()
+    }
+
     #[allow(clippy::type_complexity)]
     /// Returns all values with keys that start with the given prefix
     fn get_by_prefix(
@@ -47,6 +64,22 @@ pub trait BonsaiDatabase: core::fmt::Debug {
         prefix: &DatabaseKey,
     ) -> Result<Vec<(ByteVec, ByteVec)>, Self::DatabaseError>;
 
+    /// Iterates in key order over the key-value pairs with keys that start with the given prefix,
+    /// without loading them all in memory.
+    /// The scan starts at the first key not before `seek` and stops before `upper_bound`; both are
+    /// full keys of the column of `prefix`.
+    fn iter_by_prefix<'a>(
+        &'a self,
+        prefix: &DatabaseKey,
+        seek: Option<&[u8]>,
+        upper_bound: Option<&[u8]>,
+    ) -> Result<DatabaseIterator<'a, Self::DatabaseError>, Self::DatabaseError> {
+        let mut entries = self.get_by_prefix(prefix)?;
+        entries.retain(|(key, _)| in_bounds(key, seek, upper_bound));
+        entries.sort_unstable_by(|(a, _), (b, _)| a.cmp(b));
+        Ok(Box::new(entries.into_iter().map(Ok)))
+    }
+
     /// Returns true if the key exists
     fn contains(&self, key: &DatabaseKey) -> Result<bool, Self::DatabaseError>;
 
@@ -82,6 +115,8 @@ pub trait BonsaiPersistentDatabase<ID: Id> {
     type Transaction<'a>: BonsaiDatabase<DatabaseError = Self::DatabaseError>
     where
         Self: 'a;
+    /// Read-only view of the database that can be shared between threads
+    type Reader: BonsaiDatabase<DatabaseError = Self::DatabaseError> + Send + Sync;
     #[cfg(feature = "std")]
     type DatabaseError: Error + DBError;
     #[cfg(not(feature = "std"))]
@@ -90,9 +125,22 @@ pub trait BonsaiPersistentDatabase<ID: Id> {
     /// This function returns a snapshot id that can be used to create a transaction
     fn snapshot(&mut self, id: ID);
 
-    /// Create a transaction based on the given snapshot id
+    /// Drop the snapshots taken after the given id, once their commits were reverted
+    fn remove_snapshots_after(&mut self, id: ID);
+
+    /// Id of the nearest snapshot at or before the given id
+    fn nearest_snapshot(&self, id: ID) -> Option<ID>;
+
+    /// Create a transaction based on the nearest snapshot at or before the given id, returned
+    /// along with the id of that snapshot
     fn transaction(&self, id: ID) -> Option<(ID, Self::Transaction<'_>)>;
 
+    /// Create a transaction based on the current state of the database
+    fn transaction_at_head(&self) -> Self::Transaction<'_>;
+
+    /// Create a reader of the current state of the database, unaffected by later writes
+    fn reader(&self) -> Self::Reader;
+
     /// Merge a transaction in the current persistent database
     fn merge<'a>(&mut self, transaction: Self::Transaction<'a>) -> Result<(), Self::DatabaseError>
     where
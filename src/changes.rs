@@ -1,4 +1,6 @@
-use crate::{hash_map::Entry, id::Id, trie::TrieKey, ByteVec, HashMap, Vec};
+use crate::{
+    hash_map::Entry, id::Id, trie::TrieKey, BonsaiStorageError, ByteVec, DBError, HashMap, Vec,
+};
 use core::iter;
 use serde::{Deserialize, Serialize};
 
@@ -57,20 +59,27 @@ impl ChangeBatch {
             .collect()
     }
 
-    pub fn deserialize<ID: Id>(id: &ID, changes: Vec<(ByteVec, ByteVec)>) -> Self {
+    /// Reads back the trie log of `id` from its database entries, which must be in key order.
+    pub fn deserialize<ID: Id, E: DBError>(
+        id: &ID,
+        changes: impl IntoIterator<Item = Result<(ByteVec, ByteVec), E>>,
+    ) -> Result<Self, BonsaiStorageError<E>> {
         let id = id.to_bytes();
         let mut change_batch = ChangeBatch(HashMap::new());
         let mut current_change = Change::default();
         let mut last_key = None;
-        for (key, value) in changes {
+        for change in changes {
+            let (key, value) = change?;
             if key.len() < id.len() + 3 {
-                panic!("Invalid key format");
+                return Err(BonsaiStorageError::InvalidTrieLogKey(key.to_vec()));
             }
-            // following unwraps and indices are safe because of the check above
-            let mut key = key.to_vec();
-            let change_type = key.pop().unwrap();
-            let key_type = key.pop().unwrap();
-            let change_key = TrieKey::from_variant_and_bytes(key_type, key[id.len() + 1..].into());
+            // indices are safe because of the check above
+            let (change_type, key_type) = (key[key.len() - 1], key[key.len() - 2]);
+            let Some(change_key) =
+                TrieKey::from_variant_and_bytes(key_type, key[id.len() + 1..key.len() - 2].into())
+            else {
+                return Err(BonsaiStorageError::InvalidTrieLogKey(key.to_vec()));
+            };
             if let Some(last_key) = last_key {
                 if last_key != change_key {
                     change_batch.insert_in_place(last_key, current_change);
@@ -80,7 +89,7 @@ impl ChangeBatch {
             match change_type {
                 NEW_VALUE => current_change.new_value = Some(value),
                 OLD_VALUE => current_change.old_value = Some(value),
-                _ => panic!("Invalid change type"),
+                _ => return Err(BonsaiStorageError::InvalidTrieLogKey(key.to_vec())),
             }
             last_key = Some(change_key.clone());
         }
@@ -89,7 +98,7 @@ impl ChangeBatch {
                 change_batch.insert_in_place(last_key, current_change);
             }
         }
-        change_batch
+        Ok(change_batch)
     }
 }
 
@@ -0,0 +1,58 @@
+use parity_scale_codec::{Decode, Encode, Input, Output};
+use starknet_types_core::felt::Felt;
+
+use crate::{id::Id, ByteVec, Vec};
+
+/// Version of the encoding of [`CommitInfo`] records written by this crate.
+pub const COMMIT_INFO_VERSION: u32 = 1;
+
+/// Prefix of the meta keys of the commit records, followed by the big-endian id.
+pub(crate) const COMMIT_INFO_KEY_PREFIX: &[u8] = b"commit/";
+
+pub(crate) fn commit_info_key<ID: Id>(id: ID) -> ByteVec {
+    let mut key = ByteVec::from(COMMIT_INFO_KEY_PREFIX);
+    key.extend_from_slice(&id.as_u64().to_be_bytes());
+    key
+}
+
+/// Record written next to the trie log of every commit, see [`crate::BonsaiStorage::commit_info`].
+///
+/// It is removed with the trie log, when the commit is reverted or its trie log is pruned.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CommitInfo<ID: Id> {
+    /// Version of the encoding the record was written with.
+    pub version: u32,
+    pub id: ID,
+    /// Root hashes, after the commit, of the tries it changed, sorted by identifier.
+    pub roots: Vec<(Vec<u8>, Felt)>,
+    /// Number of leaves changed by the commit.
+    pub change_count: u64,
+    /// Caller-supplied data, such as a block hash.
+    pub metadata: Option<Vec<u8>>,
+}
+
+impl<ID: Id> Encode for CommitInfo<ID> {
+    fn encode_to<T: Output + ?Sized>(&self, dest: &mut T) {
+        self.version.encode_to(dest);
+        self.id.as_u64().encode_to(dest);
+        self.roots.encode_to(dest);
+        self.change_count.encode_to(dest);
+        self.metadata.encode_to(dest);
+    }
+}
+
+impl<ID: Id> Decode for CommitInfo<ID> {
+    fn decode<I: Input>(input: &mut I) -> Result<Self, parity_scale_codec::Error> {
+        let version = u32::decode(input)?;
+        if version != COMMIT_INFO_VERSION {
+            return Err("Unsupported commit info version".into());
+        }
+        Ok(Self {
+            version,
+            id: ID::from_u64(u64::decode(input)?),
+            roots: Decode::decode(input)?,
+            change_count: u64::decode(input)?,
+            metadata: Decode::decode(input)?,
+        })
+    }
+}
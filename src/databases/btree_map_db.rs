@@ -0,0 +1,303 @@
+use crate::{
+    bonsai_database::{BonsaiPersistentDatabase, DBError, DatabaseIterator},
+    id::Id,
+    Arc, BTreeMap, BonsaiDatabase, Box, Vec,
+};
+use crate::{ByteVec, DatabaseKey};
+use core::{fmt, fmt::Display, iter, ops::Bound};
+
+#[derive(Debug)]
+pub struct BTreeMapDbError {}
+
+#[cfg(feature = "std")]
+impl std::error::Error for BTreeMapDbError {}
+
+impl Display for BTreeMapDbError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "BTreeMapDb error")
+    }
+}
+
+impl DBError for BTreeMapDbError {}
+
+#[derive(Clone, Copy, Debug)]
+pub(super) enum Column {
+    Trie,
+    Flat,
+    TrieLog,
+    Meta,
+}
+
+impl Column {
+    pub(super) const ALL: [Column; 4] = [Column::Trie, Column::Flat, Column::TrieLog, Column::Meta];
+
+    pub(super) fn to_byte(self) -> u8 {
+        self as u8
+    }
+
+    pub(super) fn from_byte(byte: u8) -> Option<Self> {
+        Column::ALL.get(byte as usize).copied()
+    }
+}
+
+impl From<&DatabaseKey<'_>> for Column {
+    fn from(key: &DatabaseKey) -> Self {
+        match key {
+            DatabaseKey::Trie(_) => Column::Trie,
+            DatabaseKey::Flat(_) => Column::Flat,
+            DatabaseKey::TrieLog(_) => Column::TrieLog,
+            DatabaseKey::Meta(_) => Column::Meta,
+        }
+    }
+}
+
+#[derive(Clone, Debug)]
+pub(super) enum BatchOperation {
+    Insert(Column, ByteVec, ByteVec),
+    Remove(Column, ByteVec),
+}
+
+/// A batch of changes for a [`BTreeMapDb`], applied in order by `write_batch`.
+#[derive(Clone, Default, Debug)]
+pub struct BTreeMapDbBatch(pub(super) Vec<BatchOperation>);
+
+/// An in-memory database backed by ordered maps.
+///
+/// Unlike [`HashMapDb`](super::HashMapDb), prefix scans are range queries, iteration is in key
+/// order, and writes made through a batch are only visible once the batch is written.
+///
+/// The maps are shared with snapshots, transactions and readers until one of them writes, so
+/// those are cheap to create.
+#[derive(Clone, Default, Debug)]
+pub struct BTreeMapDb<ID: Id> {
+    trie_db: Arc<BTreeMap<ByteVec, ByteVec>>,
+    flat_db: Arc<BTreeMap<ByteVec, ByteVec>>,
+    trie_log_db: Arc<BTreeMap<ByteVec, ByteVec>>,
+    meta_db: Arc<BTreeMap<ByteVec, ByteVec>>,
+    snapshots: BTreeMap<ID, BTreeMapDb<ID>>,
+}
+
+impl<ID: Id> BTreeMapDb<ID> {
+    pub(super) fn get_map(&self, column: Column) -> &BTreeMap<ByteVec, ByteVec> {
+        match column {
+            Column::Trie => &self.trie_db,
+            Column::Flat => &self.flat_db,
+            Column::TrieLog => &self.trie_log_db,
+            Column::Meta => &self.meta_db,
+        }
+    }
+
+    fn get_map_mut(&mut self, column: Column) -> &mut BTreeMap<ByteVec, ByteVec> {
+        match column {
+            Column::Trie => Arc::make_mut(&mut self.trie_db),
+            Column::Flat => Arc::make_mut(&mut self.flat_db),
+            Column::TrieLog => Arc::make_mut(&mut self.trie_log_db),
+            Column::Meta => Arc::make_mut(&mut self.meta_db),
+        }
+    }
+
+    /// Returns the operations that turn `self` into `other`, snapshots excluded.
+    pub(super) fn diff(&self, other: &Self) -> BTreeMapDbBatch {
+        let mut batch = BTreeMapDbBatch::default();
+        for column in Column::ALL {
+            let (ours, theirs) = (self.get_map(column), other.get_map(column));
+            for key in ours.keys().filter(|key| !theirs.contains_key(*key)) {
+                batch.0.push(BatchOperation::Remove(column, key.clone()));
+            }
+            for (key, value) in theirs {
+                if ours.get(key) != Some(value) {
+                    batch
+                        .0
+                        .push(BatchOperation::Insert(column, key.clone(), value.clone()));
+                }
+            }
+        }
+        batch
+    }
+
+    /// Iterates in key order over the entries of the column of `prefix` that start with it.
+    fn prefix_range<'a>(
+        &'a self,
+        prefix: &'a DatabaseKey,
+    ) -> impl Iterator<Item = (&'a ByteVec, &'a ByteVec)> + 'a {
+        let prefix_bytes = prefix.as_slice();
+        self.get_map(prefix.into())
+            .range::<[u8], _>((Bound::Included(prefix_bytes), Bound::Unbounded))
+            .take_while(move |(key, _)| key.starts_with(prefix_bytes))
+    }
+}
+
+impl<ID: Id> BonsaiDatabase for BTreeMapDb<ID> {
+    type Batch = BTreeMapDbBatch;
+    type DatabaseError = BTreeMapDbError;
+
+    fn create_batch(&self) -> Self::Batch {
+        BTreeMapDbBatch::default()
+    }
+
+    fn remove_by_prefix(&mut self, prefix: &DatabaseKey) -> Result<(), Self::DatabaseError> {
+        let keys_to_remove: Vec<ByteVec> = self
+            .prefix_range(prefix)
+            .map(|(key, _)| key.clone())
+            .collect();
+        let db = self.get_map_mut(prefix.into());
+        for key in keys_to_remove {
+            db.remove(&key);
+        }
+        Ok(())
+    }
+
+    fn get(&self, key: &DatabaseKey) -> Result<Option<ByteVec>, Self::DatabaseError> {
+        Ok(self.get_map(key.into()).get(key.as_slice()).cloned())
+    }
+
+    fn get_by_prefix(
+        &self,
+        prefix: &DatabaseKey,
+    ) -> Result<Vec<(ByteVec, ByteVec)>, Self::DatabaseError> {
+        Ok(self
+            .prefix_range(prefix)
+            .map(|(key, value)| (key.clone(), value.clone()))
+            .collect())
+    }
+
+    fn iter_by_prefix<'a>(
+        &'a self,
+        prefix: &DatabaseKey,
+        seek: Option<&[u8]>,
+        upper_bound: Option<&[u8]>,
+    ) -> Result<DatabaseIterator<'a, Self::DatabaseError>, Self::DatabaseError> {
+        let column = Column::from(prefix);
+        let prefix: ByteVec = prefix.as_slice().into();
+        let start = seek
+            .filter(|seek| *seek > prefix.as_slice())
+            .unwrap_or(prefix.as_slice());
+        // `range` panics on an inverted range.
+        if upper_bound.is_some_and(|bound| bound <= start) {
+            return Ok(Box::new(iter::empty()));
+        }
+        let end = upper_bound.map_or(Bound::Unbounded, Bound::Excluded);
+        let range = self
+            .get_map(column)
+            .range::<[u8], _>((Bound::Included(start), end));
+        Ok(Box::new(
+            range
+                .take_while(move |(key, _)| key.starts_with(&prefix))
+                .map(|(key, value)| Ok((key.clone(), value.clone()))),
+        ))
+    }
+
+    fn insert(
+        &mut self,
+        key: &DatabaseKey,
+        value: &[u8],
+        batch: Option<&mut Self::Batch>,
+    ) -> Result<Option<ByteVec>, Self::DatabaseError> {
+        let column = Column::from(key);
+        if let Some(batch) = batch {
+            batch.0.push(BatchOperation::Insert(
+                column,
+                key.as_slice().into(),
+                value.into(),
+            ));
+            Ok(self.get_map(column).get(key.as_slice()).cloned())
+        } else {
+            Ok(self
+                .get_map_mut(column)
+                .insert(key.as_slice().into(), value.into()))
+        }
+    }
+
+    fn remove(
+        &mut self,
+        key: &DatabaseKey,
+        batch: Option<&mut Self::Batch>,
+    ) -> Result<Option<ByteVec>, Self::DatabaseError> {
+        let column = Column::from(key);
+        if let Some(batch) = batch {
+            batch
+                .0
+                .push(BatchOperation::Remove(column, key.as_slice().into()));
+            Ok(self.get_map(column).get(key.as_slice()).cloned())
+        } else {
+            Ok(self.get_map_mut(column).remove(key.as_slice()))
+        }
+    }
+
+    fn contains(&self, key: &DatabaseKey) -> Result<bool, Self::DatabaseError> {
+        Ok(self.get_map(key.into()).contains_key(key.as_slice()))
+    }
+
+    fn write_batch(&mut self, batch: Self::Batch) -> Result<(), Self::DatabaseError> {
+        for operation in batch.0 {
+            match operation {
+                BatchOperation::Insert(column, key, value) => {
+                    self.get_map_mut(column).insert(key, value);
+                }
+                BatchOperation::Remove(column, key) => {
+                    self.get_map_mut(column).remove(&key);
+                }
+            }
+        }
+        Ok(())
+    }
+
+    #[cfg(test)]
+    fn dump_database(&self) {
+        log::debug!("{:?}", self);
+    }
+}
+
+impl<ID: Id> BonsaiPersistentDatabase<ID> for BTreeMapDb<ID> {
+    type DatabaseError = BTreeMapDbError;
+    type Transaction<'a>
+        = BTreeMapDb<ID>
+    where
+        ID: 'a;
+    type Reader = BTreeMapDb<ID>;
+
+    fn snapshot(&mut self, id: ID) {
+        let snapshot = self.transaction_at_head();
+        self.snapshots.insert(id, snapshot);
+    }
+
+    fn remove_snapshots_after(&mut self, id: ID) {
+        self.snapshots.retain(|snap_id, _| *snap_id <= id);
+    }
+
+    fn nearest_snapshot(&self, id: ID) -> Option<ID> {
+        self.snapshots.range(..=id).next_back().map(|(id, _)| *id)
+    }
+
+    fn transaction(&self, id: ID) -> Option<(ID, Self::Transaction<'_>)> {
+        self.snapshots
+            .range(..=id)
+            .next_back()
+            .map(|(id, snapshot)| (*id, snapshot.clone()))
+    }
+
+    fn transaction_at_head(&self) -> Self::Transaction<'_> {
+        BTreeMapDb {
+            trie_db: self.trie_db.clone(),
+            flat_db: self.flat_db.clone(),
+            trie_log_db: self.trie_log_db.clone(),
+            meta_db: self.meta_db.clone(),
+            snapshots: BTreeMap::new(),
+        }
+    }
+
+    fn reader(&self) -> Self::Reader {
+        self.transaction_at_head()
+    }
+
+    fn merge<'a>(&mut self, transaction: Self::Transaction<'a>) -> Result<(), Self::DatabaseError>
+    where
+        ID: 'a,
+    {
+        self.trie_db = transaction.trie_db;
+        self.flat_db = transaction.flat_db;
+        self.trie_log_db = transaction.trie_log_db;
+        self.meta_db = transaction.meta_db;
+        Ok(())
+    }
+}
@@ -0,0 +1,484 @@
+//! A dependency-free, file-backed database.
+//!
+//! The file is an append-only log. After a fixed header, every record is laid out as
+//! `payload length (u32 LE) ++ CRC-32 of payload (u32 LE) ++ payload`, where the payload is one of
+//!
+//! * `PUT ++ column ++ key length (u32 LE) ++ key ++ value`
+//! * `DELETE ++ column ++ key`
+//! * `COMMIT`
+//!
+//! A batch is written as its operations followed by a `COMMIT` record and is only applied when
+//! that record is read back. On open the log is replayed into an in-memory index and anything
+//! after the last complete commit, such as a record torn by a crash, is truncated.
+
+use std::{
+    error::Error as StdError,
+    fmt,
+    fs::{self, File, OpenOptions},
+    io::{self, Read, Write},
+    path::{Path, PathBuf},
+};
+
+use super::btree_map_db::{BTreeMapDb, BTreeMapDbBatch, BTreeMapDbError, BatchOperation, Column};
+use crate::{
+    bonsai_database::{
+        BonsaiDatabase, BonsaiPersistentDatabase, DBError, DatabaseIterator, DatabaseKey,
+    },
+    id::Id,
+    ByteVec,
+};
+
+const MAGIC: &[u8; 8] = b"BONSAIDB";
+const FORMAT_VERSION: u32 = 1;
+const HEADER_LEN: usize = MAGIC.len() + 4;
+const RECORD_HEADER_LEN: usize = 8;
+
+const PUT: u8 = 0;
+const DELETE: u8 = 1;
+const COMMIT: u8 = 2;
+
+#[derive(Debug)]
+pub enum FileDbError {
+    Io(io::Error),
+    /// The file is not a database or was written with an unknown format version.
+    InvalidHeader,
+}
+
+impl From<io::Error> for FileDbError {
+    fn from(err: io::Error) -> Self {
+        Self::Io(err)
+    }
+}
+
+impl fmt::Display for FileDbError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Io(err) => write!(f, "File database IO error: {}", err),
+            Self::InvalidHeader => write!(f, "File database has an invalid header"),
+        }
+    }
+}
+
+impl StdError for FileDbError {
+    fn source(&self) -> Option<&(dyn StdError + 'static)> {
+        match self {
+            Self::Io(err) => Some(err),
+            Self::InvalidHeader => None,
+        }
+    }
+}
+
+impl DBError for FileDbError {}
+
+/// Operations on the in-memory index cannot fail.
+fn index<T>(result: Result<T, BTreeMapDbError>) -> T {
+    match result {
+        Ok(value) => value,
+        Err(_) => unreachable!("BTreeMapDb operations are infallible"),
+    }
+}
+
+fn crc32(bytes: &[u8]) -> u32 {
+    let mut crc = !0u32;
+    for &byte in bytes {
+        crc ^= byte as u32;
+        for _ in 0..8 {
+            crc = (crc >> 1) ^ (0xEDB8_8320 & (crc & 1).wrapping_neg());
+        }
+    }
+    !crc
+}
+
+fn header() -> [u8; HEADER_LEN] {
+    let mut header = [0; HEADER_LEN];
+    header[..MAGIC.len()].copy_from_slice(MAGIC);
+    header[MAGIC.len()..].copy_from_slice(&FORMAT_VERSION.to_le_bytes());
+    header
+}
+
+fn push_record(buf: &mut Vec<u8>, payload: &[u8]) {
+    buf.extend_from_slice(&(payload.len() as u32).to_le_bytes());
+    buf.extend_from_slice(&crc32(payload).to_le_bytes());
+    buf.extend_from_slice(payload);
+}
+
+fn encode_batch(batch: &BTreeMapDbBatch) -> Vec<u8> {
+    let mut buf = Vec::new();
+    let mut payload = Vec::new();
+    for operation in &batch.0 {
+        payload.clear();
+        match operation {
+            BatchOperation::Insert(column, key, value) => {
+                payload.extend_from_slice(&[PUT, column.to_byte()]);
+                payload.extend_from_slice(&(key.len() as u32).to_le_bytes());
+                payload.extend_from_slice(key);
+                payload.extend_from_slice(value);
+            }
+            BatchOperation::Remove(column, key) => {
+                payload.extend_from_slice(&[DELETE, column.to_byte()]);
+                payload.extend_from_slice(key);
+            }
+        }
+        push_record(&mut buf, &payload);
+    }
+    push_record(&mut buf, &[COMMIT]);
+    buf
+}
+
+/// Reads the record starting at `pos`, returning its payload and the position of the next one.
+fn read_record(bytes: &[u8], pos: usize) -> Option<(&[u8], usize)> {
+    let header = bytes.get(pos..pos + RECORD_HEADER_LEN)?;
+    let len = u32::from_le_bytes(header[..4].try_into().unwrap()) as usize;
+    let crc = u32::from_le_bytes(header[4..].try_into().unwrap());
+    let start = pos + RECORD_HEADER_LEN;
+    let payload = bytes.get(start..start.checked_add(len)?)?;
+    (crc32(payload) == crc).then_some((payload, start + len))
+}
+
+fn decode_operation(payload: &[u8]) -> Option<BatchOperation> {
+    let (&tag, rest) = payload.split_first()?;
+    let (&column, rest) = rest.split_first()?;
+    let column = Column::from_byte(column)?;
+    match tag {
+        PUT => {
+            let len = u32::from_le_bytes(rest.get(..4)?.try_into().unwrap()) as usize;
+            let rest = &rest[4..];
+            if rest.len() < len {
+                return None;
+            }
+            let (key, value) = rest.split_at(len);
+            Some(BatchOperation::Insert(column, key.into(), value.into()))
+        }
+        DELETE => Some(BatchOperation::Remove(column, rest.into())),
+        _ => None,
+    }
+}
+
+/// Replays the committed batches of the log into `index` and returns the length of the valid
+/// part of the file.
+fn replay<ID: Id>(bytes: &[u8], index: &mut BTreeMapDb<ID>) -> usize {
+    let mut pos = HEADER_LEN;
+    let mut valid_len = pos;
+    let mut pending = BTreeMapDbBatch::default();
+    while let Some((payload, next)) = read_record(bytes, pos) {
+        if payload == [COMMIT] {
+            self::index(index.write_batch(core::mem::take(&mut pending)));
+            valid_len = next;
+        } else if let Some(operation) = decode_operation(payload) {
+            pending.0.push(operation);
+        } else {
+            break;
+        }
+        pos = next;
+    }
+    valid_len
+}
+
+/// A [`BonsaiDatabase`] stored in a single append-only file, see the [module](self)
+/// documentation for the layout.
+///
+/// Every `write_batch` and every write made without a batch is appended and synced to disk
+/// before returning. All entries are also kept in memory.
+#[derive(Debug)]
+pub struct FileDb<ID: Id> {
+    file: File,
+    path: PathBuf,
+    /// Length of the file up to the last complete commit.
+    len: u64,
+    index: BTreeMapDb<ID>,
+}
+
+impl<ID: Id> FileDb<ID> {
+    /// Opens the database at `path`, creating it if it does not exist, and recovers from an
+    /// interrupted write by truncating the log after its last complete commit.
+    pub fn open(path: impl AsRef<Path>) -> Result<Self, FileDbError> {
+        let path = path.as_ref().to_path_buf();
+        let mut file = OpenOptions::new()
+            .read(true)
+            .append(true)
+            .create(true)
+            .open(&path)?;
+        let mut bytes = Vec::new();
+        file.read_to_end(&mut bytes)?;
+
+        let header = header();
+        if bytes.len() < HEADER_LEN && header.starts_with(&bytes) {
+            // New file, or a crash while writing the header.
+            file.set_len(0)?;
+            file.write_all(&header)?;
+            file.sync_all()?;
+            bytes = header.to_vec();
+        } else if !bytes.starts_with(&header) {
+            return Err(FileDbError::InvalidHeader);
+        }
+
+        let mut index = BTreeMapDb::default();
+        let len = replay(&bytes, &mut index);
+        if len < bytes.len() {
+            log::warn!(
+                "Truncating {} bytes of incomplete records at the end of {}",
+                bytes.len() - len,
+                path.display()
+            );
+            file.set_len(len as u64)?;
+            file.sync_all()?;
+        }
+
+        Ok(Self {
+            file,
+            path,
+            len: len as u64,
+            index,
+        })
+    }
+
+    /// Path of the database file.
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Rewrites the database at `path` so that it only contains live entries, in a single
+    /// commit. The database must not be open.
+    ///
+    /// The compacted log is written to a temporary file which then replaces the original one,
+    /// so an interrupted compaction leaves the database untouched.
+    pub fn compact(path: impl AsRef<Path>) -> Result<(), FileDbError> {
+        let path = path.as_ref();
+        let db = FileDb::<ID>::open(path)?;
+        let batch = BTreeMapDb::default().diff(&db.index);
+        drop(db);
+
+        let mut tmp_path = path.as_os_str().to_owned();
+        tmp_path.push(".compact");
+        let tmp_path = PathBuf::from(tmp_path);
+        {
+            let mut tmp = File::create(&tmp_path)?;
+            tmp.write_all(&header())?;
+            tmp.write_all(&encode_batch(&batch))?;
+            tmp.sync_all()?;
+        }
+        fs::rename(&tmp_path, path)?;
+        if let Some(parent) = path
+            .parent()
+            .filter(|parent| !parent.as_os_str().is_empty())
+        {
+            File::open(parent)?.sync_all()?;
+        }
+        Ok(())
+    }
+
+    fn append(&mut self, batch: &BTreeMapDbBatch) -> Result<(), FileDbError> {
+        let buf = encode_batch(batch);
+        let result = self
+            .file
+            .write_all(&buf)
+            .and_then(|()| self.file.sync_data());
+        if let Err(err) = result {
+            // Do not leave a partial batch in front of the next one.
+            let _ = self.file.set_len(self.len);
+            return Err(err.into());
+        }
+        self.len += buf.len() as u64;
+        Ok(())
+    }
+}
+
+impl<ID: Id> BonsaiDatabase for FileDb<ID> {
+    type Batch = BTreeMapDbBatch;
+    type DatabaseError = FileDbError;
+
+    fn create_batch(&self) -> Self::Batch {
+        BTreeMapDbBatch::default()
+    }
+
+    fn get(&self, key: &DatabaseKey) -> Result<Option<ByteVec>, Self::DatabaseError> {
+        Ok(index(self.index.get(key)))
+    }
+
+    fn get_by_prefix(
+        &self,
+        prefix: &DatabaseKey,
+    ) -> Result<Vec<(ByteVec, ByteVec)>, Self::DatabaseError> {
+        Ok(index(self.index.get_by_prefix(prefix)))
+    }
+
+    fn iter_by_prefix<'a>(
+        &'a self,
+        prefix: &DatabaseKey,
+        seek: Option<&[u8]>,
+        upper_bound: Option<&[u8]>,
+    ) -> Result<DatabaseIterator<'a, Self::DatabaseError>, Self::DatabaseError> {
+        let iter = index(self.index.iter_by_prefix(prefix, seek, upper_bound));
+        Ok(Box::new(iter.map(|entry| Ok(index(entry)))))
+    }
+
+    fn contains(&self, key: &DatabaseKey) -> Result<bool, Self::DatabaseError> {
+        Ok(index(self.index.contains(key)))
+    }
+
+    fn insert(
+        &mut self,
+        key: &DatabaseKey,
+        value: &[u8],
+        batch: Option<&mut Self::Batch>,
+    ) -> Result<Option<ByteVec>, Self::DatabaseError> {
+        if let Some(batch) = batch {
+            Ok(index(self.index.insert(key, value, Some(batch))))
+        } else {
+            let mut batch = self.create_batch();
+            let old_value = index(self.index.insert(key, value, Some(&mut batch)));
+            self.write_batch(batch)?;
+            Ok(old_value)
+        }
+    }
+
+    fn remove(
+        &mut self,
+        key: &DatabaseKey,
+        batch: Option<&mut Self::Batch>,
+    ) -> Result<Option<ByteVec>, Self::DatabaseError> {
+        if let Some(batch) = batch {
+            Ok(index(self.index.remove(key, Some(batch))))
+        } else {
+            let mut batch = self.create_batch();
+            let old_value = index(self.index.remove(key, Some(&mut batch)));
+            self.write_batch(batch)?;
+            Ok(old_value)
+        }
+    }
+
+    fn remove_by_prefix(&mut self, prefix: &DatabaseKey) -> Result<(), Self::DatabaseError> {
+        let mut batch = self.create_batch();
+        for (key, _) in index(self.index.get_by_prefix(prefix)) {
+            batch.0.push(BatchOperation::Remove(prefix.into(), key));
+        }
+        self.write_batch(batch)
+    }
+
+    fn write_batch(&mut self, batch: Self::Batch) -> Result<(), Self::DatabaseError> {
+        if batch.0.is_empty() {
+            return Ok(());
+        }
+        self.append(&batch)?;
+        index(self.index.write_batch(batch));
+        Ok(())
+    }
+
+    #[cfg(test)]
+    fn dump_database(&self) {
+        log::debug!("{:?}", self);
+    }
+}
+
+/// A transactional state of a [`FileDb`]: an in-memory copy of one of its snapshots.
+#[derive(Debug)]
+pub struct FileDbTransaction<ID: Id>(BTreeMapDb<ID>);
+
+impl<ID: Id> BonsaiDatabase for FileDbTransaction<ID> {
+    type Batch = BTreeMapDbBatch;
+    type DatabaseError = FileDbError;
+
+    fn create_batch(&self) -> Self::Batch {
+        BTreeMapDbBatch::default()
+    }
+
+    fn get(&self, key: &DatabaseKey) -> Result<Option<ByteVec>, Self::DatabaseError> {
+        Ok(index(self.0.get(key)))
+    }
+
+    fn get_by_prefix(
+        &self,
+        prefix: &DatabaseKey,
+    ) -> Result<Vec<(ByteVec, ByteVec)>, Self::DatabaseError> {
+        Ok(index(self.0.get_by_prefix(prefix)))
+    }
+
+    fn iter_by_prefix<'a>(
+        &'a self,
+        prefix: &DatabaseKey,
+        seek: Option<&[u8]>,
+        upper_bound: Option<&[u8]>,
+    ) -> Result<DatabaseIterator<'a, Self::DatabaseError>, Self::DatabaseError> {
+        let iter = index(self.0.iter_by_prefix(prefix, seek, upper_bound));
+        Ok(Box::new(iter.map(|entry| Ok(index(entry)))))
+    }
+
+    fn contains(&self, key: &DatabaseKey) -> Result<bool, Self::DatabaseError> {
+        Ok(index(self.0.contains(key)))
+    }
+
+    fn insert(
+        &mut self,
+        key: &DatabaseKey,
+        value: &[u8],
+        batch: Option<&mut Self::Batch>,
+    ) -> Result<Option<ByteVec>, Self::DatabaseError> {
+        Ok(index(self.0.insert(key, value, batch)))
+    }
+
+    fn remove(
+        &mut self,
+        key: &DatabaseKey,
+        batch: Option<&mut Self::Batch>,
+    ) -> Result<Option<ByteVec>, Self::DatabaseError> {
+        Ok(index(self.0.remove(key, batch)))
+    }
+
+    fn remove_by_prefix(&mut self, prefix: &DatabaseKey) -> Result<(), Self::DatabaseError> {
+        Ok(index(self.0.remove_by_prefix(prefix)))
+    }
+
+    fn write_batch(&mut self, batch: Self::Batch) -> Result<(), Self::DatabaseError> {
+        Ok(index(self.0.write_batch(batch)))
+    }
+
+    #[cfg(test)]
+    fn dump_database(&self) {
+        log::debug!("{:?}", self);
+    }
+}
+
+impl<ID: Id> BonsaiPersistentDatabase<ID> for FileDb<ID> {
+    type DatabaseError = FileDbError;
+    type Transaction<'a>
+        = FileDbTransaction<ID>
+    where
+        ID: 'a;
+    type Reader = FileDbTransaction<ID>;
+
+    /// Snapshots are kept in memory, only the id recorded by the storage survives a restart.
+    fn snapshot(&mut self, id: ID) {
+        self.index.snapshot(id);
+    }
+
+    fn remove_snapshots_after(&mut self, id: ID) {
+        self.index.remove_snapshots_after(id);
+    }
+
+    fn nearest_snapshot(&self, id: ID) -> Option<ID> {
+        self.index.nearest_snapshot(id)
+    }
+
+    fn transaction(&self, id: ID) -> Option<(ID, Self::Transaction<'_>)> {
+        self.index
+            .transaction(id)
+            .map(|(id, snapshot)| (id, FileDbTransaction(snapshot)))
+    }
+
+    fn transaction_at_head(&self) -> Self::Transaction<'_> {
+        FileDbTransaction(self.index.transaction_at_head())
+    }
+
+    fn reader(&self) -> Self::Reader {
+        self.transaction_at_head()
+    }
+
+    /// Appends the difference between the current state and the transaction as one commit.
+    fn merge<'a>(&mut self, transaction: Self::Transaction<'a>) -> Result<(), Self::DatabaseError>
+    where
+        ID: 'a,
+    {
+        let batch = self.index.diff(&transaction.0);
+        self.write_batch(batch)
+    }
+}
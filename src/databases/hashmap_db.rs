@@ -1,7 +1,7 @@
 use crate::{
-    bonsai_database::{BonsaiPersistentDatabase, DBError},
+    bonsai_database::{in_bounds, BonsaiPersistentDatabase, DBError, DatabaseIterator},
     id::Id,
-    BTreeMap, BonsaiDatabase, HashMap, Vec,
+    Arc, BTreeMap, BonsaiDatabase, Box, HashMap, Vec,
 };
 use crate::{ByteVec, DatabaseKey};
 use core::{fmt, fmt::Display};
@@ -20,11 +20,16 @@ impl Display for HashMapDbError {
 
 impl DBError for HashMapDbError {}
 
+/// An in-memory database backed by hash maps.
+///
+/// The maps are shared with snapshots, transactions and readers until one of them writes, so
+/// those are cheap to create.
 #[derive(Clone, Default, Debug)]
 pub struct HashMapDb<ID: Id> {
-    trie_db: HashMap<ByteVec, ByteVec>,
-    flat_db: HashMap<ByteVec, ByteVec>,
-    trie_log_db: HashMap<ByteVec, ByteVec>,
+    trie_db: Arc<HashMap<ByteVec, ByteVec>>,
+    flat_db: Arc<HashMap<ByteVec, ByteVec>>,
+    trie_log_db: Arc<HashMap<ByteVec, ByteVec>>,
+    meta_db: Arc<HashMap<ByteVec, ByteVec>>,
     snapshots: BTreeMap<ID, HashMapDb<ID>>,
 }
 
@@ -34,20 +39,22 @@ impl<ID: Id> HashMapDb<ID> {
             DatabaseKey::Trie(_) => &self.trie_db,
             DatabaseKey::Flat(_) => &self.flat_db,
             DatabaseKey::TrieLog(_) => &self.trie_log_db,
+            DatabaseKey::Meta(_) => &self.meta_db,
         }
     }
     fn get_map_mut(&mut self, key: &DatabaseKey) -> &mut HashMap<ByteVec, ByteVec> {
         match key {
-            DatabaseKey::Trie(_) => &mut self.trie_db,
-            DatabaseKey::Flat(_) => &mut self.flat_db,
-            DatabaseKey::TrieLog(_) => &mut self.trie_log_db,
+            DatabaseKey::Trie(_) => Arc::make_mut(&mut self.trie_db),
+            DatabaseKey::Flat(_) => Arc::make_mut(&mut self.flat_db),
+            DatabaseKey::TrieLog(_) => Arc::make_mut(&mut self.trie_log_db),
+            DatabaseKey::Meta(_) => Arc::make_mut(&mut self.meta_db),
         }
     }
 
     #[cfg(test)]
     pub(crate) fn assert_empty(&self) {
-        assert_eq!(self.trie_db, [].into());
-        assert_eq!(self.flat_db, [].into());
+        assert_eq!(*self.trie_db, [].into());
+        assert_eq!(*self.flat_db, [].into());
     }
 }
 
@@ -90,6 +97,28 @@ impl<ID: Id> BonsaiDatabase for HashMapDb<ID> {
         Ok(result)
     }
 
+    fn iter_by_prefix<'a>(
+        &'a self,
+        prefix: &DatabaseKey,
+        seek: Option<&[u8]>,
+        upper_bound: Option<&[u8]>,
+    ) -> Result<DatabaseIterator<'a, Self::DatabaseError>, Self::DatabaseError> {
+        // The map is unordered, so the matching entries are sorted before being returned.
+        let mut entries: Vec<(&ByteVec, &ByteVec)> = self
+            .get_map(prefix)
+            .iter()
+            .filter(|(key, _)| {
+                key.starts_with(prefix.as_slice()) && in_bounds(key, seek, upper_bound)
+            })
+            .collect();
+        entries.sort_unstable_by(|(a, _), (b, _)| a.cmp(b));
+        Ok(Box::new(
+            entries
+                .into_iter()
+                .map(|(key, value)| Ok((key.clone(), value.clone()))),
+        ))
+    }
+
     fn insert(
         &mut self,
         key: &DatabaseKey,
@@ -130,17 +159,40 @@ impl<ID: Id> BonsaiPersistentDatabase<ID> for HashMapDb<ID> {
         = HashMapDb<ID>
     where
         ID: 'a;
+    type Reader = HashMapDb<ID>;
     fn snapshot(&mut self, id: ID) {
         self.snapshots.insert(id, self.clone());
     }
 
+    fn remove_snapshots_after(&mut self, id: ID) {
+        self.snapshots.retain(|snap_id, _| *snap_id <= id);
+    }
+
+    fn nearest_snapshot(&self, id: ID) -> Option<ID> {
+        self.snapshots.range(..=id).next_back().map(|(id, _)| *id)
+    }
+
     fn transaction(&self, id: ID) -> Option<(ID, Self::Transaction<'_>)> {
         self.snapshots
-            .range(..&id)
-            .next()
+            .range(..=id)
+            .next_back()
             .map(|(id, snapshot)| (*id, snapshot.clone()))
     }
 
+    fn transaction_at_head(&self) -> Self::Transaction<'_> {
+        HashMapDb {
+            trie_db: self.trie_db.clone(),
+            flat_db: self.flat_db.clone(),
+            trie_log_db: self.trie_log_db.clone(),
+            meta_db: self.meta_db.clone(),
+            snapshots: BTreeMap::new(),
+        }
+    }
+
+    fn reader(&self) -> Self::Reader {
+        self.transaction_at_head()
+    }
+
     fn merge<'a>(&mut self, transaction: Self::Transaction<'a>) -> Result<(), Self::DatabaseError>
     where
         ID: 'a,
@@ -148,6 +200,7 @@ impl<ID: Id> BonsaiPersistentDatabase<ID> for HashMapDb<ID> {
         self.trie_db = transaction.trie_db;
         self.flat_db = transaction.flat_db;
         self.trie_log_db = transaction.trie_log_db;
+        self.meta_db = transaction.meta_db;
         Ok(())
     }
 }
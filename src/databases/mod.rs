@@ -1,4 +1,12 @@
 #![allow(dead_code)]
+mod btree_map_db;
+pub use btree_map_db::{BTreeMapDb, BTreeMapDbBatch, BTreeMapDbError};
+
+#[cfg(feature = "std")]
+mod file_db;
+#[cfg(feature = "std")]
+pub use file_db::{FileDb, FileDbError, FileDbTransaction};
+
 mod hashmap_db;
 pub use hashmap_db::HashMapDb;
 
@@ -6,4 +14,8 @@ pub use hashmap_db::HashMapDb;
 mod rocks_db;
 
 #[cfg(feature = "rocksdb")]
-pub use rocks_db::{create_rocks_db, RocksDB, RocksDBBatch, RocksDBConfig, RocksDBTransaction};
+pub use rocks_db::{
+    create_rocks_db, open_rocks_db, open_rocks_db_read_only, RocksDB, RocksDBBatch,
+    RocksDBColumnFamilies, RocksDBColumnFamilyOptions, RocksDBConfig, RocksDBError,
+    RocksDBOpenOptions, RocksDBPrefixExtractor, RocksDBReadOnly, RocksDBReader, RocksDBTransaction,
+};
@@ -6,14 +6,18 @@ use std::{
 };
 
 use rocksdb::{
-    ColumnFamilyDescriptor, ColumnFamilyRef, Direction, Error, IteratorMode, MultiThreaded,
-    OptimisticTransactionDB, OptimisticTransactionOptions, Options, ReadOptions,
-    SnapshotWithThreadMode, Transaction, WriteBatchWithTransaction, WriteOptions,
+    BlockBasedOptions, Cache, ColumnFamilyDescriptor, ColumnFamilyRef, DBCompressionType,
+    DBWithThreadMode, Direction, Error, IteratorMode, MultiThreaded, OptimisticTransactionDB,
+    OptimisticTransactionOptions, Options, ReadOptions, SliceTransform, SnapshotWithThreadMode,
+    Transaction, WriteBatchWithTransaction, WriteOptions,
 };
 
 use crate::{
-    bonsai_database::{BonsaiDatabase, BonsaiPersistentDatabase, DBError, DatabaseKey},
+    bonsai_database::{
+        BonsaiDatabase, BonsaiPersistentDatabase, DBError, DatabaseIterator, DatabaseKey,
+    },
     id::Id,
+    trie::trie_db::split_identifier_prefix,
     ByteVec,
 };
 use log::trace;
@@ -21,32 +25,308 @@ use log::trace;
 const TRIE_LOG_CF: &str = "trie_log";
 const TRIE_CF: &str = "trie";
 const FLAT_CF: &str = "flat";
+const META_CF: &str = "meta";
 
 const CF_ERROR: &str = "critical: rocksdb column family operation failed";
 
-/// Creates a new RocksDB database from the given path
-pub fn create_rocks_db(path: impl AsRef<Path>) -> Result<OptimisticTransactionDB, Error> {
-    // Delete folder content
-    if path.as_ref().exists() {
-        std::fs::remove_dir_all(path.as_ref()).unwrap();
+/// Names of the column families holding one bonsai storage.
+///
+/// Several storages can share a database as long as they use different column families, see
+/// [`RocksDBColumnFamilies::with_namespace`].
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct RocksDBColumnFamilies {
+    pub trie: String,
+    pub flat: String,
+    pub trie_log: String,
+    pub meta: String,
+}
+
+impl Default for RocksDBColumnFamilies {
+    fn default() -> Self {
+        Self {
+            trie: TRIE_CF.to_string(),
+            flat: FLAT_CF.to_string(),
+            trie_log: TRIE_LOG_CF.to_string(),
+            meta: META_CF.to_string(),
+        }
+    }
+}
+
+impl RocksDBColumnFamilies {
+    /// Column families prefixed with `namespace`, e.g. `contract_trie` for `contract`.
+    pub fn with_namespace(namespace: &str) -> Self {
+        Self {
+            trie: format!("{namespace}_{TRIE_CF}"),
+            flat: format!("{namespace}_{FLAT_CF}"),
+            trie_log: format!("{namespace}_{TRIE_LOG_CF}"),
+            meta: format!("{namespace}_{META_CF}"),
+        }
     }
-    std::fs::create_dir_all(path.as_ref()).unwrap();
+
+    /// Column family holding the given key.
+    pub fn name(&self, key: &DatabaseKey) -> &str {
+        match key {
+            DatabaseKey::Trie(_) => &self.trie,
+            DatabaseKey::Flat(_) => &self.flat,
+            DatabaseKey::TrieLog(_) => &self.trie_log,
+            DatabaseKey::Meta(_) => &self.meta,
+        }
+    }
+
+    fn all(&self) -> [&str; 4] {
+        [&self.trie, &self.flat, &self.trie_log, &self.meta]
+    }
+
+    /// Column families that exist in every storage created by this crate; `meta` was added later.
+    fn required(&self) -> [&str; 3] {
+        [&self.trie, &self.flat, &self.trie_log]
+    }
+}
+
+/// How RocksDB extracts the key prefix used by prefix bloom filters and prefix seeks.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum RocksDBPrefixExtractor {
+    /// The length-prefixed trie identifier at the start of trie and flat keys.
+    Identifier,
+    /// The first bytes of the key, e.g. the commit id at the start of trie-log keys.
+    Fixed(usize),
+}
+
+impl RocksDBPrefixExtractor {
+    /// Length of the prefix extracted from `key`, `None` if the key is too short to have one.
+    fn prefix_len(self, key: &[u8]) -> Option<usize> {
+        match self {
+            Self::Identifier => identifier_prefix_len(key),
+            Self::Fixed(len) => (key.len() >= len).then_some(len),
+        }
+    }
+
+    fn slice_transform(self) -> SliceTransform {
+        match self {
+            Self::Identifier => SliceTransform::create(
+                "bonsai.identifier",
+                identifier_transform,
+                Some(identifier_in_domain),
+            ),
+            Self::Fixed(len) => SliceTransform::create_fixed_prefix(len),
+        }
+    }
+}
+
+fn identifier_prefix_len(key: &[u8]) -> Option<usize> {
+    split_identifier_prefix(key).map(|(_, rest)| key.len() - rest.len())
+}
+
+fn identifier_transform(key: &[u8]) -> &[u8] {
+    &key[..identifier_prefix_len(key).unwrap_or(key.len())]
+}
+
+fn identifier_in_domain(key: &[u8]) -> bool {
+    identifier_prefix_len(key).is_some()
+}
+
+/// RocksDB options of one column family.
+#[derive(Clone, Debug)]
+pub struct RocksDBColumnFamilyOptions {
+    /// Prefix used by prefix bloom filters and by `get_by_prefix` scans.
+    pub prefix_extractor: Option<RocksDBPrefixExtractor>,
+    /// Bits per key of the bloom filter, `None` to disable it.
+    pub bloom_filter_bits: Option<f64>,
+    pub compression: DBCompressionType,
+}
+
+impl RocksDBColumnFamilyOptions {
+    /// Options for trie and flat keys: point lookups and scans over one identifier.
+    pub fn trie() -> Self {
+        Self {
+            prefix_extractor: Some(RocksDBPrefixExtractor::Identifier),
+            bloom_filter_bits: Some(10.0),
+            compression: DBCompressionType::Lz4,
+        }
+    }
+
+    /// Options for trie-log keys: scans over one commit id, rarely read.
+    ///
+    /// The prefix length matches 8-byte ids such as [`BasicId`](crate::id::BasicId); scans stay
+    /// correct with other ids but may not benefit from prefix seeks.
+    pub fn trie_log() -> Self {
+        Self {
+            prefix_extractor: Some(RocksDBPrefixExtractor::Fixed(8)),
+            bloom_filter_bits: None,
+            compression: DBCompressionType::Zstd,
+        }
+    }
+
+    fn to_options(&self, cache: &Cache) -> Options {
+        let mut block_options = BlockBasedOptions::default();
+        block_options.set_block_cache(cache);
+        block_options.set_cache_index_and_filter_blocks(true);
+        if let Some(bits) = self.bloom_filter_bits {
+            block_options.set_bloom_filter(bits, false);
+        }
+
+        let mut options = Options::default();
+        options.set_block_based_table_factory(&block_options);
+        options.set_compression_type(self.compression);
+        if let Some(prefix_extractor) = self.prefix_extractor {
+            options.set_prefix_extractor(prefix_extractor.slice_transform());
+        }
+        options
+    }
+}
+
+/// Options for [`open_rocks_db`].
+#[derive(Clone, Debug)]
+pub struct RocksDBOpenOptions {
+    /// Create the database, and the column families of storages it does not hold yet, if they
+    /// are missing.
+    pub create_if_missing: bool,
+    /// Delete everything at the given path before opening the database.
+    pub wipe: bool,
+    /// Configurations of every bonsai storage kept in the database, their column families are
+    /// opened with the options they specify.
+    pub storages: Vec<RocksDBConfig>,
+    /// Size in bytes of the block cache shared by all column families.
+    pub block_cache_size: usize,
+}
+
+impl Default for RocksDBOpenOptions {
+    fn default() -> Self {
+        Self {
+            create_if_missing: true,
+            wipe: false,
+            storages: vec![RocksDBConfig::default()],
+            block_cache_size: 256 << 20,
+        }
+    }
+}
+
+/// Opens the RocksDB database at the given path, creating it if allowed by `options`.
+///
+/// For every storage of `options`, an existing database must contain either all of its `trie`,
+/// `flat` and `trie_log` column families or, if `create_if_missing` is set, none of them. The
+/// `meta` column family is added to storages created by older versions of this crate. Column
+/// families that belong to none of the storages are opened with default options.
+pub fn open_rocks_db(
+    path: impl AsRef<Path>,
+    options: &RocksDBOpenOptions,
+) -> Result<OptimisticTransactionDB, RocksDBError> {
+    let path = path.as_ref();
+    if options.wipe && path.exists() {
+        std::fs::remove_dir_all(path)?;
+    }
+
+    let mut existing = Vec::new();
+    // RocksDB writes a `CURRENT` file when it creates a database.
+    if path.join("CURRENT").exists() {
+        existing = OptimisticTransactionDB::<MultiThreaded>::list_cf(&Options::default(), path)?;
+        for storage in &options.storages {
+            let mut missing = storage
+                .column_families
+                .required()
+                .into_iter()
+                .filter(|cf| !existing.iter().any(|name| name == cf))
+                .peekable();
+            let Some(first_missing) = missing.peek().copied() else {
+                continue;
+            };
+            if !options.create_if_missing
+                || missing.count() < storage.column_families.required().len()
+            {
+                return Err(RocksDBError::MissingColumnFamily(first_missing.to_string()));
+            }
+        }
+    } else if options.create_if_missing {
+        std::fs::create_dir_all(path)?;
+    }
+
+    let descriptors = column_families(options, &existing)
+        .into_iter()
+        .map(|(name, cf_options)| ColumnFamilyDescriptor::new(name, cf_options));
+
     let mut opts = Options::default();
-    opts.create_if_missing(true);
+    opts.create_if_missing(options.create_if_missing);
     opts.create_missing_column_families(true);
-    let db = OptimisticTransactionDB::<MultiThreaded>::open_cf_descriptors(
-        &opts,
+    let db =
+        OptimisticTransactionDB::<MultiThreaded>::open_cf_descriptors(&opts, path, descriptors)?;
+
+    Ok(db)
+}
+
+/// Opens the RocksDB database at the given path read-only, e.g. to inspect it while another
+/// process writes to it. Use [`RocksDBReadOnly`] to read storages from it.
+///
+/// Every storage of `options` must have its `trie`, `flat` and `trie_log` column families in the
+/// database, `create_if_missing` and `wipe` are ignored. Nothing is written, so the `meta` column
+/// family of storages created by older versions of this crate stays missing.
+pub fn open_rocks_db_read_only(
+    path: impl AsRef<Path>,
+    options: &RocksDBOpenOptions,
+) -> Result<DBWithThreadMode<MultiThreaded>, RocksDBError> {
+    let path = path.as_ref();
+    let existing = DBWithThreadMode::<MultiThreaded>::list_cf(&Options::default(), path)?;
+    for storage in &options.storages {
+        if let Some(missing) = storage
+            .column_families
+            .required()
+            .into_iter()
+            .find(|cf| !existing.iter().any(|name| name == cf))
+        {
+            return Err(RocksDBError::MissingColumnFamily(missing.to_string()));
+        }
+    }
+
+    let descriptors = column_families(options, &existing)
+        .into_iter()
+        .filter(|(name, _)| existing.contains(name))
+        .map(|(name, cf_options)| ColumnFamilyDescriptor::new(name, cf_options));
+    let db = DBWithThreadMode::<MultiThreaded>::open_cf_descriptors_read_only(
+        &Options::default(),
         path,
-        vec![
-            ColumnFamilyDescriptor::new(TRIE_LOG_CF, Options::default()),
-            ColumnFamilyDescriptor::new(TRIE_CF, Options::default()),
-            ColumnFamilyDescriptor::new(FLAT_CF, Options::default()),
-        ],
+        descriptors,
+        false,
     )?;
 
     Ok(db)
 }
 
+/// Column families of the storages of `options` with the options they specify, followed by the
+/// `existing` ones that belong to none of the storages, with default options.
+fn column_families(options: &RocksDBOpenOptions, existing: &[String]) -> Vec<(String, Options)> {
+    let cache = Cache::new_lru_cache(options.block_cache_size);
+    let mut column_families = Vec::new();
+    for storage in &options.storages {
+        let names = &storage.column_families;
+        column_families.extend([
+            (names.trie.clone(), storage.trie.to_options(&cache)),
+            (names.flat.clone(), storage.flat.to_options(&cache)),
+            (names.trie_log.clone(), storage.trie_log.to_options(&cache)),
+            (names.meta.clone(), Options::default()),
+        ]);
+    }
+    for name in existing {
+        if name != rocksdb::DEFAULT_COLUMN_FAMILY_NAME
+            && !column_families.iter().any(|(known, _)| known == name)
+        {
+            column_families.push((name.clone(), Options::default()));
+        }
+    }
+    column_families
+}
+
+/// Creates a new, empty RocksDB database at the given path, deleting any existing one.
+///
+/// Use [`open_rocks_db`] to reopen a database.
+pub fn create_rocks_db(path: impl AsRef<Path>) -> Result<OptimisticTransactionDB, RocksDBError> {
+    open_rocks_db(
+        path,
+        &RocksDBOpenOptions {
+            wipe: true,
+            ..Default::default()
+        },
+    )
+}
+
 /// A struct that implements the `BonsaiDatabase` trait using RocksDB as the underlying database
 pub struct RocksDB<'db, ID: Id> {
     db: &'db OptimisticTransactionDB<MultiThreaded>,
@@ -57,32 +337,18 @@ pub struct RocksDB<'db, ID: Id> {
 impl<'db, ID: Id> fmt::Debug for RocksDB<'db, ID> {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         writeln!(f, "ROCKSDB_DATABASE_DUMP {{")?;
-        let handle_trie = self.db.cf_handle(TRIE_CF).expect(CF_ERROR);
-        let handle_flat = self.db.cf_handle(FLAT_CF).expect(CF_ERROR);
-        let handle_trie_log = self.db.cf_handle(TRIE_LOG_CF).expect(CF_ERROR);
-        let mut iter = self.db.raw_iterator_cf(&handle_trie);
-        iter.seek_to_first();
-        while iter.valid() {
-            let key = iter.key().unwrap();
-            let value = iter.value().unwrap();
-            writeln!(f, "{:?} => {:?},", key, value)?;
-            iter.next();
-        }
-        let mut iter = self.db.raw_iterator_cf(&handle_flat);
-        iter.seek_to_first();
-        while iter.valid() {
-            let key = iter.key().unwrap();
-            let value = iter.value().unwrap();
-            writeln!(f, "{:?} => {:?},", key, value)?;
-            iter.next();
-        }
-        let mut iter = self.db.raw_iterator_cf(&handle_trie_log);
-        iter.seek_to_first();
-        while iter.valid() {
-            let key = iter.key().unwrap();
-            let value = iter.value().unwrap();
-            writeln!(f, "{:?} => {:?},", key, value)?;
-            iter.next();
+        for cf in self.config.column_families.all() {
+            let handle = self.db.cf_handle(cf).expect(CF_ERROR);
+            let mut iter = self
+                .db
+                .raw_iterator_cf_opt(&handle, total_order_read_options());
+            iter.seek_to_first();
+            while iter.valid() {
+                let key = iter.key().unwrap();
+                let value = iter.value().unwrap();
+                writeln!(f, "{:?} => {:?},", key, value)?;
+                iter.next();
+            }
         }
         write!(f, "}}")?;
         Ok(())
@@ -90,19 +356,114 @@ impl<'db, ID: Id> fmt::Debug for RocksDB<'db, ID> {
 }
 
 /// Configuration for RocksDB database
+#[derive(Clone, Debug)]
 pub struct RocksDBConfig {
     /// Maximum number of snapshots kept in database
     pub max_saved_snapshots: Option<usize>,
+    /// Column families used by this storage, they must have been opened with the database
+    pub column_families: RocksDBColumnFamilies,
+    /// Options of the trie column family, applied by [`open_rocks_db`]
+    pub trie: RocksDBColumnFamilyOptions,
+    /// Options of the flat column family, applied by [`open_rocks_db`]
+    pub flat: RocksDBColumnFamilyOptions,
+    /// Options of the trie-log column family, applied by [`open_rocks_db`]
+    pub trie_log: RocksDBColumnFamilyOptions,
+    /// Sync the write-ahead log to disk on every write
+    pub sync_writes: bool,
+    /// Skip the write-ahead log, writes since the last flush are lost on a crash
+    pub disable_wal: bool,
 }
 
 impl Default for RocksDBConfig {
     fn default() -> Self {
         Self {
             max_saved_snapshots: Some(100),
+            column_families: RocksDBColumnFamilies::default(),
+            trie: RocksDBColumnFamilyOptions::trie(),
+            flat: RocksDBColumnFamilyOptions::trie(),
+            trie_log: RocksDBColumnFamilyOptions::trie_log(),
+            sync_writes: false,
+            disable_wal: false,
         }
     }
 }
 
+impl RocksDBConfig {
+    fn prefix_extractor(&self, key: &DatabaseKey) -> Option<RocksDBPrefixExtractor> {
+        match key {
+            DatabaseKey::Trie(_) => self.trie.prefix_extractor,
+            DatabaseKey::Flat(_) => self.flat.prefix_extractor,
+            DatabaseKey::TrieLog(_) => self.trie_log.prefix_extractor,
+            DatabaseKey::Meta(_) => None,
+        }
+    }
+
+    /// Read options for a scan over the keys starting with `prefix`.
+    ///
+    /// Prefix seeks only return correct results when all those keys have the same extracted
+    /// prefix, other scans fall back to a total order seek.
+    fn scan_read_options(&self, prefix: &DatabaseKey) -> ReadOptions {
+        match self.prefix_extractor(prefix) {
+            Some(extractor) if extractor.prefix_len(prefix.as_slice()).is_some() => {
+                let mut read_options = ReadOptions::default();
+                read_options.set_prefix_same_as_start(true);
+                read_options
+            }
+            _ => total_order_read_options(),
+        }
+    }
+
+    /// Read options and start of an iteration over the keys starting with `prefix`, from `seek`
+    /// and up to `upper_bound`.
+    fn iter_options<'k>(
+        &self,
+        prefix: &'k DatabaseKey,
+        seek: Option<&'k [u8]>,
+        upper_bound: Option<&[u8]>,
+    ) -> (ReadOptions, IteratorMode<'k>) {
+        let mut read_options = self.scan_read_options(prefix);
+        if let Some(upper_bound) = upper_bound {
+            read_options.set_iterate_upper_bound(upper_bound);
+        }
+        let start = seek
+            .filter(|seek| *seek > prefix.as_slice())
+            .unwrap_or(prefix.as_slice());
+        (read_options, IteratorMode::From(start, Direction::Forward))
+    }
+
+    fn write_options(&self) -> WriteOptions {
+        let mut write_options = WriteOptions::default();
+        write_options.set_sync(self.sync_writes);
+        write_options.disable_wal(self.disable_wal);
+        write_options
+    }
+}
+
+fn total_order_read_options() -> ReadOptions {
+    let mut read_options = ReadOptions::default();
+    read_options.set_total_order_seek(true);
+    read_options
+}
+
+/// Stops a RocksDB iteration at the first key outside of `prefix`, keeping the errors.
+fn prefix_iterator<'a>(
+    iter: impl Iterator<Item = Result<(Box<[u8]>, Box<[u8]>), Error>> + 'a,
+    prefix: &DatabaseKey,
+) -> DatabaseIterator<'a, RocksDBError> {
+    let prefix: ByteVec = prefix.as_slice().into();
+    Box::new(
+        iter.map(|entry| {
+            entry
+                .map(|(key, value)| ((*key).into(), (*value).into()))
+                .map_err(RocksDBError::from)
+        })
+        .take_while(move |entry: &Result<(ByteVec, ByteVec), _>| match entry {
+            Ok((key, _)) => key.starts_with(&prefix),
+            Err(_) => true,
+        }),
+    )
+}
+
 impl<'db, ID: Id> RocksDB<'db, ID> {
     /// Creates a new RocksDB wrapper from the given RocksDB database
     pub fn new(db: &'db OptimisticTransactionDB, config: RocksDBConfig) -> Self {
@@ -121,6 +482,9 @@ pub type RocksDBBatch = WriteBatchWithTransaction<true>;
 #[derive(Debug)]
 pub enum RocksDBError {
     RocksDB(Error),
+    Io(std::io::Error),
+    /// The database exists but lacks one of the column families used by the trie.
+    MissingColumnFamily(String),
     Custom(String),
 }
 
@@ -130,10 +494,20 @@ impl From<Error> for RocksDBError {
     }
 }
 
+impl From<std::io::Error> for RocksDBError {
+    fn from(err: std::io::Error) -> Self {
+        Self::Io(err)
+    }
+}
+
 impl fmt::Display for RocksDBError {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         match self {
             Self::RocksDB(err) => write!(f, "RocksDB error: {}", err),
+            Self::Io(err) => write!(f, "RocksDB IO error: {}", err),
+            Self::MissingColumnFamily(cf) => {
+                write!(f, "RocksDB database has no column family {:?}", cf)
+            }
             Self::Custom(err) => write!(f, "RocksDB error in trie: {}", err),
         }
     }
@@ -145,24 +519,32 @@ impl StdError for RocksDBError {
     fn cause(&self) -> Option<&dyn StdError> {
         match self {
             Self::RocksDB(err) => Some(err),
-            Self::Custom(_) => None,
+            Self::Io(err) => Some(err),
+            Self::MissingColumnFamily(_) | Self::Custom(_) => None,
         }
     }
 
     fn source(&self) -> Option<&(dyn StdError + 'static)> {
         match self {
             Self::RocksDB(err) => Some(err),
-            Self::Custom(_) => None,
+            Self::Io(err) => Some(err),
+            Self::MissingColumnFamily(_) | Self::Custom(_) => None,
         }
     }
 }
 
-impl DatabaseKey<'_> {
-    fn get_cf(&self) -> &'static str {
+/// Snapshot a [`RocksDBTransaction`] reads from: one of the snapshots of the database, or one
+/// taken for the transaction.
+enum TransactionSnapshot<'a> {
+    Shared(&'a SnapshotWithThreadMode<'a, OptimisticTransactionDB>),
+    Owned(SnapshotWithThreadMode<'a, OptimisticTransactionDB>),
+}
+
+impl<'a> TransactionSnapshot<'a> {
+    fn get(&self) -> &SnapshotWithThreadMode<'a, OptimisticTransactionDB> {
         match self {
-            DatabaseKey::Trie(_) => TRIE_CF,
-            DatabaseKey::Flat(_) => FLAT_CF,
-            DatabaseKey::TrieLog(_) => TRIE_LOG_CF,
+            Self::Shared(snapshot) => snapshot,
+            Self::Owned(snapshot) => snapshot,
         }
     }
 }
@@ -170,7 +552,10 @@ impl DatabaseKey<'_> {
 pub struct RocksDBTransaction<'a> {
     txn: Transaction<'a, OptimisticTransactionDB>,
     read_options: ReadOptions,
-    column_families: HashMap<String, ColumnFamilyRef<'a>>,
+    /// Snapshot read through `read_options`, also set on the read options of the scans.
+    snapshot: TransactionSnapshot<'a>,
+    config: RocksDBConfig,
+    handles: HashMap<String, ColumnFamilyRef<'a>>,
 }
 
 impl<'a> fmt::Debug for RocksDBTransaction<'a> {
@@ -202,50 +587,89 @@ where
         batch: Option<&mut Self::Batch>,
     ) -> Result<Option<ByteVec>, Self::DatabaseError> {
         trace!("Inserting into RocksDB: {:?} {:?}", key, value);
-        let handle_cf = self.db.cf_handle(key.get_cf()).expect(CF_ERROR);
+        let handle_cf = self
+            .db
+            .cf_handle(self.config.column_families.name(key))
+            .expect(CF_ERROR);
         let old_value = self.db.get_cf(&handle_cf, key.as_slice())?;
         if let Some(batch) = batch {
             batch.put_cf(&handle_cf, key.as_slice(), value);
         } else {
-            self.db.put_cf(&handle_cf, key.as_slice(), value)?;
+            self.db.put_cf_opt(
+                &handle_cf,
+                key.as_slice(),
+                value,
+                &self.config.write_options(),
+            )?;
         }
         Ok(old_value.map(Into::into))
     }
 
     fn get(&self, key: &DatabaseKey) -> Result<Option<ByteVec>, Self::DatabaseError> {
         trace!("Getting from RocksDB: {:?}", key);
-        let handle = self.db.cf_handle(key.get_cf()).expect(CF_ERROR);
+        let handle = self
+            .db
+            .cf_handle(self.config.column_families.name(key))
+            .expect(CF_ERROR);
         Ok(self.db.get_cf(&handle, key.as_slice())?.map(Into::into))
     }
 
+    fn get_many(&self, keys: &[DatabaseKey]) -> Result<Vec<Option<ByteVec>>, Self::DatabaseError> {
+        trace!("Getting {} keys from RocksDB", keys.len());
+        let handles: Vec<_> = keys
+            .iter()
+            .map(|key| {
+                self.db
+                    .cf_handle(self.config.column_families.name(key))
+                    .expect(CF_ERROR)
+            })
+            .collect();
+        self.db
+            .multi_get_cf(
+                handles
+                    .iter()
+                    .zip(keys)
+                    .map(|(handle, key)| (handle, key.as_slice())),
+            )
+            .into_iter()
+            .map(|value| {
+                value
+                    .map(|value| value.map(Into::into))
+                    .map_err(RocksDBError::from)
+            })
+            .collect()
+    }
+
     fn get_by_prefix(
         &self,
         prefix: &DatabaseKey,
     ) -> Result<Vec<(ByteVec, ByteVec)>, Self::DatabaseError> {
         trace!("Getting from RocksDB: {:?}", prefix);
-        let handle = self.db.cf_handle(prefix.get_cf()).expect(CF_ERROR);
-        let iter = self.db.iterator_cf(
-            &handle,
-            IteratorMode::From(prefix.as_slice(), Direction::Forward),
-        );
-        Ok(iter
-            .map_while(|kv| {
-                if let Ok((key, value)) = kv {
-                    if key.starts_with(prefix.as_slice()) {
-                        Some(((*key).into(), (*value).into()))
-                    } else {
-                        None
-                    }
-                } else {
-                    None
-                }
-            })
-            .collect())
+        self.iter_by_prefix(prefix, None, None)?.collect()
+    }
+
+    fn iter_by_prefix<'a>(
+        &'a self,
+        prefix: &DatabaseKey,
+        seek: Option<&[u8]>,
+        upper_bound: Option<&[u8]>,
+    ) -> Result<DatabaseIterator<'a, Self::DatabaseError>, Self::DatabaseError> {
+        trace!("Iterating over RocksDB: {:?}", prefix);
+        let handle = self
+            .db
+            .cf_handle(self.config.column_families.name(prefix))
+            .expect(CF_ERROR);
+        let (read_options, mode) = self.config.iter_options(prefix, seek, upper_bound);
+        let iter = self.db.iterator_cf_opt(&handle, read_options, mode);
+        Ok(prefix_iterator(iter, prefix))
     }
 
     fn contains(&self, key: &DatabaseKey) -> Result<bool, Self::DatabaseError> {
         trace!("Checking if RocksDB contains: {:?}", key);
-        let handle = self.db.cf_handle(key.get_cf()).expect(CF_ERROR);
+        let handle = self
+            .db
+            .cf_handle(self.config.column_families.name(key))
+            .expect(CF_ERROR);
         Ok(self
             .db
             .get_cf(&handle, key.as_slice())
@@ -258,41 +682,37 @@ where
         batch: Option<&mut Self::Batch>,
     ) -> Result<Option<ByteVec>, Self::DatabaseError> {
         trace!("Removing from RocksDB: {:?}", key);
-        let handle = self.db.cf_handle(key.get_cf()).expect(CF_ERROR);
+        let handle = self
+            .db
+            .cf_handle(self.config.column_families.name(key))
+            .expect(CF_ERROR);
         let old_value = self.db.get_cf(&handle, key.as_slice())?;
         if let Some(batch) = batch {
             batch.delete_cf(&handle, key.as_slice());
         } else {
-            self.db.delete_cf(&handle, key.as_slice())?;
+            self.db
+                .delete_cf_opt(&handle, key.as_slice(), &self.config.write_options())?;
         }
         Ok(old_value.map(Into::into))
     }
 
     fn remove_by_prefix(&mut self, prefix: &DatabaseKey) -> Result<(), Self::DatabaseError> {
         trace!("Getting from RocksDB: {:?}", prefix);
-        let handle = self.db.cf_handle(prefix.get_cf()).expect(CF_ERROR);
-        let iter = self.db.iterator_cf(
-            &handle,
-            IteratorMode::From(prefix.as_slice(), Direction::Forward),
-        );
+        let handle = self
+            .db
+            .cf_handle(self.config.column_families.name(prefix))
+            .expect(CF_ERROR);
         let mut batch = self.create_batch();
-        for kv in iter {
-            if let Ok((key, _)) = kv {
-                if key.starts_with(prefix.as_slice()) {
-                    batch.delete_cf(&handle, &key);
-                } else {
-                    break;
-                }
-            } else {
-                break;
-            }
+        for entry in self.iter_by_prefix(prefix, None, None)? {
+            let (key, _) = entry?;
+            batch.delete_cf(&handle, &key);
         }
         self.write_batch(batch)?;
         Ok(())
     }
 
     fn write_batch(&mut self, batch: Self::Batch) -> Result<(), Self::DatabaseError> {
-        Ok(self.db.write(batch)?)
+        Ok(self.db.write_opt(batch, &self.config.write_options())?)
     }
 }
 
@@ -308,32 +728,18 @@ impl<'db> BonsaiDatabase for RocksDBTransaction<'db> {
 
     #[cfg(test)]
     fn dump_database(&self) {
-        let handle_trie = self.column_families.get(TRIE_CF).expect(CF_ERROR);
-        let handle_flat = self.column_families.get(FLAT_CF).expect(CF_ERROR);
-        let handle_trie_log = self.column_families.get(TRIE_LOG_CF).expect(CF_ERROR);
-        let mut iter = self.txn.raw_iterator_cf(handle_trie);
-        iter.seek_to_first();
-        while iter.valid() {
-            let key = iter.key().unwrap();
-            let value = iter.value().unwrap();
-            println!("{:?} {:?}", key, value);
-            iter.next();
-        }
-        let mut iter = self.txn.raw_iterator_cf(handle_flat);
-        iter.seek_to_first();
-        while iter.valid() {
-            let key = iter.key().unwrap();
-            let value = iter.value().unwrap();
-            println!("{:?} {:?}", key, value);
-            iter.next();
-        }
-        let mut iter = self.txn.raw_iterator_cf(handle_trie_log);
-        iter.seek_to_first();
-        while iter.valid() {
-            let key = iter.key().unwrap();
-            let value = iter.value().unwrap();
-            println!("{:?} {:?}", key, value);
-            iter.next();
+        for cf in self.config.column_families.all() {
+            let handle = self.handles.get(cf).expect(CF_ERROR);
+            let mut iter = self
+                .txn
+                .raw_iterator_cf_opt(handle, total_order_read_options());
+            iter.seek_to_first();
+            while iter.valid() {
+                let key = iter.key().unwrap();
+                let value = iter.value().unwrap();
+                println!("{:?} {:?}", key, value);
+                iter.next();
+            }
         }
     }
 
@@ -344,7 +750,10 @@ impl<'db> BonsaiDatabase for RocksDBTransaction<'db> {
         batch: Option<&mut Self::Batch>,
     ) -> Result<Option<ByteVec>, Self::DatabaseError> {
         trace!("Inserting into RocksDB: {:?} {:?}", key, value);
-        let handle_cf = self.column_families.get(key.get_cf()).expect(CF_ERROR);
+        let handle_cf = self
+            .handles
+            .get(self.config.column_families.name(key))
+            .expect(CF_ERROR);
         let old_value = self
             .txn
             .get_cf_opt(handle_cf, key.as_slice(), &self.read_options)?;
@@ -358,41 +767,70 @@ impl<'db> BonsaiDatabase for RocksDBTransaction<'db> {
 
     fn get(&self, key: &DatabaseKey) -> Result<Option<ByteVec>, Self::DatabaseError> {
         trace!("Getting from RocksDB: {:?}", key);
-        let handle = self.column_families.get(key.get_cf()).expect(CF_ERROR);
+        let handle = self
+            .handles
+            .get(self.config.column_families.name(key))
+            .expect(CF_ERROR);
         Ok(self
             .txn
             .get_cf_opt(handle, key.as_slice(), &self.read_options)?
             .map(Into::into))
     }
 
+    fn get_many(&self, keys: &[DatabaseKey]) -> Result<Vec<Option<ByteVec>>, Self::DatabaseError> {
+        trace!("Getting {} keys from RocksDB", keys.len());
+        let handles = keys.iter().map(|key| {
+            self.handles
+                .get(self.config.column_families.name(key))
+                .expect(CF_ERROR)
+        });
+        self.txn
+            .multi_get_cf_opt(
+                handles
+                    .zip(keys)
+                    .map(|(handle, key)| (handle, key.as_slice())),
+                &self.read_options,
+            )
+            .into_iter()
+            .map(|value| {
+                value
+                    .map(|value| value.map(Into::into))
+                    .map_err(RocksDBError::from)
+            })
+            .collect()
+    }
+
     fn get_by_prefix(
         &self,
         prefix: &DatabaseKey,
     ) -> Result<Vec<(ByteVec, ByteVec)>, Self::DatabaseError> {
         trace!("Getting from RocksDB: {:?}", prefix);
-        let handle = self.column_families.get(prefix.get_cf()).expect(CF_ERROR);
-        let iter = self.txn.iterator_cf(
-            handle,
-            IteratorMode::From(prefix.as_slice(), Direction::Forward),
-        );
-        Ok(iter
-            .map_while(|kv| {
-                if let Ok((key, value)) = kv {
-                    if key.starts_with(prefix.as_slice()) {
-                        Some(((*key).into(), (*value).into()))
-                    } else {
-                        None
-                    }
-                } else {
-                    None
-                }
-            })
-            .collect())
+        self.iter_by_prefix(prefix, None, None)?.collect()
+    }
+
+    fn iter_by_prefix<'a>(
+        &'a self,
+        prefix: &DatabaseKey,
+        seek: Option<&[u8]>,
+        upper_bound: Option<&[u8]>,
+    ) -> Result<DatabaseIterator<'a, Self::DatabaseError>, Self::DatabaseError> {
+        trace!("Iterating over RocksDB: {:?}", prefix);
+        let handle = self
+            .handles
+            .get(self.config.column_families.name(prefix))
+            .expect(CF_ERROR);
+        let (mut read_options, mode) = self.config.iter_options(prefix, seek, upper_bound);
+        read_options.set_snapshot(self.snapshot.get());
+        let iter = self.txn.iterator_cf_opt(handle, read_options, mode);
+        Ok(prefix_iterator(iter, prefix))
     }
 
     fn contains(&self, key: &DatabaseKey) -> Result<bool, Self::DatabaseError> {
         trace!("Checking if RocksDB contains: {:?}", key);
-        let handle = self.column_families.get(key.get_cf()).expect(CF_ERROR);
+        let handle = self
+            .handles
+            .get(self.config.column_families.name(key))
+            .expect(CF_ERROR);
         Ok(self
             .txn
             .get_cf_opt(handle, key.as_slice(), &self.read_options)
@@ -405,7 +843,10 @@ impl<'db> BonsaiDatabase for RocksDBTransaction<'db> {
         batch: Option<&mut Self::Batch>,
     ) -> Result<Option<ByteVec>, Self::DatabaseError> {
         trace!("Removing from RocksDB: {:?}", key);
-        let handle = self.column_families.get(key.get_cf()).expect(CF_ERROR);
+        let handle = self
+            .handles
+            .get(self.config.column_families.name(key))
+            .expect(CF_ERROR);
         let old_value = self
             .txn
             .get_cf_opt(handle, key.as_slice(), &self.read_options)?;
@@ -421,21 +862,13 @@ impl<'db> BonsaiDatabase for RocksDBTransaction<'db> {
         trace!("Getting from RocksDB: {:?}", prefix);
         let mut batch = self.create_batch();
         {
-            let handle = self.column_families.get(prefix.get_cf()).expect(CF_ERROR);
-            let iter = self.txn.iterator_cf(
-                handle,
-                IteratorMode::From(prefix.as_slice(), Direction::Forward),
-            );
-            for kv in iter {
-                if let Ok((key, _)) = kv {
-                    if key.starts_with(prefix.as_slice()) {
-                        batch.delete_cf(handle, &key);
-                    } else {
-                        break;
-                    }
-                } else {
-                    break;
-                }
+            let handle = self
+                .handles
+                .get(self.config.column_families.name(prefix))
+                .expect(CF_ERROR);
+            for entry in self.iter_by_prefix(prefix, None, None)? {
+                let (key, _) = entry?;
+                batch.delete_cf(handle, &key);
             }
         }
         self.write_batch(batch)?;
@@ -447,6 +880,292 @@ impl<'db> BonsaiDatabase for RocksDBTransaction<'db> {
     }
 }
 
+/// A read-only view of a RocksDB database pinned to a snapshot, see
+/// [`BonsaiPersistentDatabase::reader`].
+///
+/// Writes fail with [`RocksDBError::Custom`].
+pub struct RocksDBReader<'db> {
+    db: &'db OptimisticTransactionDB<MultiThreaded>,
+    snapshot: SnapshotWithThreadMode<'db, OptimisticTransactionDB>,
+    config: RocksDBConfig,
+}
+
+impl<'db> fmt::Debug for RocksDBReader<'db> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("RocksDBReader").finish()
+    }
+}
+
+impl<'db> RocksDBReader<'db> {
+    fn handle(&self, key: &DatabaseKey) -> ColumnFamilyRef<'db> {
+        self.db
+            .cf_handle(self.config.column_families.name(key))
+            .expect(CF_ERROR)
+    }
+
+    fn read_only<T>(&self) -> Result<T, RocksDBError> {
+        Err(RocksDBError::Custom(
+            "cannot write through a RocksDB reader".to_string(),
+        ))
+    }
+}
+
+impl<'db> BonsaiDatabase for RocksDBReader<'db> {
+    type Batch = RocksDBBatch;
+    type DatabaseError = RocksDBError;
+
+    fn create_batch(&self) -> Self::Batch {
+        Self::Batch::default()
+    }
+
+    #[cfg(test)]
+    fn dump_database(&self) {
+        println!("{:?}", self)
+    }
+
+    fn get(&self, key: &DatabaseKey) -> Result<Option<ByteVec>, Self::DatabaseError> {
+        trace!("Getting from RocksDB reader: {:?}", key);
+        Ok(self
+            .snapshot
+            .get_cf(&self.handle(key), key.as_slice())?
+            .map(Into::into))
+    }
+
+    fn get_many(&self, keys: &[DatabaseKey]) -> Result<Vec<Option<ByteVec>>, Self::DatabaseError> {
+        trace!("Getting {} keys from RocksDB reader", keys.len());
+        let handles: Vec<_> = keys.iter().map(|key| self.handle(key)).collect();
+        self.snapshot
+            .multi_get_cf(
+                handles
+                    .iter()
+                    .zip(keys)
+                    .map(|(handle, key)| (handle, key.as_slice())),
+            )
+            .into_iter()
+            .map(|value| {
+                value
+                    .map(|value| value.map(Into::into))
+                    .map_err(RocksDBError::from)
+            })
+            .collect()
+    }
+
+    fn get_by_prefix(
+        &self,
+        prefix: &DatabaseKey,
+    ) -> Result<Vec<(ByteVec, ByteVec)>, Self::DatabaseError> {
+        trace!("Getting from RocksDB reader: {:?}", prefix);
+        self.iter_by_prefix(prefix, None, None)?.collect()
+    }
+
+    fn iter_by_prefix<'a>(
+        &'a self,
+        prefix: &DatabaseKey,
+        seek: Option<&[u8]>,
+        upper_bound: Option<&[u8]>,
+    ) -> Result<DatabaseIterator<'a, Self::DatabaseError>, Self::DatabaseError> {
+        trace!("Iterating over RocksDB reader: {:?}", prefix);
+        let (read_options, mode) = self.config.iter_options(prefix, seek, upper_bound);
+        let iter = self
+            .snapshot
+            .iterator_cf_opt(&self.handle(prefix), read_options, mode);
+        Ok(prefix_iterator(iter, prefix))
+    }
+
+    fn contains(&self, key: &DatabaseKey) -> Result<bool, Self::DatabaseError> {
+        Ok(self.get(key)?.is_some())
+    }
+
+    fn insert(
+        &mut self,
+        _key: &DatabaseKey,
+        _value: &[u8],
+        _batch: Option<&mut Self::Batch>,
+    ) -> Result<Option<ByteVec>, Self::DatabaseError> {
+        self.read_only()
+    }
+
+    fn remove(
+        &mut self,
+        _key: &DatabaseKey,
+        _batch: Option<&mut Self::Batch>,
+    ) -> Result<Option<ByteVec>, Self::DatabaseError> {
+        self.read_only()
+    }
+
+    fn remove_by_prefix(&mut self, _prefix: &DatabaseKey) -> Result<(), Self::DatabaseError> {
+        self.read_only()
+    }
+
+    fn write_batch(&mut self, _batch: Self::Batch) -> Result<(), Self::DatabaseError> {
+        self.read_only()
+    }
+}
+
+/// A storage of a database opened with [`open_rocks_db_read_only`].
+///
+/// Writes fail with [`RocksDBError::Custom`], as do reads of a column family that is not in the
+/// database.
+pub struct RocksDBReadOnly<'db> {
+    db: &'db DBWithThreadMode<MultiThreaded>,
+    config: RocksDBConfig,
+}
+
+impl<'db> fmt::Debug for RocksDBReadOnly<'db> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("RocksDBReadOnly").finish()
+    }
+}
+
+impl<'db> RocksDBReadOnly<'db> {
+    /// Creates a read-only wrapper from the given RocksDB database
+    pub fn new(db: &'db DBWithThreadMode<MultiThreaded>, config: RocksDBConfig) -> Self {
+        trace!("RockDB database opened read-only");
+        Self { db, config }
+    }
+
+    /// `None` for the meta column family of a database written before it existed, which reads
+    /// as empty.
+    fn handle(&self, key: &DatabaseKey) -> Result<Option<ColumnFamilyRef<'db>>, RocksDBError> {
+        let name = self.config.column_families.name(key);
+        match self.db.cf_handle(name) {
+            Some(handle) => Ok(Some(handle)),
+            None if matches!(key, DatabaseKey::Meta(_)) => Ok(None),
+            None => Err(RocksDBError::MissingColumnFamily(name.to_string())),
+        }
+    }
+
+    fn read_only<T>(&self) -> Result<T, RocksDBError> {
+        Err(RocksDBError::Custom(
+            "cannot write to a RocksDB database opened read-only".to_string(),
+        ))
+    }
+}
+
+impl<'db> BonsaiDatabase for RocksDBReadOnly<'db> {
+    type Batch = RocksDBBatch;
+    type DatabaseError = RocksDBError;
+
+    fn create_batch(&self) -> Self::Batch {
+        Self::Batch::default()
+    }
+
+    #[cfg(test)]
+    fn dump_database(&self) {
+        println!("{:?}", self)
+    }
+
+    fn get(&self, key: &DatabaseKey) -> Result<Option<ByteVec>, Self::DatabaseError> {
+        trace!("Getting from read-only RocksDB: {:?}", key);
+        let Some(handle) = self.handle(key)? else {
+            return Ok(None);
+        };
+        Ok(self.db.get_cf(&handle, key.as_slice())?.map(Into::into))
+    }
+
+    fn get_many(&self, keys: &[DatabaseKey]) -> Result<Vec<Option<ByteVec>>, Self::DatabaseError> {
+        trace!("Getting {} keys from read-only RocksDB", keys.len());
+        let handles = keys
+            .iter()
+            .map(|key| self.handle(key))
+            .collect::<Result<Vec<_>, _>>()?;
+        if handles.iter().any(Option::is_none) {
+            return keys.iter().map(|key| self.get(key)).collect();
+        }
+        self.db
+            .multi_get_cf(
+                handles
+                    .iter()
+                    .flatten()
+                    .zip(keys)
+                    .map(|(handle, key)| (handle, key.as_slice())),
+            )
+            .into_iter()
+            .map(|value| {
+                value
+                    .map(|value| value.map(Into::into))
+                    .map_err(RocksDBError::from)
+            })
+            .collect()
+    }
+
+    fn get_by_prefix(
+        &self,
+        prefix: &DatabaseKey,
+    ) -> Result<Vec<(ByteVec, ByteVec)>, Self::DatabaseError> {
+        trace!("Getting from read-only RocksDB: {:?}", prefix);
+        self.iter_by_prefix(prefix, None, None)?.collect()
+    }
+
+    fn iter_by_prefix<'a>(
+        &'a self,
+        prefix: &DatabaseKey,
+        seek: Option<&[u8]>,
+        upper_bound: Option<&[u8]>,
+    ) -> Result<DatabaseIterator<'a, Self::DatabaseError>, Self::DatabaseError> {
+        trace!("Iterating over read-only RocksDB: {:?}", prefix);
+        let Some(handle) = self.handle(prefix)? else {
+            return Ok(Box::new(std::iter::empty()));
+        };
+        let (read_options, mode) = self.config.iter_options(prefix, seek, upper_bound);
+        let iter = self.db.iterator_cf_opt(&handle, read_options, mode);
+        Ok(prefix_iterator(iter, prefix))
+    }
+
+    fn contains(&self, key: &DatabaseKey) -> Result<bool, Self::DatabaseError> {
+        Ok(self.get(key)?.is_some())
+    }
+
+    fn insert(
+        &mut self,
+        _key: &DatabaseKey,
+        _value: &[u8],
+        _batch: Option<&mut Self::Batch>,
+    ) -> Result<Option<ByteVec>, Self::DatabaseError> {
+        self.read_only()
+    }
+
+    fn remove(
+        &mut self,
+        _key: &DatabaseKey,
+        _batch: Option<&mut Self::Batch>,
+    ) -> Result<Option<ByteVec>, Self::DatabaseError> {
+        self.read_only()
+    }
+
+    fn remove_by_prefix(&mut self, _prefix: &DatabaseKey) -> Result<(), Self::DatabaseError> {
+        self.read_only()
+    }
+
+    fn write_batch(&mut self, _batch: Self::Batch) -> Result<(), Self::DatabaseError> {
+        self.read_only()
+    }
+}
+
+impl<'db, ID: Id> RocksDB<'db, ID> {
+    fn new_transaction<'a>(&'a self, snapshot: TransactionSnapshot<'a>) -> RocksDBTransaction<'a> {
+        let mut read_options = ReadOptions::default();
+        read_options.set_snapshot(snapshot.get());
+        let write_opts = self.config.write_options();
+        let mut txn_opts = OptimisticTransactionOptions::default();
+        txn_opts.set_snapshot(true);
+        let txn = self.db.transaction_opt(&write_opts, &txn_opts);
+
+        let config = self.config.clone();
+        let mut handles = HashMap::new();
+        for cf in config.column_families.all() {
+            handles.insert(cf.to_string(), self.db.cf_handle(cf).expect(CF_ERROR));
+        }
+        RocksDBTransaction {
+            txn,
+            read_options,
+            snapshot,
+            config,
+            handles,
+        }
+    }
+}
+
 impl<'db, ID> BonsaiPersistentDatabase<ID> for RocksDB<'db, ID>
 where
     ID: Id,
@@ -455,6 +1174,7 @@ where
         = RocksDBTransaction<'a>
     where
         Self: 'a;
+    type Reader = RocksDBReader<'db>;
     type DatabaseError = RocksDBError;
 
     fn snapshot(&mut self, id: ID) {
@@ -468,38 +1188,34 @@ where
         }
     }
 
+    fn remove_snapshots_after(&mut self, id: ID) {
+        self.snapshots.retain(|snap_id, _| *snap_id <= id);
+    }
+
+    fn nearest_snapshot(&self, id: ID) -> Option<ID> {
+        self.snapshots.range(..=id).next_back().map(|(id, _)| *id)
+    }
+
     fn transaction(&self, id: ID) -> Option<(ID, Self::Transaction<'_>)> {
         trace!("Generating RocksDB transaction");
-        if let Some((id, snapshot)) = self.snapshots.range(..&id).next() {
-            let write_opts = WriteOptions::default();
-            let mut txn_opts = OptimisticTransactionOptions::default();
-            txn_opts.set_snapshot(true);
-            let txn = self.db.transaction_opt(&write_opts, &txn_opts);
-
-            let mut read_options = ReadOptions::default();
-            read_options.set_snapshot(snapshot);
-
-            let mut column_families = HashMap::new();
-            column_families.insert(
-                TRIE_LOG_CF.to_string(),
-                self.db.cf_handle(TRIE_LOG_CF).expect(CF_ERROR),
-            );
-            column_families.insert(
-                TRIE_CF.to_string(),
-                self.db.cf_handle(TRIE_CF).expect(CF_ERROR),
-            );
-            column_families.insert(
-                FLAT_CF.to_string(),
-                self.db.cf_handle(FLAT_CF).expect(CF_ERROR),
-            );
-            let boxed_txn = RocksDBTransaction {
-                txn,
-                column_families,
-                read_options,
-            };
-            Some((*id, boxed_txn))
-        } else {
-            None
+        let (id, snapshot) = self.snapshots.range(..=id).next_back()?;
+        Some((
+            *id,
+            self.new_transaction(TransactionSnapshot::Shared(snapshot)),
+        ))
+    }
+
+    fn transaction_at_head(&self) -> Self::Transaction<'_> {
+        trace!("Generating RocksDB transaction at head");
+        self.new_transaction(TransactionSnapshot::Owned(self.db.snapshot()))
+    }
+
+    fn reader(&self) -> Self::Reader {
+        trace!("Generating RocksDB reader");
+        RocksDBReader {
+            db: self.db,
+            snapshot: self.db.snapshot(),
+            config: self.config.clone(),
         }
     }
 
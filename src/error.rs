@@ -1,7 +1,63 @@
 #[cfg(feature = "std")]
 use std::{error::Error, fmt::Display};
 
-use crate::{bonsai_database::DBError, String};
+use starknet_types_core::felt::Felt;
+
+use crate::{bonsai_database::DBError, trie::path::Path, BitVec, String, Vec};
+
+/// A key changed both in a transactional state and in the storage it is merged into, after the
+/// transactional state was created.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MergeConflict {
+    /// Identifier of the trie.
+    pub identifier: Vec<u8>,
+    /// Key in the trie.
+    pub key: BitVec,
+    /// Value in the storage, `None` if the key was removed.
+    pub base_value: Option<Felt>,
+    /// Value in the transactional state, `None` if the key was removed.
+    pub txn_value: Option<Felt>,
+}
+
+/// Reasons a transactional state cannot be merged.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum MergeError<DatabaseError> {
+    /// Keys changed on both sides since the transactional state was created.
+    Conflicts(Vec<MergeConflict>),
+    /// The storage merged is not a transactional state.
+    NotTransactional,
+    /// A commit of the transactional state is not newer than the latest commit of the storage.
+    CommitOrder { id: u64, latest_id: u64 },
+    /// The storage committed since the transactional state was created, which
+    /// [`crate::MergePolicy::LastWriterWins`] does not merge.
+    Outdated { created_at: u64, latest_id: u64 },
+    /// The trie logs of this commit of the storage are needed to detect conflicts but were pruned.
+    MissingTrieLogs(u64),
+    /// Error of the database of the storage while applying the changes.
+    Storage(DatabaseError),
+}
+
+/// Reasons a trie log cannot be exported or applied.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum TrieLogError {
+    /// The trie log or the [`crate::CommitInfo`] of this commit is not in the database.
+    Missing(u64),
+    /// The bytes do not start like a trie log stream.
+    InvalidStream,
+    /// The stream was written with an unsupported version of the encoding.
+    UnsupportedVersion(u32),
+    /// A commit of the stream is not newer than the latest commit of the storage.
+    CommitOrder { id: u64, latest_id: u64 },
+    /// The value of a key before the commit is not the one in the storage.
+    StateMismatch { id: u64, key: Vec<u8> },
+    /// The root of a trie after the commit is not the one recorded in its header.
+    RootMismatch {
+        id: u64,
+        identifier: Vec<u8>,
+        expected: Felt,
+        got: Felt,
+    },
+}
 
 /// All errors that can be returned by BonsaiStorage.
 #[derive(Debug)]
@@ -9,20 +65,119 @@ pub enum BonsaiStorageError<DatabaseError>
 where
     DatabaseError: DBError,
 {
-    /// Error from the underlying trie.
-    Trie(String),
-    /// Error when trying to go to a specific commit ID.
-    GoTo(String),
-    /// Error when working with a transactional state.
-    Transaction(String),
+    /// The root of this trie has changes since the last commit, so it has no hash yet.
+    UncommittedChanges { identifier: Vec<u8> },
+    /// The root of this trie is not loaded, so it cannot be hashed.
+    RootNotLoaded { identifier: Vec<u8> },
+    /// A node of this trie was reached before its hash was computed while hashing the trie.
+    UnhashedNode { identifier: Vec<u8> },
+    /// The hasher returned `got` hashes for `expected` pairs.
+    HashCount { expected: usize, got: usize },
+    /// The node of this trie at this path is not in the database.
+    NodeNotFound { identifier: Vec<u8>, path: Path },
+    /// An in-memory node key of this trie does not point to a node.
+    DanglingNode { identifier: Vec<u8> },
+    /// The savepoint was taken before the last commit or reset of the storage, or was rolled back
+    /// past.
+    StaleSavepoint,
+    /// A commit was made after `id`, so it is not the latest one.
+    NotLatest { id: u64 },
+    /// Cannot revert to `id`, which is after the latest commit.
+    RevertToFuture { id: u64, latest_id: u64 },
     /// Error when trying to merge a transactional state.
-    Merge(String),
+    Merge(MergeError<DatabaseError>),
+    /// Error when exporting or applying trie logs.
+    TrieLog(TrieLogError),
+    /// A record read from the meta column of the database has a malformed key.
+    InvalidMetaKey(Vec<u8>),
+    /// A flat key does not start with the prefix of a trie identifier.
+    InvalidFlatKey(Vec<u8>),
     /// Error from the underlying database.
     Database(DatabaseError),
     /// Error when decoding a node
     NodeDecodeError(parity_scale_codec::Error),
+    /// A value read from the database at this key could not be decoded.
+    ValueDecode {
+        key: Vec<u8>,
+        error: parity_scale_codec::Error,
+    },
+    /// A trie log entry read from the database has a malformed key.
+    InvalidTrieLogKey(Vec<u8>),
+    /// The trie node read from the database at this key has no hash.
+    MissingNodeHash(Vec<u8>),
     /// Malformated trie key.
     KeyLength { expected: usize, got: usize },
+    /// Error when upgrading the database to a newer storage layout.
+    Migration(String),
+    /// The database was written with an older storage layout and must be upgraded with
+    /// [`crate::migration::migrate`] before use.
+    MigrationRequired { from: u32, to: u32 },
+    /// The database was written by a newer version of this crate.
+    UnsupportedSchemaVersion(u32),
+}
+
+impl<DatabaseError: DBError> BonsaiStorageError<DatabaseError> {
+    /// Converts the error to another database error type, mapping only [`Self::Database`].
+    pub(crate) fn map_database<E: DBError>(
+        self,
+        f: impl FnOnce(DatabaseError) -> BonsaiStorageError<E>,
+    ) -> BonsaiStorageError<E> {
+        match self {
+            Self::UncommittedChanges { identifier } => {
+                BonsaiStorageError::UncommittedChanges { identifier }
+            }
+            Self::RootNotLoaded { identifier } => BonsaiStorageError::RootNotLoaded { identifier },
+            Self::UnhashedNode { identifier } => BonsaiStorageError::UnhashedNode { identifier },
+            Self::HashCount { expected, got } => BonsaiStorageError::HashCount { expected, got },
+            Self::NodeNotFound { identifier, path } => {
+                BonsaiStorageError::NodeNotFound { identifier, path }
+            }
+            Self::DanglingNode { identifier } => BonsaiStorageError::DanglingNode { identifier },
+            Self::StaleSavepoint => BonsaiStorageError::StaleSavepoint,
+            Self::NotLatest { id } => BonsaiStorageError::NotLatest { id },
+            Self::RevertToFuture { id, latest_id } => {
+                BonsaiStorageError::RevertToFuture { id, latest_id }
+            }
+            Self::Merge(e) => match e {
+                MergeError::Conflicts(conflicts) => {
+                    BonsaiStorageError::Merge(MergeError::Conflicts(conflicts))
+                }
+                MergeError::NotTransactional => {
+                    BonsaiStorageError::Merge(MergeError::NotTransactional)
+                }
+                MergeError::CommitOrder { id, latest_id } => {
+                    BonsaiStorageError::Merge(MergeError::CommitOrder { id, latest_id })
+                }
+                MergeError::Outdated {
+                    created_at,
+                    latest_id,
+                } => BonsaiStorageError::Merge(MergeError::Outdated {
+                    created_at,
+                    latest_id,
+                }),
+                MergeError::MissingTrieLogs(id) => {
+                    BonsaiStorageError::Merge(MergeError::MissingTrieLogs(id))
+                }
+                MergeError::Storage(e) => f(e),
+            },
+            Self::TrieLog(e) => BonsaiStorageError::TrieLog(e),
+            Self::InvalidMetaKey(key) => BonsaiStorageError::InvalidMetaKey(key),
+            Self::InvalidFlatKey(key) => BonsaiStorageError::InvalidFlatKey(key),
+            Self::Database(e) => f(e),
+            Self::NodeDecodeError(e) => BonsaiStorageError::NodeDecodeError(e),
+            Self::ValueDecode { key, error } => BonsaiStorageError::ValueDecode { key, error },
+            Self::InvalidTrieLogKey(key) => BonsaiStorageError::InvalidTrieLogKey(key),
+            Self::MissingNodeHash(key) => BonsaiStorageError::MissingNodeHash(key),
+            Self::KeyLength { expected, got } => BonsaiStorageError::KeyLength { expected, got },
+            Self::Migration(e) => BonsaiStorageError::Migration(e),
+            Self::MigrationRequired { from, to } => {
+                BonsaiStorageError::MigrationRequired { from, to }
+            }
+            Self::UnsupportedSchemaVersion(version) => {
+                BonsaiStorageError::UnsupportedSchemaVersion(version)
+            }
+        }
+    }
 }
 
 impl<DatabaseError: DBError> core::convert::From<DatabaseError>
@@ -41,6 +196,69 @@ impl<DatabaseError: DBError> core::convert::From<parity_scale_codec::Error>
     }
 }
 
+#[cfg(feature = "std")]
+impl<DatabaseError: Display> Display for MergeError<DatabaseError> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            MergeError::Conflicts(conflicts) => {
+                write!(f, "{} conflicting keys", conflicts.len())?;
+                for conflict in conflicts {
+                    write!(
+                        f,
+                        "; {:?} {}: base {:?}, transaction {:?}",
+                        conflict.identifier, conflict.key, conflict.base_value, conflict.txn_value
+                    )?;
+                }
+                Ok(())
+            }
+            MergeError::NotTransactional => write!(f, "not a transactional state"),
+            MergeError::CommitOrder { id, latest_id } => {
+                write!(f, "commit {id} is not after the latest commit {latest_id}")
+            }
+            MergeError::Outdated {
+                created_at,
+                latest_id,
+            } => write!(
+                f,
+                "transaction created at {created_at} is before the latest commit {latest_id}"
+            ),
+            MergeError::MissingTrieLogs(id) => write!(f, "missing trie logs for commit {id}"),
+            MergeError::Storage(e) => write!(f, "{}", e),
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl Display for TrieLogError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            TrieLogError::Missing(id) => write!(f, "missing trie log for commit {id}"),
+            TrieLogError::InvalidStream => write!(f, "not a trie log stream"),
+            TrieLogError::UnsupportedVersion(version) => {
+                write!(f, "unsupported trie log stream version {version}")
+            }
+            TrieLogError::CommitOrder { id, latest_id } => {
+                write!(f, "commit {id} is not after the latest commit {latest_id}")
+            }
+            TrieLogError::StateMismatch { id, key } => {
+                write!(
+                    f,
+                    "commit {id} does not apply on the current value of {key:?}"
+                )
+            }
+            TrieLogError::RootMismatch {
+                id,
+                identifier,
+                expected,
+                got,
+            } => write!(
+                f,
+                "commit {id} leads to root {got:#x} of trie {identifier:?} instead of {expected:#x}"
+            ),
+        }
+    }
+}
+
 #[cfg(feature = "std")]
 impl<DatabaseError> Display for BonsaiStorageError<DatabaseError>
 where
@@ -48,15 +266,87 @@ where
 {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         match self {
-            BonsaiStorageError::Trie(e) => write!(f, "Trie error: {}", e),
-            BonsaiStorageError::GoTo(e) => write!(f, "GoTo error: {}", e),
-            BonsaiStorageError::Transaction(e) => write!(f, "Transaction error: {}", e),
+            BonsaiStorageError::UncommittedChanges { identifier } => {
+                write!(f, "Trie {identifier:?} has uncommitted changes")
+            }
+            BonsaiStorageError::RootNotLoaded { identifier } => {
+                write!(f, "Root node of trie {identifier:?} is not loaded")
+            }
+            BonsaiStorageError::UnhashedNode { identifier } => {
+                write!(
+                    f,
+                    "Node of trie {identifier:?} reached before its hash was computed"
+                )
+            }
+            BonsaiStorageError::HashCount { expected, got } => {
+                write!(f, "Hasher returned {got} hashes for {expected} pairs")
+            }
+            BonsaiStorageError::NodeNotFound { identifier, path } => {
+                write!(
+                    f,
+                    "Node {path:?} of trie {identifier:?} is not in the database"
+                )
+            }
+            BonsaiStorageError::DanglingNode { identifier } => {
+                write!(f, "Dangling in-memory node key in trie {identifier:?}")
+            }
+            BonsaiStorageError::StaleSavepoint => {
+                write!(
+                    f,
+                    "Savepoint was taken before the last commit or reset, or was rolled back past"
+                )
+            }
+            BonsaiStorageError::NotLatest { id } => {
+                write!(f, "Commit {id} is not the latest")
+            }
+            BonsaiStorageError::RevertToFuture { id, latest_id } => {
+                write!(
+                    f,
+                    "Cannot revert to {id}, after the latest commit {latest_id}"
+                )
+            }
             BonsaiStorageError::Merge(e) => write!(f, "Merge error: {}", e),
+            BonsaiStorageError::TrieLog(e) => write!(f, "Trie log error: {}", e),
+            BonsaiStorageError::InvalidMetaKey(key) => {
+                write!(f, "Invalid meta key {key:?}")
+            }
+            BonsaiStorageError::InvalidFlatKey(key) => write!(f, "Invalid flat key {key:?}"),
             BonsaiStorageError::Database(e) => write!(f, "Database error: {}", e),
             BonsaiStorageError::NodeDecodeError(e) => write!(f, "Node decode error: {}", e),
+            BonsaiStorageError::ValueDecode { key, error } => {
+                write!(f, "Could not decode the value at {key:?}: {error}")
+            }
+            BonsaiStorageError::InvalidTrieLogKey(key) => {
+                write!(f, "Invalid trie log key {key:?}")
+            }
+            BonsaiStorageError::MissingNodeHash(key) => {
+                write!(f, "The trie node at {key:?} has no hash")
+            }
             BonsaiStorageError::KeyLength { expected, got } => {
                 write!(f, "Malformated key length: expected {expected}, got {got}")
             }
+            BonsaiStorageError::Migration(e) => write!(f, "Migration error: {}", e),
+            BonsaiStorageError::MigrationRequired { from, to } => {
+                write!(f, "Database schema version {from} must be migrated to {to}")
+            }
+            BonsaiStorageError::UnsupportedSchemaVersion(version) => {
+                write!(f, "Unsupported database schema version {version}")
+            }
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl<DatabaseError> Error for BonsaiStorageError<DatabaseError>
+where
+    DatabaseError: Error + DBError + 'static,
+{
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            BonsaiStorageError::Database(e) | BonsaiStorageError::Merge(MergeError::Storage(e)) => {
+                Some(e)
+            }
+            _ => None,
         }
     }
 }
@@ -2,7 +2,9 @@ use crate::ByteVec;
 use core::{fmt::Debug, hash};
 
 /// Trait to be implemented on any type that can be used as an ID.
-pub trait Id: hash::Hash + PartialEq + Eq + PartialOrd + Ord + Debug + Copy + Default {
+pub trait Id:
+    hash::Hash + PartialEq + Eq + PartialOrd + Ord + Debug + Copy + Default + Send + Sync
+{
     fn to_bytes(&self) -> ByteVec;
     fn as_u64(self) -> u64;
     fn from_u64(v: u64) -> Self;
@@ -1,17 +1,97 @@
-use crate::{bytes_to_bitvec, format, BitVec, ByteVec, Change as ExternChange};
+use crate::{bytes_to_bitvec, BTreeMap, BitVec, ByteVec, Change as ExternChange, HashSet, Vec};
+use core::ops::RangeInclusive;
 use hashbrown::HashMap;
 use log::trace;
-use parity_scale_codec::Decode;
+use parity_scale_codec::{Decode, Encode};
 use starknet_types_core::felt::Felt;
 
 use crate::{
     bonsai_database::{BonsaiDatabase, BonsaiPersistentDatabase, DatabaseKey},
     changes::{Change, ChangeBatch, ChangeStore},
+    commit_info::{commit_info_key, CommitInfo, COMMIT_INFO_KEY_PREFIX, COMMIT_INFO_VERSION},
     id::Id,
-    trie::TrieKey,
-    BonsaiStorageConfig, BonsaiStorageError,
+    migration::{self, CURRENT_SCHEMA_VERSION},
+    trie::{
+        trie_db::{identifier_prefix, split_identifier_prefix},
+        TrieKey,
+    },
+    BonsaiStorageConfig, BonsaiStorageError, DBError, MergeError, TrieLogError,
 };
 
+/// Meta key of the id of the last commit, a SCALE-encoded `u64`.
+const LATEST_ID_KEY: &[u8] = b"latest_id";
+/// Prefix of the meta keys recording the ids of the snapshots, followed by the big-endian id.
+const SNAPSHOT_KEY_PREFIX: &[u8] = b"snapshot/";
+
+/// Smallest key greater than every key starting with `prefix`, `None` if there is none.
+fn prefix_successor(prefix: &[u8]) -> Option<ByteVec> {
+    let mut next = ByteVec::from(prefix);
+    while let Some(last) = next.pop() {
+        if last < u8::MAX {
+            next.push(last + 1);
+            return Some(next);
+        }
+    }
+    None
+}
+
+fn snapshot_key<ID: Id>(id: ID) -> ByteVec {
+    let mut key = ByteVec::from(SNAPSHOT_KEY_PREFIX);
+    key.extend_from_slice(&id.as_u64().to_be_bytes());
+    key
+}
+
+/// Values of the leaves changed by a commit, keyed by flat key, `None` for removed leaves.
+pub(crate) type LeafChanges = Vec<(ByteVec, Option<Felt>)>;
+
+/// Decodes a leaf value read from the database at `key`.
+pub(crate) fn decode_felt<E: DBError>(
+    key: &[u8],
+    value: &[u8],
+) -> Result<Felt, BonsaiStorageError<E>> {
+    Felt::decode(&mut &value[..]).map_err(|error| BonsaiStorageError::ValueDecode {
+        key: key.to_vec(),
+        error,
+    })
+}
+
+/// Decodes the old and new values of a leaf change.
+fn decode_change<E: DBError>(
+    key: &[u8],
+    change: &Change,
+) -> Result<ExternChange, BonsaiStorageError<E>> {
+    let decode = |value: &Option<ByteVec>| {
+        value
+            .as_ref()
+            .map(|value| decode_felt::<E>(key, value))
+            .transpose()
+    };
+    Ok(ExternChange {
+        old_value: decode(&change.old_value)?,
+        new_value: decode(&change.new_value)?,
+    })
+}
+
+/// Changed leaves of a change batch by trie identifier, with their decoded values.
+pub(crate) fn leaf_changes_by_identifier<E: DBError>(
+    changes: &ChangeBatch,
+) -> Result<BTreeMap<Vec<u8>, HashMap<BitVec, ExternChange>>, BonsaiStorageError<E>> {
+    let mut leaf_changes: BTreeMap<Vec<u8>, HashMap<BitVec, ExternChange>> = BTreeMap::new();
+    for (key, change) in &changes.0 {
+        let TrieKey::Flat(flat_key) = key else {
+            continue;
+        };
+        let Some((identifier, key)) = split_identifier_prefix(flat_key) else {
+            continue;
+        };
+        leaf_changes
+            .entry(identifier.to_vec())
+            .or_default()
+            .insert(bytes_to_bitvec(key), decode_change::<E>(flat_key, change)?);
+    }
+    Ok(leaf_changes)
+}
+
 /// Crate Trie <= KeyValueDB => BonsaiDatabase
 #[cfg_attr(feature = "bench", derive(Clone))]
 #[derive(Debug)]
@@ -19,7 +99,13 @@ pub struct KeyValueDB<DB: BonsaiDatabase, ID: Id> {
     pub(crate) db: DB,
     pub(crate) changes_store: ChangeStore,
     pub(crate) config: KeyValueDBConfig,
-    pub(crate) _created_at: Option<ID>,
+    /// Id of the state a transactional state was created at, `None` for the storage itself.
+    pub(crate) created_at: Option<ID>,
+    /// Leaf changes of the commits made in a transactional state, replayed when it is merged.
+    pub(crate) transactional_commits: Vec<(ID, LeafChanges)>,
+    /// The database was empty and had no schema version record when opened, it is written before
+    /// anything else.
+    pub(crate) unversioned: bool,
 }
 
 #[derive(Clone, Debug)]
@@ -73,10 +159,24 @@ where
             db: underline_db,
             changes_store,
             config,
-            _created_at: created_at,
+            created_at,
+            transactional_commits: Vec::new(),
+            unversioned: false,
         }
     }
 
+    /// Stamps an empty database with the current schema version, before anything else is
+    /// written to it.
+    pub(crate) fn write_schema_version(
+        &mut self,
+    ) -> Result<(), BonsaiStorageError<DB::DatabaseError>> {
+        if self.unversioned {
+            migration::set_schema_version(&mut self.db, CURRENT_SCHEMA_VERSION, None)?;
+            self.unversioned = false;
+        }
+        Ok(())
+    }
+
     #[allow(clippy::type_complexity)]
     pub(crate) fn get_changes(
         &self,
@@ -86,62 +186,220 @@ where
         let changes = ChangeBatch::deserialize(
             &id,
             self.db
-                .get_by_prefix(&DatabaseKey::TrieLog(&id.to_bytes()))?,
-        );
+                .iter_by_prefix(&DatabaseKey::TrieLog(&id.to_bytes()), None, None)?,
+        )?;
         for (k, v) in changes.0 {
-            if let TrieKey::Flat(k) = k {
-                // Note on safety of expect():
-                // We are sure that the values are valid Felt because they can be saved only by our crate
-                let old_value = v.old_value.map(|x| {
-                    Felt::decode(&mut x.as_ref()).expect(
-                        "We saved this Felt ('old_value') so we should be able to decode it",
-                    )
-                });
-                let new_value = v.new_value.map(|x| {
-                    Felt::decode(&mut x.as_ref()).expect(
-                        "We saved this Felt ('new_value') so we should be able to decode it",
-                    )
-                });
+            if let TrieKey::Flat(flat_key) = k {
+                let Some((_identifier, k)) = split_identifier_prefix(&flat_key) else {
+                    continue;
+                };
                 leaf_changes.insert(
-                    bytes_to_bitvec(&k),
-                    ExternChange {
-                        old_value,
-                        new_value,
-                    },
+                    bytes_to_bitvec(k),
+                    decode_change::<DB::DatabaseError>(&flat_key, &v)?,
                 );
             }
         }
         Ok(leaf_changes)
     }
 
-    pub(crate) fn commit(&mut self, id: ID) -> Result<(), BonsaiStorageError<DB::DatabaseError>> {
+    /// Writes the trie log of `id` with its [`CommitInfo`], `roots` being the root hashes of the
+    /// tries the commit changed.
+    pub(crate) fn commit(
+        &mut self,
+        id: ID,
+        roots: Vec<(Vec<u8>, Felt)>,
+        metadata: Option<&[u8]>,
+    ) -> Result<(), BonsaiStorageError<DB::DatabaseError>> {
         // Insert flat db changes
         let mut batch = self.db.create_batch();
         let current_changes = core::mem::take(&mut self.changes_store.current_changes);
         log::debug!("Committing id {id:?}");
 
+        self.db.insert(
+            &DatabaseKey::Meta(LATEST_ID_KEY),
+            &id.as_u64().encode(),
+            Some(&mut batch),
+        )?;
         if self.config.max_saved_trie_logs != Some(0) {
             // optim when trie logs are disabled.
             for (key, change) in current_changes.serialize(&id).iter() {
                 self.db
                     .insert(&DatabaseKey::TrieLog(key), change, Some(&mut batch))?;
             }
-            self.db.write_batch(batch)?;
+            let info = CommitInfo {
+                version: COMMIT_INFO_VERSION,
+                id,
+                roots,
+                change_count: current_changes
+                    .0
+                    .iter()
+                    .filter(|(key, change)| {
+                        matches!(key, TrieKey::Flat(_)) && change.old_value != change.new_value
+                    })
+                    .count() as u64,
+                metadata: metadata.map(<[u8]>::to_vec),
+            };
+            self.db.insert(
+                &DatabaseKey::Meta(&commit_info_key(id)),
+                &info.encode(),
+                Some(&mut batch),
+            )?;
+        }
+        self.db.write_batch(batch)?;
+
+        if self.created_at.is_some() {
+            let changes = current_changes
+                .0
+                .iter()
+                .filter_map(|(key, change)| match key {
+                    TrieKey::Flat(key) => Some((key, change)),
+                    _ => None,
+                })
+                .map(|(key, change)| {
+                    let value = change
+                        .new_value
+                        .as_ref()
+                        .map(|value| decode_felt::<DB::DatabaseError>(key, value))
+                        .transpose()?;
+                    Ok((key.clone(), value))
+                })
+                .collect::<Result<_, BonsaiStorageError<DB::DatabaseError>>>()?;
+            self.transactional_commits.push((id, changes));
+        }
 
+        if self.config.max_saved_trie_logs != Some(0) {
             if let Some(id) = self
                 .config
                 .max_saved_trie_logs
                 .and_then(|max_saved_trie_logs| id.as_u64().checked_sub(max_saved_trie_logs as _))
             {
                 log::debug!("Remove by prefix {id:?}");
+                let id = ID::from_u64(id);
                 self.db
-                    .remove_by_prefix(&DatabaseKey::TrieLog(&ID::from_u64(id).to_bytes()))?;
+                    .remove_by_prefix(&DatabaseKey::TrieLog(&id.to_bytes()))?;
+                self.db
+                    .remove(&DatabaseKey::Meta(&commit_info_key(id)), None)?;
             }
         }
 
         Ok(())
     }
 
+    pub(crate) fn get_commit_info(
+        &self,
+        id: ID,
+    ) -> Result<Option<CommitInfo<ID>>, BonsaiStorageError<DB::DatabaseError>> {
+        self.db
+            .get(&DatabaseKey::Meta(&commit_info_key(id)))?
+            .map(|info| Ok(CommitInfo::decode(&mut info.as_slice())?))
+            .transpose()
+    }
+
+    /// Record and changes of a commit, to be exported to another storage.
+    pub(crate) fn get_trie_log(
+        &self,
+        id: ID,
+    ) -> Result<(CommitInfo<ID>, ChangeBatch), BonsaiStorageError<DB::DatabaseError>> {
+        let Some(info) = self.get_commit_info(id)? else {
+            return Err(BonsaiStorageError::TrieLog(TrieLogError::Missing(
+                id.as_u64(),
+            )));
+        };
+        let changes = ChangeBatch::deserialize(
+            &id,
+            self.db
+                .iter_by_prefix(&DatabaseKey::TrieLog(&id.to_bytes()), None, None)?,
+        )?;
+        Ok((info, changes))
+    }
+
+    /// Trie logs and records of the commits in `ids`, in order.
+    ///
+    /// Ids without a trie log are skipped, unless they are old enough for it to have been pruned.
+    #[allow(clippy::type_complexity)]
+    pub(crate) fn get_trie_logs(
+        &self,
+        ids: RangeInclusive<ID>,
+    ) -> Result<Vec<(CommitInfo<ID>, ChangeBatch)>, BonsaiStorageError<DB::DatabaseError>> {
+        // Committing `id` prunes the trie log of `id - max_saved_trie_logs`.
+        let pruned = self
+            .config
+            .max_saved_trie_logs
+            .zip(self.get_latest_id()?)
+            .and_then(|(max_saved_trie_logs, latest_id)| {
+                latest_id.as_u64().checked_sub(max_saved_trie_logs as u64)
+            });
+        if let Some(pruned) = pruned {
+            if ids.start().as_u64() <= pruned {
+                return Err(BonsaiStorageError::TrieLog(TrieLogError::Missing(
+                    ids.start().as_u64(),
+                )));
+            }
+        }
+        self.get_trie_log_ids(ids)?
+            .into_iter()
+            .map(|id| self.get_trie_log(id))
+            .collect()
+    }
+
+    /// Checks that a commit exported from another storage applies on the current state: it must
+    /// be newer than the latest commit, and the old values of its leaves the current ones.
+    ///
+    /// Trie nodes are not compared, the same node can be stored in the legacy or the compact
+    /// encoding: the roots recomputed from the leaves are checked instead.
+    pub(crate) fn check_trie_log(
+        &self,
+        info: &CommitInfo<ID>,
+        changes: &ChangeBatch,
+    ) -> Result<(), BonsaiStorageError<DB::DatabaseError>> {
+        let id = info.id.as_u64();
+        if let Some(latest_id) = self.get_latest_id()? {
+            if latest_id >= info.id {
+                return Err(BonsaiStorageError::TrieLog(TrieLogError::CommitOrder {
+                    id,
+                    latest_id: latest_id.as_u64(),
+                }));
+            }
+        }
+
+        let keys: Vec<TrieKey> = changes
+            .0
+            .keys()
+            .filter(|key| matches!(key, TrieKey::Flat(_)))
+            .cloned()
+            .collect();
+        for (key, value) in keys.iter().zip(self.get_many(&keys)?) {
+            if value != changes.0[key].old_value {
+                return Err(BonsaiStorageError::TrieLog(TrieLogError::StateMismatch {
+                    id,
+                    key: key.as_slice().to_vec(),
+                }));
+            }
+        }
+        Ok(())
+    }
+
+    /// Records in `batch` that the commits after `id` were reverted: `id` becomes the latest
+    /// commit and the snapshots taken after it are forgotten.
+    pub(crate) fn revert_records(
+        &mut self,
+        id: ID,
+        batch: &mut DB::Batch,
+    ) -> Result<(), BonsaiStorageError<DB::DatabaseError>> {
+        self.db.insert(
+            &DatabaseKey::Meta(LATEST_ID_KEY),
+            &id.as_u64().encode(),
+            Some(batch),
+        )?;
+        for snap_id in self.get_snapshot_ids()? {
+            if snap_id > id {
+                self.db
+                    .remove(&DatabaseKey::Meta(&snapshot_key(snap_id)), Some(batch))?;
+            }
+        }
+        Ok(())
+    }
+
     pub(crate) fn create_batch(&self) -> DB::Batch {
         self.db.create_batch()
     }
@@ -158,6 +416,15 @@ where
         Ok(self.db.get(&key.into())?)
     }
 
+    pub(crate) fn get_many(
+        &self,
+        keys: &[TrieKey],
+    ) -> Result<Vec<Option<ByteVec>>, BonsaiStorageError<DB::DatabaseError>> {
+        trace!("Getting {} keys from KeyValueDB", keys.len());
+        let keys: Vec<DatabaseKey> = keys.iter().map(Into::into).collect();
+        Ok(self.db.get_many(&keys)?)
+    }
+
     pub(crate) fn get_at(
         &self,
         _key: &TrieKey,
@@ -166,8 +433,81 @@ where
         todo!()
     }
 
-    pub(crate) fn get_latest_id(&self) -> Option<ID> {
-        todo!()
+    pub(crate) fn get_latest_id(
+        &self,
+    ) -> Result<Option<ID>, BonsaiStorageError<DB::DatabaseError>> {
+        self.db
+            .get(&DatabaseKey::Meta(LATEST_ID_KEY))?
+            .map(|value| Ok(ID::from_u64(u64::decode(&mut value.as_slice())?)))
+            .transpose()
+    }
+
+    /// Ids of the snapshots recorded in the database, in increasing order.
+    pub(crate) fn get_snapshot_ids(
+        &self,
+    ) -> Result<Vec<ID>, BonsaiStorageError<DB::DatabaseError>> {
+        let mut ids = Vec::new();
+        for entry in self
+            .db
+            .iter_by_prefix(&DatabaseKey::Meta(SNAPSHOT_KEY_PREFIX), None, None)?
+        {
+            let (key, _) = entry?;
+            let Ok(id) = <[u8; 8]>::try_from(&key[SNAPSHOT_KEY_PREFIX.len()..]) else {
+                return Err(BonsaiStorageError::InvalidMetaKey(key.to_vec()));
+            };
+            ids.push(ID::from_u64(u64::from_be_bytes(id)));
+        }
+        Ok(ids)
+    }
+
+    /// Identifiers of the tries that have leaves in the database, in the order of their keys.
+    ///
+    /// Seeks past the leaves of each trie, so only one leaf per trie is read.
+    pub(crate) fn get_identifiers(
+        &self,
+    ) -> Result<Vec<Vec<u8>>, BonsaiStorageError<DB::DatabaseError>> {
+        let mut identifiers = Vec::new();
+        let mut seek: Option<ByteVec> = None;
+        while let Some((key, _)) = self
+            .db
+            .iter_by_prefix(&DatabaseKey::Flat(&[]), seek.as_deref(), None)?
+            .next()
+            .transpose()?
+        {
+            let Some((identifier, _)) = split_identifier_prefix(&key) else {
+                return Err(BonsaiStorageError::InvalidFlatKey(key.to_vec()));
+            };
+            identifiers.push(identifier.to_vec());
+            let Some(next) = prefix_successor(&identifier_prefix(identifier)) else {
+                break;
+            };
+            seek = Some(next);
+        }
+        Ok(identifiers)
+    }
+
+    /// Ids of the commits in `ids` whose trie log is in the database, in increasing order.
+    ///
+    /// Read from the keys of the commit records, which are written and removed with the trie logs.
+    pub(crate) fn get_trie_log_ids(
+        &self,
+        ids: RangeInclusive<ID>,
+    ) -> Result<Vec<ID>, BonsaiStorageError<DB::DatabaseError>> {
+        let seek = commit_info_key(*ids.start());
+        let upper_bound = prefix_successor(&commit_info_key(*ids.end()));
+        let mut ids = Vec::new();
+        for entry in self.db.iter_by_prefix(
+            &DatabaseKey::Meta(COMMIT_INFO_KEY_PREFIX),
+            Some(&seek),
+            upper_bound.as_deref(),
+        )? {
+            let (key, _) = entry?;
+            let Ok(id) = <[u8; 8]>::try_from(&key[COMMIT_INFO_KEY_PREFIX.len()..]) else {
+                return Err(BonsaiStorageError::InvalidMetaKey(key.to_vec()));
+            };
+            ids.push(ID::from_u64(u64::from_be_bytes(id)));
+        }
+        Ok(ids)
     }
 
     pub(crate) fn contains(
@@ -225,66 +565,197 @@ where
 impl<DB, ID> KeyValueDB<DB, ID>
 where
     ID: Id,
-    DB: BonsaiDatabase + BonsaiPersistentDatabase<ID>,
+    DB: BonsaiDatabase
+        + BonsaiPersistentDatabase<ID, DatabaseError = <DB as BonsaiDatabase>::DatabaseError>,
 {
-    pub(crate) fn create_snapshot(&mut self, id: ID) {
-        if id.as_u64() % self.config.snapshot_interval == 0 {
-            self.db.snapshot(id);
+    /// Snapshots the database every `snapshot_interval` commits.
+    ///
+    /// The database only keeps the snapshot itself in memory, so its id is also recorded in the
+    /// database: after a restart the states it covers are rebuilt from the current state and the
+    /// trie logs, see [`KeyValueDB::get_transaction`].
+    ///
+    /// The snapshots taken after `id` belong to reverted commits and are dropped.
+    pub(crate) fn create_snapshot(
+        &mut self,
+        id: ID,
+    ) -> Result<(), BonsaiStorageError<<DB as BonsaiDatabase>::DatabaseError>> {
+        self.db.remove_snapshots_after(id);
+        if id.as_u64() % self.config.snapshot_interval != 0 {
+            return Ok(());
+        }
+        self.db.snapshot(id);
+
+        let mut batch = self.db.create_batch();
+        self.db
+            .insert(&DatabaseKey::Meta(&snapshot_key(id)), &[], Some(&mut batch))?;
+        if let Some(max_saved_snapshots) = self.config.max_saved_snapshots {
+            let mut ids = self.get_snapshot_ids()?;
+            if !ids.contains(&id) {
+                ids.push(id);
+                ids.sort();
+            }
+            for id in &ids[..ids.len().saturating_sub(max_saved_snapshots)] {
+                self.db
+                    .remove(&DatabaseKey::Meta(&snapshot_key(*id)), Some(&mut batch))?;
+            }
         }
+        self.db.write_batch(batch)?;
+        Ok(())
     }
 
+    /// Builds the state at `id` in a transaction, taking the shortest of two paths:
+    /// - from the nearest snapshot at or before `id`, replaying the trie logs of the following
+    ///   commits;
+    /// - from the current state, undoing the trie logs of the commits after `id`, newest first.
+    ///   This is also how states are rebuilt after a restart, when no snapshot is left in memory.
     pub(crate) fn get_transaction(
         &self,
         id: ID,
     ) -> Result<
         Option<DB::Transaction<'_>>,
-        BonsaiStorageError<<DB::Transaction<'_> as BonsaiDatabase>::DatabaseError>,
+        BonsaiStorageError<<DB as BonsaiDatabase>::DatabaseError>,
     > {
         log::debug!("get_transaction {id:?}");
-        let Some((snap_id, mut txn)) = self.db.transaction(id) else {
+        // The snapshots after the latest commit were reverted, they are only dropped by the next
+        // commit.
+        if !self
+            .get_latest_id()?
+            .is_some_and(|latest_id| id <= latest_id)
+        {
+            return Ok(None);
+        }
+        let head = self.head_to_rebuild(id)?;
+        let from_snapshot = match (self.db.nearest_snapshot(id), head) {
+            (Some(snap_id), Some(latest_id)) => {
+                id.as_u64() - snap_id.as_u64() <= latest_id.as_u64() - id.as_u64()
+            }
+            (snap_id, _) => snap_id.is_some(),
+        };
+
+        if from_snapshot {
+            if let Some((snap_id, mut txn)) = self.db.transaction(id) {
+                log::debug!("get_transaction {id:?} from snapshot {snap_id:?}");
+                self.replay_trie_logs(&mut txn, snap_id.as_u64() + 1..=id.as_u64(), false)?;
+                return Ok(Some(txn));
+            }
+        }
+        let Some(latest_id) = head else {
             return Ok(None);
         };
-        log::debug!("get_transaction {snap_id:?} {id:?}");
+        log::debug!("get_transaction {id:?} from head {latest_id:?}");
+        let mut txn = self.db.transaction_at_head();
+        self.replay_trie_logs(&mut txn, (id.as_u64() + 1..=latest_id.as_u64()).rev(), true)?;
+        Ok(Some(txn))
+    }
 
+    /// Latest commit id, if the state at `id` can be built from the current state.
+    ///
+    /// Like with a snapshot, this is only possible for ids no older than the oldest recorded
+    /// snapshot, and the trie logs of the commits after `id` must not be pruned.
+    fn head_to_rebuild(
+        &self,
+        id: ID,
+    ) -> Result<Option<ID>, BonsaiStorageError<<DB as BonsaiDatabase>::DatabaseError>> {
+        let latest_id = self.get_latest_id()?;
+        let snapshot_ids = self.get_snapshot_ids()?;
+        let Some(latest_id) = latest_id.filter(|latest_id| id <= *latest_id) else {
+            return Ok(None);
+        };
+        if !snapshot_ids.iter().any(|snap_id| *snap_id <= id) {
+            return Ok(None);
+        }
+        // Trie logs older than `max_saved_trie_logs` commits were pruned.
+        if let Some(max_saved_trie_logs) = self.config.max_saved_trie_logs {
+            if latest_id.as_u64() - id.as_u64() > max_saved_trie_logs as u64 {
+                return Ok(None);
+            }
+        }
+        Ok(Some(latest_id))
+    }
+
+    /// Replays the trie logs of the commits `ids` on `txn`, in that order: forwards sets their new
+    /// values, backwards restores their old values.
+    fn replay_trie_logs(
+        &self,
+        txn: &mut DB::Transaction<'_>,
+        ids: impl Iterator<Item = u64>,
+        backwards: bool,
+    ) -> Result<(), BonsaiStorageError<<DB as BonsaiDatabase>::DatabaseError>> {
         let mut batch = txn.create_batch();
-        for cur_id in snap_id.as_u64()..id.as_u64() {
+        for cur_id in ids {
             let cur_id = ID::from_u64(cur_id);
             let changes = ChangeBatch::deserialize(
                 &cur_id,
                 self.db
-                    .get_by_prefix(&DatabaseKey::TrieLog(&cur_id.to_bytes()))
-                    .map_err(|_| {
-                        BonsaiStorageError::Transaction(format!(
-                            "database is missing trie logs for {:?}",
-                            cur_id
-                        ))
-                    })?,
-            );
-            // Apply backwards
+                    .iter_by_prefix(&DatabaseKey::TrieLog(&cur_id.to_bytes()), None, None)?,
+            )?;
             for (key, change) in changes.0 {
                 let key = DatabaseKey::from(&key);
-                match (&change.old_value, &change.new_value) {
-                    (Some(old_value), Some(_)) => {
-                        txn.insert(&key, old_value, Some(&mut batch))?;
-                    }
-                    (Some(old_value), None) => {
-                        txn.insert(&key, old_value, Some(&mut batch))?;
-                    }
-                    (None, Some(_)) => {
-                        txn.remove(&key, Some(&mut batch))?;
-                    }
-                    (None, None) => unreachable!(),
+                let value = if backwards {
+                    change.old_value
+                } else {
+                    change.new_value
+                };
+                match value {
+                    Some(value) => txn.insert(&key, &value, Some(&mut batch))?,
+                    None => txn.remove(&key, Some(&mut batch))?,
                 };
             }
         }
         txn.write_batch(batch)?;
-        Ok(Some(txn))
+        Ok(())
     }
 
+    #[allow(clippy::type_complexity)]
+    /// Checks that a transactional state can be merged into this database and returns the leaf
+    /// changes of its commits, in order, along with the flat keys changed by the commits of this
+    /// database since the transactional state was created, read from the trie logs.
     pub(crate) fn merge(
-        &mut self,
-        _transaction: KeyValueDB<DB::Transaction<'_>, ID>,
-    ) -> Result<(), BonsaiStorageError<<DB as BonsaiPersistentDatabase<ID>>::DatabaseError>> {
-        todo!("unused yet")
+        &self,
+        transaction: KeyValueDB<DB::Transaction<'_>, ID>,
+    ) -> Result<
+        (Vec<(ID, LeafChanges)>, HashSet<ByteVec>),
+        BonsaiStorageError<<DB as BonsaiDatabase>::DatabaseError>,
+    > {
+        let Some(created_at) = transaction.created_at else {
+            return Err(BonsaiStorageError::Merge(MergeError::NotTransactional));
+        };
+        let commits = transaction.transactional_commits;
+        let latest_id = self.get_latest_id()?;
+        if let (Some(latest_id), Some((id, _))) = (latest_id, commits.first()) {
+            if *id <= latest_id {
+                return Err(BonsaiStorageError::Merge(MergeError::CommitOrder {
+                    id: id.as_u64(),
+                    latest_id: latest_id.as_u64(),
+                }));
+            }
+        }
+        let mut base_keys = HashSet::new();
+        let Some(latest_id) = latest_id.filter(|latest_id| *latest_id > created_at) else {
+            return Ok((commits, base_keys));
+        };
+        if self
+            .config
+            .max_saved_trie_logs
+            .is_some_and(|max| latest_id.as_u64() - created_at.as_u64() > max as u64)
+        {
+            return Err(BonsaiStorageError::Merge(MergeError::MissingTrieLogs(
+                created_at.as_u64() + 1,
+            )));
+        }
+
+        for id in created_at.as_u64() + 1..=latest_id.as_u64() {
+            let id = ID::from_u64(id);
+            let changes = ChangeBatch::deserialize(
+                &id,
+                self.db
+                    .iter_by_prefix(&DatabaseKey::TrieLog(&id.to_bytes()), None, None)?,
+            )?;
+            base_keys.extend(changes.0.into_iter().filter_map(|(key, _)| match key {
+                TrieKey::Flat(key) => Some(key),
+                _ => None,
+            }));
+        }
+        Ok((commits, base_keys))
     }
 }
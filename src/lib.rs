@@ -5,7 +5,7 @@
 //! Example usage with a RocksDB database:
 //! ```ignore
 //! # use bonsai_trie::{
-//! #     databases::{RocksDB, create_rocks_db, RocksDBConfig},
+//! #     databases::{RocksDB, open_rocks_db, RocksDBConfig, RocksDBOpenOptions},
 //! #     BonsaiStorageError,
 //! #     id::{BasicIdBuilder, BasicId},
 //! #     BonsaiStorage, BonsaiStorageConfig, BonsaiTrieHash,
@@ -13,12 +13,12 @@
 //! # use starknet_types_core::felt::Felt;
 //! # use starknet_types_core::hash::Pedersen;
 //! # use bitvec::prelude::*;
-//! let db = create_rocks_db("./rocksdb").unwrap();
+//! let db = open_rocks_db("./rocksdb", &RocksDBOpenOptions::default()).unwrap();
 //! let config = BonsaiStorageConfig::default();
 //!
 //! let identifier = vec![];
 //! let mut bonsai_storage: BonsaiStorage<_, _, Pedersen> =
-//!     BonsaiStorage::new(RocksDB::new(&db, RocksDBConfig::default()), config, 251);
+//!     BonsaiStorage::new(RocksDB::new(&db, RocksDBConfig::default()), config, 251).unwrap();
 //! let mut id_builder = BasicIdBuilder::new();
 //!
 //! let pair1 = (vec![1, 2, 1], Felt::from_hex("0x66342762FDD54D033c195fec3ce2568b62052e").unwrap());
@@ -85,6 +85,8 @@
 //! bonsai_storage.commit(id_builder.new_id()).unwrap();
 //! ```
 #![cfg_attr(not(feature = "std"), no_std)]
+// Without the storage, only the helpers used by proof verification are reachable.
+#![cfg_attr(not(feature = "storage"), allow(dead_code, unused_imports))]
 
 // hashbrown uses ahash by default instead of siphash
 pub(crate) type HashMap<K, V> = hashbrown::HashMap<K, V>;
@@ -95,48 +97,76 @@ pub(crate) use hashbrown::hash_map;
 extern crate alloc;
 #[cfg(not(feature = "std"))]
 pub(crate) use alloc::{
-    collections::BTreeMap,
-    format,
-    string::{String, ToString},
-    vec,
-    vec::Vec,
+    boxed::Box, collections::BTreeMap, format, string::String, sync::Arc, vec, vec::Vec,
 };
-use core::fmt;
+use core::{fmt, ops::RangeInclusive};
+#[cfg(feature = "storage")]
 use id::Id;
 #[cfg(feature = "std")]
 pub(crate) use std::{
-    collections::BTreeMap,
-    format,
-    string::{String, ToString},
-    vec,
-    vec::Vec,
+    boxed::Box, collections::BTreeMap, format, string::String, sync::Arc, vec, vec::Vec,
 };
 
 pub type ByteVec = smallvec::SmallVec<[u8; 32]>;
 pub type BitVec = bitvec::vec::BitVec<u8, bitvec::order::Msb0>;
 pub type BitSlice = bitvec::slice::BitSlice<u8, bitvec::order::Msb0>;
 
+#[cfg(feature = "storage")]
 mod changes;
+#[cfg(feature = "storage")]
+mod commit_info;
 mod hasher;
+#[cfg(feature = "storage")]
 mod key_value_db;
+#[cfg(feature = "storage")]
+mod merge;
 #[cfg(feature = "std")]
 mod metrics;
+#[cfg(feature = "storage")]
+mod observer;
+#[cfg(feature = "storage")]
+mod reader;
 mod trie;
+#[cfg(feature = "storage")]
+mod trie_log_stream;
 
+#[cfg(feature = "storage")]
 mod bonsai_database;
 /// All databases already implemented in this crate.
+#[cfg(feature = "storage")]
 pub mod databases;
+#[cfg(feature = "storage")]
 mod error;
 /// Definition and basic implementation of an CommitID
+#[cfg(feature = "storage")]
 pub mod id;
+/// Upgrades of databases created with an older storage layout.
+#[cfg(feature = "storage")]
+pub mod migration;
 
-pub use bonsai_database::{BonsaiDatabase, BonsaiPersistentDatabase, DBError, DatabaseKey};
-pub use error::BonsaiStorageError;
+#[cfg(feature = "storage")]
+pub use bonsai_database::{
+    BonsaiDatabase, BonsaiPersistentDatabase, DBError, DatabaseIterator, DatabaseKey,
+};
+#[cfg(feature = "storage")]
+pub use commit_info::{CommitInfo, COMMIT_INFO_VERSION};
+#[cfg(feature = "storage")]
+pub use error::{BonsaiStorageError, MergeConflict, MergeError, TrieLogError};
 pub use hasher::BonsaiHasher;
 #[cfg(feature = "pedersen-gpu")]
 pub use hasher::PedersenGpu;
+#[cfg(feature = "storage")]
+pub use merge::{MergePolicy, MergeResolver};
+#[cfg(feature = "storage")]
+pub use observer::{CommitEvent, CommitKind, CommitObserver};
+#[cfg(feature = "storage")]
+pub use reader::BonsaiReader;
 pub use trie::path::Path;
 pub use trie::proof::{MultiProof, ProofNode};
+#[cfg(feature = "storage")]
+pub use trie::trees::Savepoint;
+#[cfg(feature = "storage")]
+pub use trie_log_stream::TRIE_LOG_STREAM_VERSION;
 
 #[cfg(test)]
 mod tests;
@@ -158,12 +188,17 @@ pub(crate) trait EncodeExt: parity_scale_codec::Encode {
 }
 impl<T: parity_scale_codec::Encode> EncodeExt for T {}
 
-use key_value_db::KeyValueDB;
+#[cfg(feature = "storage")]
+use commit_info::commit_info_key;
+#[cfg(feature = "storage")]
+use key_value_db::{leaf_changes_by_identifier, KeyValueDB};
 use starknet_types_core::felt::Felt;
-use trie::{tree::bytes_to_bitvec, trees::MerkleTrees};
+#[cfg(feature = "storage")]
+use trie::{tree::bytes_to_bitvec, trees::MerkleTrees, trie_db::split_identifier_prefix, TrieKey};
 
 /// Structure that contains the configuration for the BonsaiStorage.
 /// A default implementation is provided with coherent values.
+#[cfg(feature = "storage")]
 #[derive(Clone)]
 pub struct BonsaiStorageConfig {
     /// Maximal number of trie logs saved.
@@ -180,6 +215,7 @@ pub struct BonsaiStorageConfig {
     pub snapshot_interval: u64,
 }
 
+#[cfg(feature = "storage")]
 impl Default for BonsaiStorageConfig {
     fn default() -> Self {
         Self {
@@ -194,6 +230,7 @@ impl Default for BonsaiStorageConfig {
 /// It contains the old value and the new value.
 /// If the `old_value` is None, it means that the key was not present in the trie before the change.
 /// If the `new_value` is None, it means that the key was removed from the trie.
+#[cfg(feature = "storage")]
 #[derive(Debug, Clone, PartialEq, Eq)]
 pub struct Change {
     pub old_value: Option<Felt>,
@@ -203,16 +240,20 @@ pub struct Change {
 /// Structure that hold the trie and all the necessary information to work with it.
 ///
 /// This structure is the main entry point to work with this crate.
+#[cfg(feature = "storage")]
 pub struct BonsaiStorage<ChangeID: Id, DB: BonsaiDatabase, H: BonsaiHasher + Send + Sync> {
     tries: MerkleTrees<H, DB, ChangeID>,
+    observers: Vec<Box<dyn CommitObserver<ChangeID>>>,
 }
 
+#[cfg(feature = "storage")]
 impl<ChangeID: Id, DB: BonsaiDatabase + fmt::Debug, H: BonsaiHasher + Send + Sync> fmt::Debug
     for BonsaiStorage<ChangeID, DB, H>
 {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         f.debug_struct("BonsaiStorage")
             .field("tries", &self.tries)
+            .field("observers", &self.observers.len())
             .finish()
     }
 }
@@ -227,6 +268,7 @@ where
     fn clone(&self) -> Self {
         Self {
             tries: self.tries.clone(),
+            observers: Vec::new(),
         }
     }
 }
@@ -234,6 +276,7 @@ where
 /// Trie root hash type.
 pub type BonsaiTrieHash = Felt;
 
+#[cfg(feature = "storage")]
 impl<ChangeID, DB, H> BonsaiStorage<ChangeID, DB, H>
 where
     DB: BonsaiDatabase,
@@ -241,11 +284,21 @@ where
     H: BonsaiHasher + Send + Sync,
 {
     /// Create a new bonsai storage instance
-    pub fn new(db: DB, config: BonsaiStorageConfig, max_height: u8) -> Self {
-        let key_value_db = KeyValueDB::new(db, config.into(), None);
-        Self {
+    ///
+    /// Fails if the database was written with another storage layout, older databases can be
+    /// upgraded with [`migration::migrate`].
+    pub fn new(
+        db: DB,
+        config: BonsaiStorageConfig,
+        max_height: u8,
+    ) -> Result<Self, BonsaiStorageError<DB::DatabaseError>> {
+        let unversioned = migration::check_schema_version(&db)?;
+        let mut key_value_db = KeyValueDB::new(db, config.into(), None);
+        key_value_db.unversioned = unversioned;
+        Ok(Self {
             tries: MerkleTrees::new(key_value_db, max_height),
-        }
+            observers: Vec::new(),
+        })
     }
 
     pub fn new_from_transactional_state(
@@ -256,7 +309,10 @@ where
     ) -> Result<Self, BonsaiStorageError<DB::DatabaseError>> {
         let key_value_db = KeyValueDB::new(db, config.into(), Some(created_at));
         let tries = MerkleTrees::<H, DB, ChangeID>::new(key_value_db, max_height);
-        Ok(Self { tries })
+        Ok(Self {
+            tries,
+            observers: Vec::new(),
+        })
     }
 
     /// Insert a new key/value in the trie, overwriting the previous value if it exists.
@@ -316,6 +372,9 @@ where
     /// Go to a specific commit ID.
     /// If insert/remove is called between the last `commit()` and a call to this function,
     /// the in-memory changes will be discarded.
+    ///
+    /// `requested_id` becomes the latest commit: the commits after it are forgotten, with their
+    /// trie logs and snapshots, and their ids can be committed again.
     pub fn revert_to(
         &mut self,
         requested_id: ChangeID,
@@ -323,6 +382,9 @@ where
     ) -> Result<(), BonsaiStorageError<DB::DatabaseError>> {
         self.tries.reset_to_last_commit()?;
 
+        let observed = !self.observers.is_empty();
+        // Leaf changes made by the revert, for the observers.
+        let mut reverted = changes::ChangeBatch::default();
         let kv = self.tries.db_mut();
 
         // Clear current changes
@@ -335,15 +397,12 @@ where
         // ensure that the id is the latest by checking for an id one higher
         // note that we don't use contains() because we have a prefix, not the full key
         let next_id = ChangeID::from_u64(next_id);
-        if let Ok(matches) = kv
-            .db
-            .get_by_prefix(&DatabaseKey::TrieLog(&next_id.to_bytes()))
+        if let Ok(mut matches) =
+            kv.db
+                .iter_by_prefix(&DatabaseKey::TrieLog(&next_id.to_bytes()), None, None)
         {
-            if !matches.is_empty() {
-                return Err(BonsaiStorageError::GoTo(format!(
-                    "current_id ({}) is not the latest",
-                    latest_id
-                )));
+            if matches.next().is_some() {
+                return Err(BonsaiStorageError::NotLatest { id: latest_id });
             }
         }
 
@@ -351,9 +410,10 @@ where
         if latest_id == revert_to_id {
             return Ok(());
         } else if latest_id < revert_to_id {
-            return Err(BonsaiStorageError::GoTo(
-                "current_id must be >= revert_to".to_string(),
-            ));
+            return Err(BonsaiStorageError::RevertToFuture {
+                id: revert_to_id,
+                latest_id,
+            });
         }
 
         let mut batch = kv.db.create_batch();
@@ -362,12 +422,32 @@ where
 
             let changes = changes::ChangeBatch::deserialize(
                 &id,
-                kv.db.get_by_prefix(&DatabaseKey::TrieLog(&id.to_bytes()))?,
-            )
+                kv.db
+                    .iter_by_prefix(&DatabaseKey::TrieLog(&id.to_bytes()), None, None)?,
+            )?
             .0;
 
             kv.db
                 .remove_by_prefix(&DatabaseKey::TrieLog(&id.to_bytes()))?;
+            kv.db
+                .remove(&DatabaseKey::Meta(&commit_info_key(id)), Some(&mut batch))?;
+
+            if observed {
+                for (key, change) in changes
+                    .iter()
+                    .filter(|(key, _)| matches!(key, TrieKey::Flat(_)))
+                {
+                    let reverted =
+                        reverted
+                            .0
+                            .entry(key.clone())
+                            .or_insert_with(|| changes::Change {
+                                old_value: change.new_value.clone(),
+                                new_value: None,
+                            });
+                    reverted.new_value = change.old_value.clone();
+                }
+            }
 
             // Add revert changes to batch
             for (key, change) in changes {
@@ -384,11 +464,68 @@ where
             }
         }
 
+        kv.revert_records(requested_id, &mut batch)?;
         // Write revert changes and trie logs truncation
         kv.db.write_batch(batch)?;
+        // Reverted commits of a transactional state must not be replayed when it is merged.
+        kv.transactional_commits
+            .retain(|(id, _)| id.as_u64() <= revert_to_id);
+
+        if observed {
+            let changes = leaf_changes_by_identifier::<DB::DatabaseError>(&reverted)?;
+            let roots = changes
+                .keys()
+                .map(|identifier| Ok((identifier.clone(), self.tries.root_hash(identifier)?)))
+                .collect::<Result<_, BonsaiStorageError<DB::DatabaseError>>>()?;
+            self.notify(Some(CommitEvent {
+                kind: CommitKind::Revert,
+                id: requested_id,
+                changes,
+                roots,
+            }));
+        }
         Ok(())
     }
 
+    /// Registers an observer, called after every successful commit, transactional commit, replayed
+    /// trie log and revert of this storage.
+    pub fn add_commit_observer(&mut self, observer: impl CommitObserver<ChangeID> + 'static) {
+        self.observers.push(Box::new(observer));
+    }
+
+    fn notify(&mut self, event: Option<CommitEvent<ChangeID>>) {
+        if let Some(event) = event {
+            for observer in &mut self.observers {
+                observer.on_commit(&event);
+            }
+        }
+    }
+
+    /// Get the record of a commit: the root hashes of the tries it changed, its number of
+    /// changes and its metadata.
+    ///
+    /// Returns `None` for unknown ids, for commits whose trie log was pruned or disabled, and for
+    /// commits made before these records existed.
+    pub fn commit_info(
+        &self,
+        id: ChangeID,
+    ) -> Result<Option<CommitInfo<ChangeID>>, BonsaiStorageError<DB::DatabaseError>> {
+        self.tries.db_ref().get_commit_info(id)
+    }
+
+    /// Serialises the trie logs of the commits in `ids`, with their [`CommitInfo`], into a
+    /// versioned stream that [`BonsaiStorage::apply_trie_log`] replays on another storage.
+    ///
+    /// Ids without a commit are skipped, but the range fails with [`TrieLogError::Missing`] if it
+    /// reaches back to commits whose trie logs were pruned.
+    pub fn export_trie_logs(
+        &self,
+        ids: RangeInclusive<ChangeID>,
+    ) -> Result<Vec<u8>, BonsaiStorageError<DB::DatabaseError>> {
+        let commits = self.tries.db_ref().get_trie_logs(ids)?;
+        Ok(trie_log_stream::encode_trie_logs(&commits))
+    }
+
     /// Get all changes applied at a certain commit ID.
     #[allow(clippy::type_complexity)]
     pub fn get_changes(
@@ -422,11 +559,43 @@ where
         &mut self,
         id: ChangeID,
     ) -> Result<(), BonsaiStorageError<DB::DatabaseError>> {
-        self.tries.commit()?;
-        self.tries.db_mut().commit(id)?;
+        let event = self
+            .tries
+            .commit_with_info(id, None, !self.observers.is_empty())?;
+        self.notify(event);
         Ok(())
     }
 
+    /// Throws away all the changes made since the last commit, in all the tries, so that the
+    /// next operation starts from the last committed state.
+    ///
+    /// Savepoints taken before can no longer be rolled back to.
+    pub fn discard_pending(&mut self) -> Result<(), BonsaiStorageError<DB::DatabaseError>> {
+        self.tries.reset_to_last_commit()
+    }
+
+    /// Marks the changes made since the last commit, in all the tries, so that the ones made
+    /// afterwards can be undone with [`BonsaiStorage::rollback_to`].
+    ///
+    /// Taking a savepoint costs nothing: the previous value of every key set is kept until the
+    /// next commit, and a rollback only reloads the tries whose keys it restores.
+    ///
+    /// The savepoint can be rolled back to any number of times, until the next commit or a
+    /// rollback to an earlier savepoint.
+    pub fn savepoint(&self) -> Savepoint {
+        self.tries.savepoint()
+    }
+
+    /// Undoes the changes made since `savepoint` was taken, in all the tries.
+    ///
+    /// Fails if a commit, a revert or a rollback to an earlier savepoint happened since.
+    pub fn rollback_to(
+        &mut self,
+        savepoint: &Savepoint,
+    ) -> Result<(), BonsaiStorageError<DB::DatabaseError>> {
+        self.tries.rollback_to(savepoint)
+    }
+
     /// Get all the keys in a specific trie.
     pub fn get_keys(
         &self,
@@ -444,23 +613,57 @@ where
         self.tries.get_key_value_pairs(identifier)
     }
 
+    /// Get the identifiers of the tries that have leaves in the database, sorted by their
+    /// encoding in keys: by length, then by bytes.
+    pub fn identifiers(&self) -> Result<Vec<Vec<u8>>, BonsaiStorageError<DB::DatabaseError>> {
+        self.tries.db_ref().get_identifiers()
+    }
+
+    /// Get the ids of the commits whose trie log is still in the database, in increasing order:
+    /// the ones [`BonsaiStorage::revert_to`] and [`BonsaiStorage::get_changes`] can use.
+    pub fn trie_log_ids(&self) -> Result<Vec<ChangeID>, BonsaiStorageError<DB::DatabaseError>> {
+        self.tries
+            .db_ref()
+            .get_trie_log_ids(ChangeID::from_u64(0)..=ChangeID::from_u64(u64::MAX))
+    }
+
     /// Get the id from the latest commit, or `None` if no commit has taken place yet.
-    pub fn get_latest_id(&self) -> Option<ChangeID> {
+    pub fn get_latest_id(&self) -> Result<Option<ChangeID>, BonsaiStorageError<DB::DatabaseError>> {
         self.tries.db_ref().get_latest_id()
     }
 
-    pub fn get_multi_proof(
+    /// Loads from the database, in a few batched reads, the trie nodes on the paths to `keys`.
+    ///
+    /// Calling this before inserting or removing many keys of the same trie saves one database
+    /// read per node during the updates.
+    pub fn prefetch(
         &mut self,
         identifier: &[u8],
         keys: impl IntoIterator<Item = impl AsRef<BitSlice>>,
+    ) -> Result<(), BonsaiStorageError<DB::DatabaseError>> {
+        let keys: Vec<_> = keys.into_iter().collect();
+        let keys: Vec<&BitSlice> = keys.iter().map(AsRef::as_ref).collect();
+        self.tries.prefetch(identifier, &keys)
+    }
+
+    /// Get a proof of the values of `keys` in a trie, uncommitted changes included.
+    ///
+    /// The nodes read for the proof are not kept in the trie, so proofs can be generated from
+    /// several threads while the storage is shared.
+    pub fn get_multi_proof(
+        &self,
+        identifier: &[u8],
+        keys: impl IntoIterator<Item = impl AsRef<BitSlice>>,
     ) -> Result<MultiProof, BonsaiStorageError<DB::DatabaseError>> {
         self.tries.get_multi_proof(identifier, keys)
     }
 }
 
+#[cfg(feature = "storage")]
 impl<ChangeID, DB, H> BonsaiStorage<ChangeID, DB, H>
 where
-    DB: BonsaiDatabase + BonsaiPersistentDatabase<ChangeID>,
+    DB: BonsaiDatabase
+        + BonsaiPersistentDatabase<ChangeID, DatabaseError = <DB as BonsaiDatabase>::DatabaseError>,
     ChangeID: id::Id,
     H: BonsaiHasher + Send + Sync,
 {
@@ -469,9 +672,50 @@ where
         &mut self,
         id: ChangeID,
     ) -> Result<(), BonsaiStorageError<<DB as BonsaiDatabase>::DatabaseError>> {
-        self.tries.commit()?;
-        self.tries.db_mut().commit(id)?;
-        self.tries.db_mut().create_snapshot(id);
+        let event = self
+            .tries
+            .commit_with_info(id, None, !self.observers.is_empty())?;
+        self.tries.db_mut().create_snapshot(id)?;
+        self.notify(event);
+        Ok(())
+    }
+
+    /// Same as [`BonsaiStorage::commit`], also storing `metadata`, such as a block hash, in the
+    /// [`CommitInfo`] of the commit.
+    pub fn commit_with_metadata(
+        &mut self,
+        id: ChangeID,
+        metadata: &[u8],
+    ) -> Result<(), BonsaiStorageError<<DB as BonsaiDatabase>::DatabaseError>> {
+        let event = self
+            .tries
+            .commit_with_info(id, Some(metadata), !self.observers.is_empty())?;
+        self.tries.db_mut().create_snapshot(id)?;
+        self.notify(event);
+        Ok(())
+    }
+
+    /// Replays the commits of a stream written by [`BonsaiStorage::export_trie_logs`]: their leaves
+    /// are committed with the same ids and metadata, recording trie logs so they can be reverted.
+    ///
+    /// Each commit must be newer than the latest one and apply on the current state, and the
+    /// roots recomputed from its leaves must be the ones recorded in its header: the trie nodes
+    /// of the stream are not trusted. The commits before a failing one stay applied.
+    /// Like [`BonsaiStorage::revert_to`], this discards the changes made since the last commit.
+    pub fn apply_trie_log(
+        &mut self,
+        stream: &[u8],
+    ) -> Result<(), BonsaiStorageError<<DB as BonsaiDatabase>::DatabaseError>> {
+        let commits = trie_log_stream::decode_trie_logs(stream)?;
+        self.tries.reset_to_last_commit()?;
+        for (info, changes) in commits {
+            let id = info.id;
+            let event = self
+                .tries
+                .apply_trie_log(info, &changes, !self.observers.is_empty())?;
+            self.tries.db_mut().create_snapshot(id)?;
+            self.notify(event);
+        }
         Ok(())
     }
 
@@ -505,48 +749,126 @@ where
         }
     }
 
+    /// Read-only handle on the last committed state, that can be sent to other threads and keeps
+    /// working while this storage commits.
+    ///
+    /// It is backed by a snapshot of the database, which is cheap for all the databases of this
+    /// crate.
+    pub fn reader(&self) -> BonsaiReader<ChangeID, DB::Reader, H> {
+        let db = KeyValueDB::new(
+            self.tries.db_ref().db.reader(),
+            self.tries.db_ref().get_config(),
+            None,
+        );
+        BonsaiReader {
+            tries: MerkleTrees::new(db, self.tries.max_height),
+        }
+    }
+
     /// Get a copy of the config that can be used to create a transactional state or a new bonsai storage.
     pub fn get_config(&self) -> BonsaiStorageConfig {
         self.tries.db_ref().get_config().into()
     }
 
     /// Merge a transactional state into the main trie.
+    ///
+    /// The commits made in the transactional state are replayed here with their ids, then its
+    /// changes since its last commit are applied on top of the uncommitted changes of this
+    /// storage.
+    ///
+    /// Keys changed both in the transactional state and here since it was created, committed or
+    /// not, are handled by `policy`. If it makes the merge fail, this storage is left untouched
+    /// and the keys are reported in [`MergeError::Conflicts`].
+    ///
+    /// The default [`MergePolicy::LastWriterWins`] only merges a transactional state created at
+    /// the latest commit of this storage, and fails with [`MergeError::Outdated`] otherwise.
     pub fn merge(
         &mut self,
         transactional_bonsai_storage: BonsaiStorage<ChangeID, DB::Transaction<'_>, H>,
+        mut policy: MergePolicy<'_>,
     ) -> Result<(), BonsaiStorageError<<DB as BonsaiPersistentDatabase<ChangeID>>::DatabaseError>>
-    where
-        <DB as BonsaiDatabase>::DatabaseError: core::fmt::Debug,
     {
-        // memorize changes
-        let MerkleTrees { db, trees, .. } = transactional_bonsai_storage.tries;
-
-        self.tries.db_mut().merge(db)?;
-
-        // apply changes
-        for (identifier, tree) in trees {
-            for (k, op) in tree.cache_leaf_modified() {
-                match op {
-                    crate::trie::tree::InsertOrRemove::Insert(v) => {
-                        self.insert(&identifier, &bytes_to_bitvec(k), v)
-                            .map_err(|e| {
-                                BonsaiStorageError::Merge(format!(
-                                    "While merging insert({:?} {}) faced error: {:?}",
-                                    k, v, e
-                                ))
-                            })?;
-                    }
-                    crate::trie::tree::InsertOrRemove::Remove => {
-                        self.remove(&identifier, &bytes_to_bitvec(k)).map_err(|e| {
-                            BonsaiStorageError::Merge(format!(
-                                "While merging remove({:?}) faced error: {:?}",
-                                k, e
-                            ))
-                        })?;
-                    }
+        let storage_error = |e: BonsaiStorageError<<DB as BonsaiDatabase>::DatabaseError>| {
+            e.map_database(|e| BonsaiStorageError::Merge(MergeError::Storage(e)))
+        };
+        // The default policy only merges a transactional state of the latest commit.
+        if matches!(policy, MergePolicy::LastWriterWins) {
+            let created_at = transactional_bonsai_storage.tries.db.created_at;
+            let latest_id = self.tries.db_ref().get_latest_id().map_err(storage_error)?;
+            if let (Some(created_at), Some(latest_id)) = (created_at, latest_id) {
+                if created_at < latest_id {
+                    return Err(BonsaiStorageError::Merge(MergeError::Outdated {
+                        created_at: created_at.as_u64(),
+                        latest_id: latest_id.as_u64(),
+                    }));
+                }
+            }
+        }
+        let uncommitted = transactional_bonsai_storage
+            .tries
+            .uncommitted_leaf_changes();
+        let (commits, committed_keys) = self
+            .tries
+            .db_ref()
+            .merge(transactional_bonsai_storage.tries.db)
+            .map_err(storage_error)?;
+        let pending = self.tries.uncommitted_leaf_changes();
+        let pending_keys: HashSet<&ByteVec> = pending.iter().map(|(key, _)| key).collect();
+
+        // Last value of each key in the transactional state.
+        let mut txn_values = BTreeMap::new();
+        for (key, value) in commits
+            .iter()
+            .flat_map(|(_, changes)| changes)
+            .chain(&uncommitted)
+        {
+            txn_values.insert(key.clone(), *value);
+        }
+        let mut resolved = HashMap::new();
+        let mut conflicts = Vec::new();
+        for (key, txn_value) in &txn_values {
+            if !committed_keys.contains(key) && !pending_keys.contains(key) {
+                continue;
+            }
+            let Some((identifier, path)) = split_identifier_prefix(key) else {
+                continue;
+            };
+            let path = bytes_to_bitvec(path);
+            let base_value = self.tries.get(identifier, &path).map_err(storage_error)?;
+            let conflict = MergeConflict {
+                identifier: identifier.to_vec(),
+                key: path,
+                base_value,
+                txn_value: *txn_value,
+            };
+            match policy.resolve(conflict) {
+                Ok(value) => {
+                    resolved.insert(key.clone(), value);
                 }
+                Err(conflict) => conflicts.push(conflict),
             }
         }
+        if !conflicts.is_empty() {
+            return Err(BonsaiStorageError::Merge(MergeError::Conflicts(conflicts)));
+        }
+
+        self.tries.reset_to_last_commit().map_err(storage_error)?;
+        for (id, changes) in commits {
+            for (key, value) in changes {
+                let value = resolved.get(&key).copied().unwrap_or(value);
+                self.tries.set_flat(&key, value).map_err(storage_error)?;
+            }
+            self.commit(id).map_err(storage_error)?;
+        }
+        // The uncommitted changes of this storage were reset, they are applied again.
+        for (key, value) in pending
+            .iter()
+            .filter(|(key, _)| !txn_values.contains_key(key))
+            .chain(&uncommitted)
+        {
+            let value = resolved.get(key).copied().unwrap_or(*value);
+            self.tries.set_flat(key, value).map_err(storage_error)?;
+        }
         Ok(())
     }
 }
@@ -0,0 +1,48 @@
+use starknet_types_core::felt::Felt;
+
+use crate::{BitSlice, Box, MergeConflict};
+
+/// Resolver of a [`MergePolicy::Resolve`], called with the identifier of the trie, the key, the
+/// value in the storage and the value in the transactional state, and returning the value to
+/// keep. `None` values are removed keys.
+pub type MergeResolver<'a> =
+    Box<dyn FnMut(&[u8], &BitSlice, Option<Felt>, Option<Felt>) -> Option<Felt> + 'a>;
+
+/// How [`crate::BonsaiStorage::merge`] handles keys changed both in the transactional state and in
+/// the storage since the transactional state was created.
+#[derive(Default)]
+pub enum MergePolicy<'a> {
+    /// The last writer wins: the transactional state overwrites the uncommitted changes of the
+    /// storage, but the merge fails with [`crate::MergeError::Outdated`] once the storage
+    /// committed since the transactional state was created.
+    #[default]
+    LastWriterWins,
+    /// The merge fails on any key changed on both sides.
+    Fail,
+    /// The value of the storage is kept.
+    PreferBase,
+    /// The value of the transactional state is kept.
+    PreferTransaction,
+    /// The value returned by the resolver is kept.
+    Resolve(MergeResolver<'a>),
+}
+
+impl MergePolicy<'_> {
+    /// Value to keep for a key changed on both sides, or the conflict back if the merge must fail.
+    pub(crate) fn resolve(
+        &mut self,
+        conflict: MergeConflict,
+    ) -> Result<Option<Felt>, MergeConflict> {
+        match self {
+            MergePolicy::Fail => Err(conflict),
+            MergePolicy::LastWriterWins | MergePolicy::PreferTransaction => Ok(conflict.txn_value),
+            MergePolicy::PreferBase => Ok(conflict.base_value),
+            MergePolicy::Resolve(resolver) => Ok(resolver(
+                &conflict.identifier,
+                &conflict.key,
+                conflict.base_value,
+                conflict.txn_value,
+            )),
+        }
+    }
+}
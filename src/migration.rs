@@ -0,0 +1,319 @@
+//! On-disk schema versioning and in-place upgrades of databases written with an older storage
+//! layout.
+//!
+//! The schema version is stored as a SCALE-encoded `u32` under [`DatabaseKey::Meta`]. A database
+//! without this record is either empty, in which case it is stamped with
+//! [`CURRENT_SCHEMA_VERSION`] by its first commit or by [`migrate`], or was written before
+//! versioning was introduced, in which case it is at version `0`. Opening a database never writes
+//! to it, so read-only databases can be checked too.
+//!
+//! [`migrate`] upgrades a database one version at a time. Each step is written in batches of
+//! bounded size that record its progress, the last one together with the version it upgrades to,
+//! so an interrupted migration resumes from the last written batch.
+
+use crate::{
+    bonsai_database::{BonsaiDatabase, DatabaseKey},
+    format,
+    id::Id,
+    trie::trie_db::identifier_prefix,
+    BonsaiStorageError, ByteVec, Vec,
+};
+use parity_scale_codec::{Decode, Encode};
+
+/// Version of the storage layout written by this crate.
+///
+/// | Version | Change                                                      |
+/// |---------|-------------------------------------------------------------|
+/// | 0       | Unversioned layout, identifiers are concatenated with keys. |
+/// | 1       | Identifiers are prefixed with their length.                 |
+/// | 2       | Compact trie node encoding without the node height.         |
+pub const CURRENT_SCHEMA_VERSION: u32 = 2;
+
+pub(crate) const SCHEMA_VERSION_KEY: &[u8] = b"schema_version";
+
+/// Meta key of the progress of an interrupted migration step: its phase and the last key it
+/// rewrote, SCALE-encoded.
+pub(crate) const MIGRATION_PROGRESS_KEY: &[u8] = b"migration_progress";
+
+/// Prefix of the meta keys [`namespace_identifiers`] stages the rewritten entries under, followed
+/// by the index of their column and their new key.
+const STAGING_KEY_PREFIX: &[u8] = b"migration/";
+
+/// Number of entries a migration step rewrites per batch.
+const MIGRATION_CHUNK_SIZE: usize = 10_000;
+
+/// Reads the schema version stored in the database, `None` if there is no version record.
+pub fn schema_version<DB: BonsaiDatabase>(
+    db: &DB,
+) -> Result<Option<u32>, BonsaiStorageError<DB::DatabaseError>> {
+    db.get(&DatabaseKey::Meta(SCHEMA_VERSION_KEY))?
+        .map(|value| u32::decode(&mut value.as_slice()))
+        .transpose()
+        .map_err(Into::into)
+}
+
+pub(crate) fn set_schema_version<DB: BonsaiDatabase>(
+    db: &mut DB,
+    version: u32,
+    batch: Option<&mut DB::Batch>,
+) -> Result<(), BonsaiStorageError<DB::DatabaseError>> {
+    db.insert(
+        &DatabaseKey::Meta(SCHEMA_VERSION_KEY),
+        &version.encode(),
+        batch,
+    )?;
+    Ok(())
+}
+
+/// Whether the database has no entries, a migration in progress counting as one.
+fn is_empty<DB: BonsaiDatabase>(db: &DB) -> Result<bool, BonsaiStorageError<DB::DatabaseError>> {
+    if db.contains(&DatabaseKey::Meta(MIGRATION_PROGRESS_KEY))? {
+        return Ok(false);
+    }
+    for key in [
+        DatabaseKey::Trie(&[]),
+        DatabaseKey::Flat(&[]),
+        DatabaseKey::TrieLog(&[]),
+    ] {
+        if db
+            .iter_by_prefix(&key, None, None)?
+            .next()
+            .transpose()?
+            .is_some()
+        {
+            return Ok(false);
+        }
+    }
+    Ok(true)
+}
+
+/// Returns the schema version of the database, stamping empty databases with the current one.
+fn resolve_schema_version<DB: BonsaiDatabase>(
+    db: &mut DB,
+) -> Result<u32, BonsaiStorageError<DB::DatabaseError>> {
+    if let Some(version) = schema_version(db)? {
+        return Ok(version);
+    }
+    if is_empty(db)? {
+        set_schema_version(db, CURRENT_SCHEMA_VERSION, None)?;
+        Ok(CURRENT_SCHEMA_VERSION)
+    } else {
+        Ok(0)
+    }
+}
+
+/// Checks that the database uses the layout of this crate version, without writing to it.
+///
+/// Returns `true` if the database is empty and has no version record yet: its first commit must
+/// stamp it with [`CURRENT_SCHEMA_VERSION`].
+pub(crate) fn check_schema_version<DB: BonsaiDatabase>(
+    db: &DB,
+) -> Result<bool, BonsaiStorageError<DB::DatabaseError>> {
+    let version = match schema_version(db)? {
+        Some(version) => version,
+        None if is_empty(db)? => return Ok(true),
+        None => 0,
+    };
+    match version {
+        CURRENT_SCHEMA_VERSION => Ok(false),
+        version if version > CURRENT_SCHEMA_VERSION => {
+            Err(BonsaiStorageError::UnsupportedSchemaVersion(version))
+        }
+        version => Err(BonsaiStorageError::MigrationRequired {
+            from: version,
+            to: CURRENT_SCHEMA_VERSION,
+        }),
+    }
+}
+
+/// Upgrades the database to [`CURRENT_SCHEMA_VERSION`].
+///
+/// `identifiers` must list every identifier that was ever used with this database: databases
+/// older than version 1 do not record which part of a key is the identifier.
+///
+/// Every step is applied in batches of bounded size that record its progress, the last one also
+/// recording its resulting version, calling this function again after an interruption picks up
+/// where it stopped. Databases that are already up to date are left untouched.
+pub fn migrate<DB: BonsaiDatabase, ID: Id>(
+    db: &mut DB,
+    identifiers: &[&[u8]],
+) -> Result<(), BonsaiStorageError<DB::DatabaseError>> {
+    migrate_in_chunks::<DB, ID>(db, identifiers, MIGRATION_CHUNK_SIZE)
+}
+
+/// Same as [`migrate`], rewriting at most `chunk_size` entries per batch.
+pub(crate) fn migrate_in_chunks<DB: BonsaiDatabase, ID: Id>(
+    db: &mut DB,
+    identifiers: &[&[u8]],
+    chunk_size: usize,
+) -> Result<(), BonsaiStorageError<DB::DatabaseError>> {
+    loop {
+        let version = resolve_schema_version(db)?;
+        if version > CURRENT_SCHEMA_VERSION {
+            return Err(BonsaiStorageError::UnsupportedSchemaVersion(version));
+        }
+        if version == CURRENT_SCHEMA_VERSION {
+            return Ok(());
+        }
+
+        log::debug!("Migrating database from schema version {version}");
+        match version {
+            0 => namespace_identifiers::<DB, ID>(db, identifiers, chunk_size)?,
+            // Nodes in the legacy encoding are still readable and get rewritten in the compact
+            // one the next time they are committed. The version bump only keeps older crate
+            // versions from opening the database.
+            1 => {}
+            _ => unreachable!("every version below the current one has a migration step"),
+        }
+        let mut batch = db.create_batch();
+        db.remove(&DatabaseKey::Meta(MIGRATION_PROGRESS_KEY), Some(&mut batch))?;
+        set_schema_version(db, version + 1, Some(&mut batch))?;
+        db.write_batch(batch)?;
+    }
+}
+
+#[derive(Clone, Copy)]
+enum Column {
+    Trie,
+    Flat,
+    TrieLog,
+}
+
+impl Column {
+    const ALL: [Column; 3] = [Column::Trie, Column::Flat, Column::TrieLog];
+
+    fn key(self, key: &[u8]) -> DatabaseKey<'_> {
+        match self {
+            Column::Trie => DatabaseKey::Trie(key),
+            Column::Flat => DatabaseKey::Flat(key),
+            Column::TrieLog => DatabaseKey::TrieLog(key),
+        }
+    }
+}
+
+/// Version 0 to 1: rewrites every trie, flat and trie-log key so that the identifier is prefixed
+/// with its length.
+///
+/// Legacy keys are a plain `identifier ++ key` concatenation, so a key is attributed to the
+/// longest listed identifier it starts with whose remainder is a well-formed path. If a key
+/// matches none of them the step fails before its batch is written, and can be resumed with the
+/// missing identifier.
+///
+/// The rewritten keys could not be told apart from the legacy ones in their column, so the step
+/// has two phases. The entries of each column are first moved, `chunk_size` at a time, under
+/// [`STAGING_KEY_PREFIX`] with their new key, then moved back to their column. Every batch
+/// records the phase and the last key it moved in [`MIGRATION_PROGRESS_KEY`], where the next
+/// batch starts.
+fn namespace_identifiers<DB: BonsaiDatabase, ID: Id>(
+    db: &mut DB,
+    identifiers: &[&[u8]],
+    chunk_size: usize,
+) -> Result<(), BonsaiStorageError<DB::DatabaseError>> {
+    let mut identifiers = identifiers.to_vec();
+    identifiers.sort_by_key(|identifier| core::cmp::Reverse(identifier.len()));
+    let id_len = ID::from_u64(0).to_bytes().len();
+
+    // Phases below `Column::ALL.len()` stage the entries of that column, the last one moves them
+    // back.
+    let (mut phase, mut cursor) = match db.get(&DatabaseKey::Meta(MIGRATION_PROGRESS_KEY))? {
+        Some(progress) => <(u8, Vec<u8>)>::decode(&mut progress.as_slice())?,
+        None => (0, Vec::new()),
+    };
+    while usize::from(phase) <= Column::ALL.len() {
+        let column = Column::ALL.get(usize::from(phase)).copied();
+        let prefix = match column {
+            Some(column) => column.key(&[]),
+            None => DatabaseKey::Meta(STAGING_KEY_PREFIX),
+        };
+        let chunk = db
+            .iter_by_prefix(&prefix, Some(&cursor), None)?
+            .take(chunk_size.max(1))
+            .collect::<Result<Vec<_>, _>>()?;
+        let Some((last_key, _)) = chunk.last() else {
+            phase += 1;
+            cursor.clear();
+            continue;
+        };
+        cursor = last_key.to_vec();
+
+        let mut batch = db.create_batch();
+        for (key, value) in &chunk {
+            match column {
+                Some(column) => {
+                    let new_key = match column {
+                        Column::Trie | Column::Flat => namespace_trie_key(key, &identifiers)?,
+                        Column::TrieLog => namespace_trie_log_key(key, id_len, &identifiers)?,
+                    };
+                    let mut staged_key = ByteVec::from(STAGING_KEY_PREFIX);
+                    staged_key.push(phase);
+                    staged_key.extend_from_slice(&new_key);
+                    db.remove(&column.key(key), Some(&mut batch))?;
+                    db.insert(&DatabaseKey::Meta(&staged_key), value, Some(&mut batch))?;
+                }
+                None => {
+                    let staged = &key[STAGING_KEY_PREFIX.len()..];
+                    let Some(column) = staged
+                        .first()
+                        .and_then(|index| Column::ALL.get(usize::from(*index)))
+                    else {
+                        return Err(BonsaiStorageError::InvalidMetaKey(key.to_vec()));
+                    };
+                    db.remove(&DatabaseKey::Meta(key), Some(&mut batch))?;
+                    db.insert(&column.key(&staged[1..]), value, Some(&mut batch))?;
+                }
+            }
+        }
+        db.insert(
+            &DatabaseKey::Meta(MIGRATION_PROGRESS_KEY),
+            &(phase, &cursor).encode(),
+            Some(&mut batch),
+        )?;
+        db.write_batch(batch)?;
+    }
+    Ok(())
+}
+
+/// Converts a legacy trie-log key, `id ++ separator ++ trie key ++ key type ++ change type`, to
+/// the namespaced layout.
+fn namespace_trie_log_key<E: crate::DBError>(
+    key: &[u8],
+    id_len: usize,
+    identifiers: &[&[u8]],
+) -> Result<ByteVec, BonsaiStorageError<E>> {
+    if key.len() < id_len + 3 {
+        return Err(BonsaiStorageError::Migration(format!(
+            "Invalid trie log key {key:?}"
+        )));
+    }
+    let (head, trie_key) = key.split_at(id_len + 1);
+    let (trie_key, tail) = trie_key.split_at(trie_key.len() - 2);
+    let mut new_key = ByteVec::from(head);
+    new_key.extend_from_slice(&namespace_trie_key(trie_key, identifiers)?);
+    new_key.extend_from_slice(tail);
+    Ok(new_key)
+}
+
+/// Converts a legacy `identifier ++ path` key to the namespaced layout. `identifiers` must be
+/// sorted from the longest to the shortest.
+fn namespace_trie_key<E: crate::DBError>(
+    key: &[u8],
+    identifiers: &[&[u8]],
+) -> Result<ByteVec, BonsaiStorageError<E>> {
+    identifiers
+        .iter()
+        .find_map(|identifier| {
+            let rest = key.strip_prefix(*identifier)?;
+            let bits = *rest.first()? as usize;
+            if rest.len() != 1 + bits.div_ceil(8) {
+                return None;
+            }
+            let mut new_key = identifier_prefix(identifier);
+            new_key.extend_from_slice(rest);
+            Some(new_key)
+        })
+        .ok_or_else(|| {
+            BonsaiStorageError::Migration(format!(
+                "Key {key:?} does not belong to any of the given identifiers"
+            ))
+        })
+}
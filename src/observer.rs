@@ -0,0 +1,35 @@
+use starknet_types_core::felt::Felt;
+
+use crate::{id::Id, BTreeMap, BitVec, Change, HashMap, Vec};
+
+/// What wrote the state a [`CommitEvent`] describes.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CommitKind {
+    /// [`crate::BonsaiStorage::commit`], [`crate::BonsaiStorage::transactional_commit`] or a
+    /// commit replayed by [`crate::BonsaiStorage::apply_trie_log`].
+    Commit,
+    /// [`crate::BonsaiStorage::revert_to`].
+    Revert,
+}
+
+/// A successful write of the storage, as seen by a [`CommitObserver`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CommitEvent<ID: Id> {
+    pub kind: CommitKind,
+    /// Id of the commit, or of the commit reverted to.
+    pub id: ID,
+    /// Changed leaves by trie identifier. For a revert, the old values are the ones before the
+    /// revert.
+    pub changes: BTreeMap<Vec<u8>, HashMap<BitVec, Change>>,
+    /// Root hashes of the changed tries after the write, sorted by identifier.
+    pub roots: Vec<(Vec<u8>, Felt)>,
+}
+
+/// Callback registered with [`crate::BonsaiStorage::add_commit_observer`], called after every
+/// successful write of the storage.
+///
+/// Unlike polling [`crate::BonsaiStorage::get_changes`], observers see every commit even when its
+/// trie log is pruned right away.
+pub trait CommitObserver<ID: Id>: Send + Sync {
+    fn on_commit(&mut self, event: &CommitEvent<ID>);
+}
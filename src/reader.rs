@@ -0,0 +1,78 @@
+use starknet_types_core::felt::Felt;
+
+use crate::{
+    id::Id, trie::trees::MerkleTrees, BitSlice, BonsaiDatabase, BonsaiHasher, BonsaiStorageError,
+    BonsaiTrieHash, MultiProof, Vec,
+};
+
+/// Read-only handle on the last committed state of a [`crate::BonsaiStorage`], see
+/// [`crate::BonsaiStorage::reader`].
+///
+/// It can be shared between threads and keeps reading the same state while the storage commits.
+pub struct BonsaiReader<ChangeID: Id, DB: BonsaiDatabase, H: BonsaiHasher + Send + Sync> {
+    pub(crate) tries: MerkleTrees<H, DB, ChangeID>,
+}
+
+impl<ChangeID, DB, H> BonsaiReader<ChangeID, DB, H>
+where
+    DB: BonsaiDatabase,
+    ChangeID: Id,
+    H: BonsaiHasher + Send + Sync,
+{
+    /// Get a value in a trie.
+    pub fn get(
+        &self,
+        identifier: &[u8],
+        key: &BitSlice,
+    ) -> Result<Option<Felt>, BonsaiStorageError<DB::DatabaseError>> {
+        self.tries.get(identifier, key)
+    }
+
+    /// Checks if the key exists in the trie.
+    pub fn contains(
+        &self,
+        identifier: &[u8],
+        key: &BitSlice,
+    ) -> Result<bool, BonsaiStorageError<DB::DatabaseError>> {
+        self.tries.contains(identifier, key)
+    }
+
+    /// Get trie root hash.
+    pub fn root_hash(
+        &self,
+        identifier: &[u8],
+    ) -> Result<BonsaiTrieHash, BonsaiStorageError<DB::DatabaseError>> {
+        self.tries.root_hash(identifier)
+    }
+
+    /// Get all the keys in a specific trie.
+    pub fn get_keys(
+        &self,
+        identifier: &[u8],
+    ) -> Result<Vec<Vec<u8>>, BonsaiStorageError<DB::DatabaseError>> {
+        self.tries.get_keys(identifier)
+    }
+
+    /// Get all the key-value pairs in a specific trie.
+    #[allow(clippy::type_complexity)]
+    pub fn get_key_value_pairs(
+        &self,
+        identifier: &[u8],
+    ) -> Result<Vec<(Vec<u8>, Vec<u8>)>, BonsaiStorageError<DB::DatabaseError>> {
+        self.tries.get_key_value_pairs(identifier)
+    }
+
+    /// Get the id of the commit this reader is pinned to, `None` if there was none.
+    pub fn get_latest_id(&self) -> Result<Option<ChangeID>, BonsaiStorageError<DB::DatabaseError>> {
+        self.tries.db_ref().get_latest_id()
+    }
+
+    /// Get a proof of the values of `keys` in a trie.
+    pub fn get_multi_proof(
+        &self,
+        identifier: &[u8],
+        keys: impl IntoIterator<Item = impl AsRef<BitSlice>>,
+    ) -> Result<MultiProof, BonsaiStorageError<DB::DatabaseError>> {
+        self.tries.get_multi_proof(identifier, keys)
+    }
+}
@@ -0,0 +1,96 @@
+#![cfg(feature = "std")]
+use super::common::key;
+use crate::{
+    databases::{BTreeMapDb, HashMapDb},
+    id::{BasicId, BasicIdBuilder},
+    BonsaiDatabase, BonsaiStorage, BonsaiStorageConfig, DatabaseKey,
+};
+use starknet_types_core::{felt::Felt, hash::Pedersen};
+
+#[test]
+fn batch_and_prefix_scans() {
+    let mut db = BTreeMapDb::<BasicId>::default();
+    db.insert(&DatabaseKey::Flat(&[1, 3]), &[3], None).unwrap();
+    db.insert(&DatabaseKey::Flat(&[2]), &[4], None).unwrap();
+    db.insert(&DatabaseKey::Flat(&[1, 1]), &[1], None).unwrap();
+    db.insert(&DatabaseKey::Trie(&[1, 2]), &[9], None).unwrap();
+
+    let mut batch = db.create_batch();
+    db.insert(&DatabaseKey::Flat(&[1, 2]), &[2], Some(&mut batch))
+        .unwrap();
+    assert_eq!(
+        db.remove(&DatabaseKey::Flat(&[1, 3]), Some(&mut batch))
+            .unwrap(),
+        Some([3].as_slice().into())
+    );
+    // Nothing is visible before the batch is written.
+    assert!(!db.contains(&DatabaseKey::Flat(&[1, 2])).unwrap());
+    assert!(db.contains(&DatabaseKey::Flat(&[1, 3])).unwrap());
+    db.write_batch(batch).unwrap();
+
+    let entries: Vec<(Vec<u8>, Vec<u8>)> = db
+        .get_by_prefix(&DatabaseKey::Flat(&[1]))
+        .unwrap()
+        .into_iter()
+        .map(|(k, v)| (k.to_vec(), v.to_vec()))
+        .collect();
+    assert_eq!(entries, vec![(vec![1, 1], vec![1]), (vec![1, 2], vec![2])]);
+
+    db.remove_by_prefix(&DatabaseKey::Flat(&[1])).unwrap();
+    assert!(db
+        .get_by_prefix(&DatabaseKey::Flat(&[1]))
+        .unwrap()
+        .is_empty());
+    assert!(db.contains(&DatabaseKey::Flat(&[2])).unwrap());
+    assert!(db.contains(&DatabaseKey::Trie(&[1, 2])).unwrap());
+}
+
+#[test]
+fn same_state_as_hashmap_db() {
+    let identifier = vec![];
+    let config = BonsaiStorageConfig::default();
+    let mut btree_storage: BonsaiStorage<_, _, Pedersen> =
+        BonsaiStorage::new(BTreeMapDb::<BasicId>::default(), config.clone(), 24).unwrap();
+    let mut hashmap_storage: BonsaiStorage<_, _, Pedersen> =
+        BonsaiStorage::new(HashMapDb::<BasicId>::default(), config, 24).unwrap();
+    let mut id_builder = BasicIdBuilder::new();
+
+    let mut ids = Vec::new();
+    for round in 0..8u64 {
+        for n in 0..50u64 {
+            let k = key((n * 7919 + round * 31) % 997);
+            let value = Felt::from(n + round * 1000 + 1);
+            btree_storage.insert(&identifier, &k, &value).unwrap();
+            hashmap_storage.insert(&identifier, &k, &value).unwrap();
+        }
+        for n in 0..5u64 {
+            let k = key((n * 7919 + round * 17) % 997);
+            btree_storage.remove(&identifier, &k).unwrap();
+            hashmap_storage.remove(&identifier, &k).unwrap();
+        }
+        let id = id_builder.new_id();
+        btree_storage.commit(id).unwrap();
+        hashmap_storage.commit(id).unwrap();
+        ids.push(id);
+        assert_eq!(
+            btree_storage.root_hash(&identifier).unwrap(),
+            hashmap_storage.root_hash(&identifier).unwrap()
+        );
+    }
+
+    // Key/value pairs come out in key order.
+    let pairs = btree_storage.get_key_value_pairs(&identifier).unwrap();
+    let mut sorted = pairs.clone();
+    sorted.sort();
+    assert_eq!(pairs, sorted);
+    let mut hashmap_pairs = hashmap_storage.get_key_value_pairs(&identifier).unwrap();
+    hashmap_pairs.sort();
+    assert_eq!(pairs, hashmap_pairs);
+
+    btree_storage.revert_to(ids[3], ids[7]).unwrap();
+    hashmap_storage.revert_to(ids[3], ids[7]).unwrap();
+    assert_eq!(
+        btree_storage.root_hash(&identifier).unwrap(),
+        hashmap_storage.root_hash(&identifier).unwrap()
+    );
+}
@@ -0,0 +1,105 @@
+#![cfg(feature = "std")]
+use super::common::{key, storage};
+use crate::{
+    databases::HashMapDb,
+    id::{BasicId, BasicIdBuilder},
+    BonsaiStorage, BonsaiStorageConfig, COMMIT_INFO_VERSION,
+};
+use starknet_types_core::{felt::Felt, hash::Pedersen};
+
+#[test]
+fn commits_record_roots_and_metadata() {
+    let mut bonsai_storage = storage();
+    let mut id_builder = BasicIdBuilder::new();
+    for n in 0..3 {
+        bonsai_storage
+            .insert(&[2], &key(n), &Felt::from(n + 1))
+            .unwrap();
+    }
+    bonsai_storage
+        .insert(&[1], &key(0), &Felt::from(10u64))
+        .unwrap();
+    let id1 = id_builder.new_id();
+    bonsai_storage
+        .commit_with_metadata(id1, b"block 1")
+        .unwrap();
+
+    let info = bonsai_storage.commit_info(id1).unwrap().unwrap();
+    assert_eq!(info.version, COMMIT_INFO_VERSION);
+    assert_eq!(info.id, id1);
+    assert_eq!(
+        info.roots,
+        vec![
+            (vec![1], bonsai_storage.root_hash(&[1]).unwrap()),
+            (vec![2], bonsai_storage.root_hash(&[2]).unwrap()),
+        ]
+    );
+    assert_eq!(info.change_count, 4);
+    assert_eq!(info.metadata.as_deref(), Some(&b"block 1"[..]));
+
+    // Only the tries changed by a commit are recorded.
+    bonsai_storage.remove(&[2], &key(0)).unwrap();
+    let id2 = id_builder.new_id();
+    bonsai_storage.commit(id2).unwrap();
+    let info = bonsai_storage.commit_info(id2).unwrap().unwrap();
+    assert_eq!(
+        info.roots,
+        vec![(vec![2], bonsai_storage.root_hash(&[2]).unwrap())]
+    );
+    assert_eq!(info.change_count, 1);
+    assert_eq!(info.metadata, None);
+
+    assert_eq!(
+        bonsai_storage.commit_info(id_builder.new_id()).unwrap(),
+        None
+    );
+
+    // Reverted commits lose their record.
+    bonsai_storage.revert_to(id1, id2).unwrap();
+    assert_eq!(bonsai_storage.commit_info(id2).unwrap(), None);
+    assert!(bonsai_storage.commit_info(id1).unwrap().is_some());
+}
+
+#[test]
+fn records_are_pruned_with_trie_logs() {
+    let config = BonsaiStorageConfig {
+        max_saved_trie_logs: Some(2),
+        ..Default::default()
+    };
+    let mut bonsai_storage: BonsaiStorage<BasicId, _, Pedersen> =
+        BonsaiStorage::new(HashMapDb::<BasicId>::default(), config, 24).unwrap();
+    let mut id_builder = BasicIdBuilder::new();
+    let mut ids = Vec::new();
+    for n in 0..5 {
+        bonsai_storage
+            .insert(&[], &key(n), &Felt::from(n + 1))
+            .unwrap();
+        let id = id_builder.new_id();
+        bonsai_storage.commit(id).unwrap();
+        ids.push(id);
+    }
+
+    for (n, id) in ids.into_iter().enumerate() {
+        let info = bonsai_storage.commit_info(id).unwrap();
+        assert_eq!(info.is_some(), n >= 3, "commit {n}");
+        assert_eq!(
+            info.is_some(),
+            !bonsai_storage.get_changes(id).unwrap().is_empty()
+        );
+    }
+}
+
+#[test]
+fn trie_log_ids_are_not_contiguous() {
+    let mut bonsai_storage = storage();
+    // 300 sorts after 10 and 20 only if the ids are compared as numbers.
+    let ids = [10, 20, 300].map(BasicId::new);
+    for (n, id) in ids.into_iter().enumerate() {
+        bonsai_storage
+            .insert(&[], &key(n as u64), &Felt::from(n as u64 + 1))
+            .unwrap();
+        bonsai_storage.commit(id).unwrap();
+    }
+
+    assert_eq!(bonsai_storage.trie_log_ids().unwrap(), ids);
+}
@@ -0,0 +1,17 @@
+//! Helpers shared by the tests.
+#![cfg(feature = "std")]
+use crate::{databases::HashMapDb, id::BasicId, BitVec, BonsaiStorage, BonsaiStorageConfig};
+use bitvec::view::BitView;
+use starknet_types_core::hash::Pedersen;
+
+pub(super) type Storage = BonsaiStorage<BasicId, HashMapDb<BasicId>, Pedersen>;
+
+/// Key of the leaf `n` in a trie of height 24.
+pub(super) fn key(n: u64) -> BitVec {
+    n.to_be_bytes().view_bits()[40..].to_bitvec()
+}
+
+/// A new storage with the default config, for tries of height 24.
+pub(super) fn storage() -> Storage {
+    Storage::new(HashMapDb::default(), BonsaiStorageConfig::default(), 24).unwrap()
+}
@@ -0,0 +1,79 @@
+#![cfg(feature = "std")]
+use super::common::{key, storage};
+use crate::{
+    id::{BasicIdBuilder, Id},
+    trie::{
+        tree::bitslice_to_bytes,
+        trie_db::{TrieKey, TrieKeyType},
+    },
+    BonsaiDatabase, BonsaiStorageError, DatabaseKey,
+};
+use starknet_types_core::felt::Felt;
+
+#[test]
+fn corrupt_leaf_value_is_reported() {
+    let mut bonsai_storage = storage();
+    let mut id_builder = BasicIdBuilder::new();
+    bonsai_storage
+        .insert(&[1], &key(1), &Felt::from(1u64))
+        .unwrap();
+    bonsai_storage.commit(id_builder.new_id()).unwrap();
+
+    let flat_key = TrieKey::new(&[1], TrieKeyType::Flat, &bitslice_to_bytes(&key(1)));
+    bonsai_storage
+        .tries
+        .db
+        .db
+        .insert(&DatabaseKey::Flat(flat_key.as_slice()), &[0xff; 3], None)
+        .unwrap();
+
+    let err = bonsai_storage.get(&[1], &key(1)).unwrap_err();
+    assert!(
+        matches!(&err, BonsaiStorageError::ValueDecode { key, .. } if key.as_slice() == flat_key.as_slice()),
+        "{err:?}"
+    );
+    let err = bonsai_storage
+        .insert(&[1], &key(1), &Felt::from(2u64))
+        .unwrap_err();
+    assert!(
+        matches!(err, BonsaiStorageError::ValueDecode { .. }),
+        "{err:?}"
+    );
+}
+
+#[test]
+fn corrupt_trie_log_key_is_reported() {
+    let mut bonsai_storage = storage();
+    let mut id_builder = BasicIdBuilder::new();
+    bonsai_storage
+        .insert(&[1], &key(1), &Felt::from(1u64))
+        .unwrap();
+    let id0 = id_builder.new_id();
+    bonsai_storage.commit(id0).unwrap();
+    bonsai_storage
+        .insert(&[1], &key(2), &Felt::from(2u64))
+        .unwrap();
+    let id = id_builder.new_id();
+    bonsai_storage.commit(id).unwrap();
+
+    // Id, separator, key, then an unknown key type and a valid change type.
+    let mut log_key = id.to_bytes().to_vec();
+    log_key.extend_from_slice(&[0x00, 0x01, 0x02, 0x07, 0x00]);
+    bonsai_storage
+        .tries
+        .db
+        .db
+        .insert(&DatabaseKey::TrieLog(&log_key), &[0x00], None)
+        .unwrap();
+
+    let err = bonsai_storage.get_changes(id).unwrap_err();
+    assert!(
+        matches!(&err, BonsaiStorageError::InvalidTrieLogKey(key) if *key == log_key),
+        "{err:?}"
+    );
+    let err = bonsai_storage.revert_to(id0, id).unwrap_err();
+    assert!(
+        matches!(err, BonsaiStorageError::InvalidTrieLogKey(_)),
+        "{err:?}"
+    );
+}
@@ -0,0 +1,178 @@
+#![cfg(feature = "std")]
+use super::common::key;
+use crate::{
+    databases::{FileDb, FileDbError, HashMapDb},
+    id::{BasicId, BasicIdBuilder},
+    BonsaiDatabase, BonsaiStorage, BonsaiStorageConfig, DatabaseKey,
+};
+use starknet_types_core::{felt::Felt, hash::Pedersen};
+use std::{fs, io::Write};
+
+fn append(path: &std::path::Path, bytes: &[u8]) {
+    let mut file = fs::OpenOptions::new().append(true).open(path).unwrap();
+    file.write_all(bytes).unwrap();
+}
+
+#[test]
+fn reopen_keeps_committed_writes() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("db");
+    {
+        let mut db = FileDb::<BasicId>::open(&path).unwrap();
+        db.insert(&DatabaseKey::Flat(&[1]), &[1], None).unwrap();
+        db.insert(&DatabaseKey::Trie(&[2]), &[2], None).unwrap();
+        let mut batch = db.create_batch();
+        db.insert(&DatabaseKey::Flat(&[3]), &[3], Some(&mut batch))
+            .unwrap();
+        db.remove(&DatabaseKey::Flat(&[1]), Some(&mut batch))
+            .unwrap();
+        db.write_batch(batch).unwrap();
+        // Never written.
+        let mut batch = db.create_batch();
+        db.insert(&DatabaseKey::Flat(&[4]), &[4], Some(&mut batch))
+            .unwrap();
+    }
+
+    let db = FileDb::<BasicId>::open(&path).unwrap();
+    assert!(!db.contains(&DatabaseKey::Flat(&[1])).unwrap());
+    assert_eq!(
+        db.get(&DatabaseKey::Trie(&[2])).unwrap(),
+        Some([2].as_slice().into())
+    );
+    assert_eq!(
+        db.get(&DatabaseKey::Flat(&[3])).unwrap(),
+        Some([3].as_slice().into())
+    );
+    assert!(!db.contains(&DatabaseKey::Flat(&[4])).unwrap());
+}
+
+#[test]
+fn torn_tail_is_truncated() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("db");
+    {
+        let mut db = FileDb::<BasicId>::open(&path).unwrap();
+        db.insert(&DatabaseKey::Flat(&[1]), &[1], None).unwrap();
+    }
+    let len = fs::metadata(&path).unwrap().len();
+
+    // A record header announcing more bytes than were written.
+    append(&path, &[100, 0, 0, 0, 1, 2]);
+    {
+        let mut db = FileDb::<BasicId>::open(&path).unwrap();
+        assert_eq!(fs::metadata(&path).unwrap().len(), len);
+        assert!(db.contains(&DatabaseKey::Flat(&[1])).unwrap());
+        db.insert(&DatabaseKey::Flat(&[2]), &[2], None).unwrap();
+    }
+
+    // A complete but corrupted record.
+    append(&path, &[1, 0, 0, 0, 0, 0, 0, 0, 2]);
+    let db = FileDb::<BasicId>::open(&path).unwrap();
+    assert!(db.contains(&DatabaseKey::Flat(&[1])).unwrap());
+    assert!(db.contains(&DatabaseKey::Flat(&[2])).unwrap());
+}
+
+#[test]
+fn uncommitted_batch_is_dropped() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("db");
+    {
+        let mut db = FileDb::<BasicId>::open(&path).unwrap();
+        db.insert(&DatabaseKey::Flat(&[1]), &[1], None).unwrap();
+    }
+    let committed = fs::read(&path).unwrap();
+    {
+        let mut db = FileDb::<BasicId>::open(&path).unwrap();
+        let mut batch = db.create_batch();
+        db.insert(&DatabaseKey::Flat(&[2]), &[2], Some(&mut batch))
+            .unwrap();
+        db.insert(&DatabaseKey::Flat(&[3]), &[3], Some(&mut batch))
+            .unwrap();
+        db.write_batch(batch).unwrap();
+    }
+    // Cut the second batch before its commit record.
+    let full = fs::read(&path).unwrap();
+    fs::write(&path, &full[..full.len() - 9]).unwrap();
+
+    let db = FileDb::<BasicId>::open(&path).unwrap();
+    assert!(db.contains(&DatabaseKey::Flat(&[1])).unwrap());
+    assert!(!db.contains(&DatabaseKey::Flat(&[2])).unwrap());
+    assert!(!db.contains(&DatabaseKey::Flat(&[3])).unwrap());
+    assert_eq!(fs::read(&path).unwrap(), committed);
+}
+
+#[test]
+fn invalid_header_is_rejected() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("db");
+    fs::write(&path, b"not a bonsai database").unwrap();
+    assert!(matches!(
+        FileDb::<BasicId>::open(&path),
+        Err(FileDbError::InvalidHeader)
+    ));
+}
+
+#[test]
+fn compaction_keeps_state() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("db");
+    let entries = {
+        let mut db = FileDb::<BasicId>::open(&path).unwrap();
+        for n in 0..100u8 {
+            db.insert(&DatabaseKey::Flat(&[n % 10]), &[n], None)
+                .unwrap();
+        }
+        db.remove(&DatabaseKey::Flat(&[0]), None).unwrap();
+        db.get_by_prefix(&DatabaseKey::Flat(&[])).unwrap()
+    };
+    let len = fs::metadata(&path).unwrap().len();
+
+    FileDb::<BasicId>::compact(&path).unwrap();
+    assert!(fs::metadata(&path).unwrap().len() < len);
+    let db = FileDb::<BasicId>::open(&path).unwrap();
+    assert_eq!(db.get_by_prefix(&DatabaseKey::Flat(&[])).unwrap(), entries);
+}
+
+#[test]
+fn same_state_as_hashmap_db() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("db");
+    let identifier = vec![];
+    let config = BonsaiStorageConfig::default();
+    let mut hashmap_storage: BonsaiStorage<_, _, Pedersen> =
+        BonsaiStorage::new(HashMapDb::<BasicId>::default(), config.clone(), 24).unwrap();
+    let mut id_builder = BasicIdBuilder::new();
+
+    let mut ids = Vec::new();
+    for round in 0..4u64 {
+        // Reopen the file between commits.
+        let mut file_storage: BonsaiStorage<_, _, Pedersen> =
+            BonsaiStorage::new(FileDb::<BasicId>::open(&path).unwrap(), config.clone(), 24)
+                .unwrap();
+        for n in 0..50u64 {
+            let k = key((n * 7919 + round * 31) % 997);
+            let value = Felt::from(n + round * 1000 + 1);
+            file_storage.insert(&identifier, &k, &value).unwrap();
+            hashmap_storage.insert(&identifier, &k, &value).unwrap();
+        }
+        let id = id_builder.new_id();
+        file_storage.commit(id).unwrap();
+        hashmap_storage.commit(id).unwrap();
+        ids.push(id);
+        assert_eq!(
+            file_storage.root_hash(&identifier).unwrap(),
+            hashmap_storage.root_hash(&identifier).unwrap()
+        );
+    }
+
+    let file_storage: BonsaiStorage<_, _, Pedersen> =
+        BonsaiStorage::new(FileDb::<BasicId>::open(&path).unwrap(), config, 24).unwrap();
+    assert_eq!(
+        file_storage.root_hash(&identifier).unwrap(),
+        hashmap_storage.root_hash(&identifier).unwrap()
+    );
+    assert_eq!(
+        file_storage.get_changes(ids[3]).unwrap(),
+        hashmap_storage.get_changes(ids[3]).unwrap()
+    );
+}
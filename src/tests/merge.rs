@@ -4,7 +4,8 @@
 use crate::{
     databases::{create_rocks_db, RocksDB, RocksDBConfig, RocksDBTransaction},
     id::{BasicId, BasicIdBuilder},
-    BitVec, BonsaiStorage, BonsaiStorageConfig,
+    BitVec, BonsaiStorage, BonsaiStorageConfig, BonsaiStorageError, DBError, MergeError,
+    MergePolicy,
 };
 use once_cell::sync::Lazy;
 use rocksdb::OptimisticTransactionDB;
@@ -61,7 +62,7 @@ fn init_test<'a>(
 
     let config = BonsaiStorageConfig::default();
     let mut bonsai_storage =
-        BonsaiStorage::new(RocksDB::new(db, RocksDBConfig::default()), config, 24);
+        BonsaiStorage::new(RocksDB::new(db, RocksDBConfig::default()), config, 24).unwrap();
 
     let mut id_builder = BasicIdBuilder::new();
 
@@ -88,6 +89,32 @@ fn init_test<'a>(
     )
 }
 
+/// Asserts that the merge failed because the storage committed since the transactional state
+/// was created at `start_id`.
+fn assert_outdated<E: DBError>(err: BonsaiStorageError<E>, latest_id: u64) {
+    assert!(
+        matches!(
+            err,
+            BonsaiStorageError::Merge(MergeError::Outdated {
+                created_at: 0,
+                latest_id: id
+            }) if id == latest_id
+        ),
+        "{err:?}"
+    );
+}
+
+/// Asserts that the merge failed on a single key changed on both sides.
+fn assert_conflict_on<E: DBError>(err: BonsaiStorageError<E>, key: &BitVec) {
+    match err {
+        BonsaiStorageError::Merge(MergeError::Conflicts(conflicts)) => {
+            assert_eq!(conflicts.len(), 1);
+            assert_eq!(&conflicts[0].key, key);
+        }
+        err => panic!("expected a merge conflict, got {err:?}"),
+    }
+}
+
 #[test]
 fn merge_before_simple() {
     let db = create_rocks_db(tempfile::tempdir().unwrap().path()).unwrap();
@@ -96,7 +123,9 @@ fn merge_before_simple() {
     bonsai_at_txn
         .insert(&identifier, &PAIR2.0, &PAIR2.1)
         .unwrap();
-    bonsai_storage.merge(bonsai_at_txn).unwrap();
+    bonsai_storage
+        .merge(bonsai_at_txn, MergePolicy::default())
+        .unwrap();
     bonsai_storage.commit(id_builder.new_id()).unwrap();
 
     assert_eq!(
@@ -112,12 +141,15 @@ fn merge_before_simple_remove() {
         init_test(&db);
 
     bonsai_at_txn.remove(&identifier, &PAIR1.0).unwrap();
-    bonsai_storage.merge(bonsai_at_txn).unwrap();
-    bonsai_storage.commit(id_builder.new_id()).unwrap();
+    bonsai_storage
+        .merge(bonsai_at_txn, MergePolicy::default())
+        .unwrap();
+    let id = id_builder.new_id();
+    bonsai_storage.commit(id).unwrap();
 
     assert!(!bonsai_storage.contains(&identifier, &PAIR1.0).unwrap());
 
-    bonsai_storage.revert_to(start_id).unwrap();
+    bonsai_storage.revert_to(start_id, id).unwrap();
 
     assert_eq!(
         bonsai_storage.get(&identifier, &PAIR1.0).unwrap(),
@@ -132,15 +164,16 @@ fn merge_tx_commit_simple_remove() {
         init_test(&db);
 
     bonsai_at_txn.remove(&identifier, &PAIR1.0).unwrap();
-    bonsai_at_txn
-        .transactional_commit(id_builder.new_id())
-        .unwrap();
+    let id = id_builder.new_id();
+    bonsai_at_txn.transactional_commit(id).unwrap();
 
-    bonsai_storage.merge(bonsai_at_txn).unwrap();
+    bonsai_storage
+        .merge(bonsai_at_txn, MergePolicy::default())
+        .unwrap();
 
     assert!(!bonsai_storage.contains(&identifier, &PAIR1.0).unwrap());
 
-    bonsai_storage.revert_to(start_id).unwrap();
+    bonsai_storage.revert_to(start_id, id).unwrap();
 
     assert_eq!(
         bonsai_storage.get(&identifier, &PAIR1.0).unwrap(),
@@ -157,9 +190,12 @@ fn merge_before_simple_revert_to() {
     bonsai_at_txn
         .insert(&identifier, &PAIR2.0, &PAIR2.1)
         .unwrap();
-    bonsai_storage.merge(bonsai_at_txn).unwrap();
-    bonsai_storage.commit(id_builder.new_id()).unwrap();
-    bonsai_storage.revert_to(start_id).unwrap();
+    bonsai_storage
+        .merge(bonsai_at_txn, MergePolicy::default())
+        .unwrap();
+    let id = id_builder.new_id();
+    bonsai_storage.commit(id).unwrap();
+    bonsai_storage.revert_to(start_id, id).unwrap();
 
     assert!(bonsai_storage.get(&identifier, &PAIR2.0).unwrap().is_none());
 }
@@ -180,7 +216,9 @@ fn merge_transactional_commit_in_txn_before() {
     bonsai_at_txn
         .insert(&identifier, &PAIR3.0, &PAIR3.1)
         .unwrap();
-    bonsai_storage.merge(bonsai_at_txn).unwrap();
+    bonsai_storage
+        .merge(bonsai_at_txn, MergePolicy::default())
+        .unwrap();
     bonsai_storage.commit(id3).unwrap();
 
     assert_eq!(
@@ -191,13 +229,13 @@ fn merge_transactional_commit_in_txn_before() {
         bonsai_storage.get(&identifier, &PAIR3.0).unwrap(),
         Some(PAIR3.1)
     );
-    bonsai_storage.revert_to(id2).unwrap();
+    bonsai_storage.revert_to(id2, id3).unwrap();
     assert_eq!(
         bonsai_storage.get(&identifier, &PAIR2.0).unwrap(),
         Some(PAIR2.1)
     );
     assert!(bonsai_storage.get(&identifier, &PAIR3.0).unwrap().is_none());
-    bonsai_storage.revert_to(start_id).unwrap();
+    bonsai_storage.revert_to(start_id, id2).unwrap();
     assert!(bonsai_storage.get(&identifier, &PAIR2.0).unwrap().is_none());
     assert!(bonsai_storage.get(&identifier, &PAIR3.0).unwrap().is_none());
 }
@@ -212,12 +250,14 @@ fn merge_transactional_commit_in_txn_before_existing_key() {
 
     let id2 = id_builder.new_id();
     bonsai_at_txn.transactional_commit(id2).unwrap();
-    bonsai_storage.merge(bonsai_at_txn).unwrap();
-    bonsai_storage.revert_to(id2).unwrap();
+    bonsai_storage
+        .merge(bonsai_at_txn, MergePolicy::default())
+        .unwrap();
+    bonsai_storage.revert_to(id2, id2).unwrap();
 
     assert!(bonsai_storage.get(&identifier, &PAIR1.0).unwrap().is_none());
 
-    bonsai_storage.revert_to(start_id).unwrap();
+    bonsai_storage.revert_to(start_id, id2).unwrap();
 
     assert_eq!(
         bonsai_storage.get(&identifier, &PAIR1.0.clone()).unwrap(),
@@ -233,7 +273,9 @@ fn merge_get_uncommitted() {
     bonsai_at_txn
         .insert(&identifier, &PAIR2.0, &PAIR2.1)
         .unwrap();
-    bonsai_storage.merge(bonsai_at_txn).unwrap();
+    bonsai_storage
+        .merge(bonsai_at_txn, MergePolicy::default())
+        .unwrap();
 
     assert_eq!(
         bonsai_storage.get(&identifier, &PAIR2.0).unwrap(),
@@ -259,12 +301,9 @@ fn merge_conflict_commited_vs_commited() {
         .unwrap();
     bonsai_storage.commit(id_builder.new_id()).unwrap();
 
-    match bonsai_storage.merge(bonsai_at_txn) {
+    match bonsai_storage.merge(bonsai_at_txn, MergePolicy::default()) {
         Ok(_) => panic!("Expected merge conflict error"),
-        Err(err) => assert_eq!(
-            err.to_string(),
-            "Merge error: Transaction created_at BasicId(0) is lower than the last recorded id"
-        ),
+        Err(err) => assert_outdated(err, 2),
     }
 }
 
@@ -287,12 +326,9 @@ fn merge_conflict_commited_vs_commited_change_order() {
         .transactional_commit(id_builder.new_id())
         .unwrap();
 
-    match bonsai_storage.merge(bonsai_at_txn) {
+    match bonsai_storage.merge(bonsai_at_txn, MergePolicy::default()) {
         Ok(_) => panic!("Expected merge conflict error"),
-        Err(err) => assert_eq!(
-            err.to_string(),
-            "Merge error: Transaction created_at BasicId(0) is lower than the last recorded id"
-        ),
+        Err(err) => assert_outdated(err, 1),
     }
 }
 
@@ -317,12 +353,9 @@ fn merge_conflict_commited_vs_commited_and_noncommited() {
         .unwrap();
     bonsai_storage.commit(id_builder.new_id()).unwrap();
 
-    match bonsai_storage.merge(bonsai_at_txn) {
+    match bonsai_storage.merge(bonsai_at_txn, MergePolicy::default()) {
         Ok(_) => panic!("Expected merge conflict error"),
-        Err(err) => assert_eq!(
-            err.to_string(),
-            "Merge error: Transaction created_at BasicId(0) is lower than the last recorded id"
-        ),
+        Err(err) => assert_outdated(err, 2),
     }
 }
 
@@ -347,12 +380,9 @@ fn merge_conflict_commited_and_noncommited_vs_commited() {
     // .insert(&identifier, &PAIR3.0, &PAIR3.1)
     // .unwrap();
 
-    match bonsai_storage.merge(bonsai_at_txn) {
+    match bonsai_storage.merge(bonsai_at_txn, MergePolicy::default()) {
         Ok(_) => panic!("Expected merge conflict error"),
-        Err(err) => assert_eq!(
-            err.to_string(),
-            "Merge error: Transaction created_at BasicId(0) is lower than the last recorded id"
-        ),
+        Err(err) => assert_outdated(err, 2),
     }
 }
 
@@ -375,12 +405,9 @@ fn merge_conflict_commited_and_noncommited_vs_noncommited() {
     // .insert(&identifier, &PAIR3.0, &PAIR3.1)
     // .unwrap();
 
-    match bonsai_storage.merge(bonsai_at_txn) {
+    match bonsai_storage.merge(bonsai_at_txn, MergePolicy::default()) {
         Ok(_) => panic!("Expected merge conflict error"),
-        Err(err) => assert_eq!(
-            err.to_string(),
-            "Merge error: Transaction created_at BasicId(0) is lower than the last recorded id"
-        ),
+        Err(err) => assert_outdated(err, 1),
     }
 }
 
@@ -400,12 +427,9 @@ fn merge_conflict_commited_vs_noncommited() {
         .unwrap();
     bonsai_storage.commit(id_builder.new_id()).unwrap();
 
-    match bonsai_storage.merge(bonsai_at_txn) {
+    match bonsai_storage.merge(bonsai_at_txn, MergePolicy::default()) {
         Ok(_) => panic!("Expected merge conflict error"),
-        Err(err) => assert_eq!(
-            err.to_string(),
-            "Merge error: Transaction created_at BasicId(0) is lower than the last recorded id"
-        ),
+        Err(err) => assert_outdated(err, 1),
     }
 }
 
@@ -427,7 +451,9 @@ fn merge_conflict_noncommited_vs_commited() {
         .transactional_commit(id_builder.new_id())
         .unwrap();
 
-    bonsai_storage.merge(bonsai_at_txn).unwrap();
+    bonsai_storage
+        .merge(bonsai_at_txn, MergePolicy::default())
+        .unwrap();
 
     // check that changes in the transactional state overwrite the ones in the
     // storage
@@ -450,7 +476,9 @@ fn merge_conflict_noncommited_vs_noncommited() {
         .insert(&identifier, &PAIR2.0, &PAIR2.1)
         .unwrap();
 
-    bonsai_storage.merge(bonsai_at_txn).unwrap();
+    bonsai_storage
+        .merge(bonsai_at_txn, MergePolicy::default())
+        .unwrap();
 
     // check that changes in the transactional state overwrite the ones in the
     // storage
@@ -479,7 +507,9 @@ fn merge_conflict_noncommited_vs_commited_noncommited() {
         .insert(&identifier, &PAIR3.0, &PAIR3.1)
         .unwrap();
 
-    bonsai_storage.merge(bonsai_at_txn).unwrap();
+    bonsai_storage
+        .merge(bonsai_at_txn, MergePolicy::default())
+        .unwrap();
 
     // change in the transactional state overwrites any noncommited changes in
     // the storage
@@ -514,14 +544,9 @@ fn merge_conflict_commited_noncommited_vs_commited_noncommited() {
         .insert(&identifier, &PAIR3.0, &PAIR3.1)
         .unwrap();
 
-    match bonsai_storage.merge(bonsai_at_txn) {
-        Ok(_) => {
-            panic!("Expected merge conflict error")
-        }
-        Err(err) => assert_eq!(
-            err.to_string(),
-            "Merge error: Transaction created_at BasicId(0) is lower than the last recorded id"
-        ),
+    match bonsai_storage.merge(bonsai_at_txn, MergePolicy::default()) {
+        Ok(_) => panic!("Expected merge conflict error"),
+        Err(err) => assert_outdated(err, 1),
     }
 }
 
@@ -542,13 +567,111 @@ fn merge_nonconflict_commited_vs_commited() {
         .transactional_commit(id_builder.new_id())
         .unwrap();
 
-    match bonsai_storage.merge(bonsai_at_txn) {
-        Ok(_) => {
-            panic!("Expected merge conflict error")
-        }
-        Err(err) => assert_eq!(
-            err.to_string(),
-            "Merge error: Transaction created_at BasicId(0) is lower than the last recorded id"
-        ),
+    match bonsai_storage.merge(bonsai_at_txn, MergePolicy::default()) {
+        Ok(_) => panic!("Expected merge conflict error"),
+        Err(err) => assert_outdated(err, 1),
     }
 }
+
+#[test]
+fn merge_fail_policy_commit_order() {
+    let db = create_rocks_db(tempfile::tempdir().unwrap().path()).unwrap();
+    let (identifier, mut bonsai_storage, mut bonsai_at_txn, mut id_builder, _) = init_test(&db);
+
+    bonsai_at_txn
+        .insert(&identifier, &PAIR2.0, &PAIR2.1)
+        .unwrap();
+    bonsai_at_txn
+        .transactional_commit(id_builder.new_id())
+        .unwrap();
+    bonsai_storage
+        .insert(&identifier, &PAIR3.0, &PAIR3.1)
+        .unwrap();
+    bonsai_storage.commit(id_builder.new_id()).unwrap();
+
+    // The commit of the storage is newer than the one of the transactional state.
+    let err = bonsai_storage
+        .merge(bonsai_at_txn, MergePolicy::Fail)
+        .unwrap_err();
+    assert!(
+        matches!(
+            err,
+            BonsaiStorageError::Merge(MergeError::CommitOrder {
+                id: 1,
+                latest_id: 2
+            })
+        ),
+        "{err:?}"
+    );
+}
+
+#[test]
+fn merge_fail_policy_conflict_commited_vs_commited() {
+    let db = create_rocks_db(tempfile::tempdir().unwrap().path()).unwrap();
+    let (identifier, mut bonsai_storage, mut bonsai_at_txn, mut id_builder, _) = init_test(&db);
+
+    bonsai_storage
+        .insert(&identifier, &PAIR2.0, &PAIR3.1)
+        .unwrap();
+    bonsai_storage.commit(id_builder.new_id()).unwrap();
+    bonsai_at_txn
+        .insert(&identifier, &PAIR2.0, &PAIR2.1)
+        .unwrap();
+    bonsai_at_txn
+        .transactional_commit(id_builder.new_id())
+        .unwrap();
+
+    let err = bonsai_storage
+        .merge(bonsai_at_txn, MergePolicy::Fail)
+        .unwrap_err();
+    assert_conflict_on(err, &PAIR2.0);
+}
+
+#[test]
+fn merge_fail_policy_conflict_commited_vs_noncommited() {
+    let db = create_rocks_db(tempfile::tempdir().unwrap().path()).unwrap();
+    let (identifier, mut bonsai_storage, mut bonsai_at_txn, mut id_builder, _) = init_test(&db);
+
+    bonsai_at_txn
+        .insert(&identifier, &PAIR2.0, &PAIR2.1)
+        .unwrap();
+    bonsai_storage
+        .insert(&identifier, &PAIR2.0, &PAIR3.1)
+        .unwrap();
+    bonsai_storage.commit(id_builder.new_id()).unwrap();
+
+    let err = bonsai_storage
+        .merge(bonsai_at_txn, MergePolicy::Fail)
+        .unwrap_err();
+    assert_conflict_on(err, &PAIR2.0);
+}
+
+#[test]
+fn merge_fail_policy_nonconflict_commited_vs_commited() {
+    let db = create_rocks_db(tempfile::tempdir().unwrap().path()).unwrap();
+    let (identifier, mut bonsai_storage, mut bonsai_at_txn, mut id_builder, _) = init_test(&db);
+
+    bonsai_storage
+        .insert(&identifier, &PAIR2.0, &PAIR2.1)
+        .unwrap();
+    bonsai_storage.commit(id_builder.new_id()).unwrap();
+    bonsai_at_txn
+        .insert(&identifier, &PAIR3.0, &PAIR3.1)
+        .unwrap();
+    bonsai_at_txn
+        .transactional_commit(id_builder.new_id())
+        .unwrap();
+
+    // The commits changed different keys.
+    bonsai_storage
+        .merge(bonsai_at_txn, MergePolicy::Fail)
+        .unwrap();
+    assert_eq!(
+        bonsai_storage.get(&identifier, &PAIR2.0).unwrap(),
+        Some(PAIR2.1)
+    );
+    assert_eq!(
+        bonsai_storage.get(&identifier, &PAIR3.0).unwrap(),
+        Some(PAIR3.1)
+    );
+}
@@ -0,0 +1,355 @@
+#![cfg(feature = "std")]
+use super::common::key;
+use crate::{
+    databases::{BTreeMapDb, BTreeMapDbError},
+    id::{BasicId, BasicIdBuilder},
+    BitVec, BonsaiStorage, BonsaiStorageConfig, BonsaiStorageError, MergeConflict, MergeError,
+    MergePolicy,
+};
+use starknet_types_core::{felt::Felt, hash::Pedersen};
+
+type Storage = BonsaiStorage<BasicId, BTreeMapDb<BasicId>, Pedersen>;
+
+fn config() -> BonsaiStorageConfig {
+    BonsaiStorageConfig {
+        snapshot_interval: 1,
+        ..Default::default()
+    }
+}
+
+/// A storage with `key(0)` set and committed at id 0.
+fn storage(config: BonsaiStorageConfig) -> (Storage, BasicIdBuilder) {
+    let mut bonsai_storage = Storage::new(BTreeMapDb::default(), config, 24).unwrap();
+    let mut id_builder = BasicIdBuilder::new();
+    bonsai_storage
+        .insert(&[], &key(0), &Felt::from(1u64))
+        .unwrap();
+    bonsai_storage.commit(id_builder.new_id()).unwrap();
+    (bonsai_storage, id_builder)
+}
+
+fn merge_error(
+    bonsai_storage: &mut Storage,
+    txn: Storage,
+    policy: MergePolicy,
+) -> MergeError<BTreeMapDbError> {
+    match bonsai_storage.merge(txn, policy) {
+        Err(BonsaiStorageError::Merge(e)) => e,
+        other => panic!("expected a merge error, got {other:?}"),
+    }
+}
+
+#[test]
+fn transaction_commits_are_replayed() {
+    let (mut bonsai_storage, mut id_builder) = storage(config());
+    let id0 = BasicId::new(0);
+    let mut txn = bonsai_storage
+        .get_transactional_state(id0, config())
+        .unwrap()
+        .unwrap();
+    let id1 = id_builder.new_id();
+    txn.insert(&[], &key(1), &Felt::from(2u64)).unwrap();
+    txn.remove(&[], &key(0)).unwrap();
+    txn.transactional_commit(id1).unwrap();
+    txn.insert(&[], &key(2), &Felt::from(3u64)).unwrap();
+    bonsai_storage
+        .insert(&[], &key(3), &Felt::from(4u64))
+        .unwrap();
+
+    bonsai_storage.merge(txn, MergePolicy::default()).unwrap();
+
+    assert_eq!(bonsai_storage.get_latest_id().unwrap(), Some(id1));
+    let changes = bonsai_storage.get_changes(id1).unwrap();
+    assert_eq!(changes.len(), 2);
+    assert_eq!(changes[&key(1)].new_value, Some(Felt::from(2u64)));
+    assert_eq!(changes[&key(0)].new_value, None);
+    let state = bonsai_storage
+        .get_transactional_state(id1, config())
+        .unwrap()
+        .unwrap();
+    assert_eq!(state.get(&[], &key(0)).unwrap(), None);
+    assert_eq!(state.get(&[], &key(1)).unwrap(), Some(Felt::from(2u64)));
+    assert_eq!(state.get(&[], &key(2)).unwrap(), None);
+
+    // Both sides' uncommitted changes are kept.
+    bonsai_storage.commit(id_builder.new_id()).unwrap();
+    let mut expected = Storage::new(BTreeMapDb::default(), config(), 24).unwrap();
+    for (n, value) in [(1, 2u64), (2, 3), (3, 4)] {
+        expected.insert(&[], &key(n), &Felt::from(value)).unwrap();
+    }
+    expected.commit(BasicId::new(0)).unwrap();
+    assert_eq!(
+        bonsai_storage.root_hash(&[]).unwrap(),
+        expected.root_hash(&[]).unwrap()
+    );
+}
+
+#[test]
+fn reverted_transaction_commits_are_not_replayed() {
+    let (mut bonsai_storage, mut id_builder) = storage(config());
+    let id0 = BasicId::new(0);
+    let mut txn = bonsai_storage
+        .get_transactional_state(id0, config())
+        .unwrap()
+        .unwrap();
+    let id1 = id_builder.new_id();
+    txn.insert(&[], &key(1), &Felt::from(2u64)).unwrap();
+    txn.transactional_commit(id1).unwrap();
+    let id2 = id_builder.new_id();
+    txn.remove(&[], &key(0)).unwrap();
+    txn.transactional_commit(id2).unwrap();
+    txn.revert_to(id1, id2).unwrap();
+
+    bonsai_storage.merge(txn, MergePolicy::default()).unwrap();
+
+    assert_eq!(bonsai_storage.get_latest_id().unwrap(), Some(id1));
+    assert_eq!(
+        bonsai_storage.get(&[], &key(0)).unwrap(),
+        Some(Felt::from(1u64))
+    );
+    assert_eq!(
+        bonsai_storage.get(&[], &key(1)).unwrap(),
+        Some(Felt::from(2u64))
+    );
+}
+
+#[test]
+fn transaction_wins_over_uncommitted_changes() {
+    let (mut bonsai_storage, _) = storage(config());
+    let mut txn = bonsai_storage
+        .get_transactional_state(BasicId::new(0), config())
+        .unwrap()
+        .unwrap();
+    txn.insert(&[], &key(1), &Felt::from(2u64)).unwrap();
+    bonsai_storage
+        .insert(&[], &key(1), &Felt::from(3u64))
+        .unwrap();
+
+    bonsai_storage.merge(txn, MergePolicy::default()).unwrap();
+    assert_eq!(
+        bonsai_storage.get(&[], &key(1)).unwrap(),
+        Some(Felt::from(2u64))
+    );
+}
+
+#[test]
+fn conflicting_commits_are_reported() {
+    let (mut bonsai_storage, mut id_builder) = storage(config());
+    let mut txn = bonsai_storage
+        .get_transactional_state(BasicId::new(0), config())
+        .unwrap()
+        .unwrap();
+    txn.insert(&[], &key(1), &Felt::from(2u64)).unwrap();
+    txn.remove(&[], &key(0)).unwrap();
+    txn.insert(&[], &key(2), &Felt::from(3u64)).unwrap();
+    bonsai_storage
+        .insert(&[], &key(1), &Felt::from(5u64))
+        .unwrap();
+    bonsai_storage
+        .insert(&[], &key(0), &Felt::from(6u64))
+        .unwrap();
+    bonsai_storage.commit(id_builder.new_id()).unwrap();
+    let root_hash = bonsai_storage.root_hash(&[]).unwrap();
+
+    let MergeError::Conflicts(mut conflicts) =
+        merge_error(&mut bonsai_storage, txn, MergePolicy::Fail)
+    else {
+        panic!("expected conflicts");
+    };
+    conflicts.sort_by(|a, b| a.key.cmp(&b.key));
+    assert_eq!(
+        conflicts,
+        vec![
+            MergeConflict {
+                identifier: vec![],
+                key: key(0),
+                base_value: Some(Felt::from(6u64)),
+                txn_value: None,
+            },
+            MergeConflict {
+                identifier: vec![],
+                key: key(1),
+                base_value: Some(Felt::from(5u64)),
+                txn_value: Some(Felt::from(2u64)),
+            },
+        ]
+    );
+    // The storage is left untouched.
+    assert_eq!(bonsai_storage.root_hash(&[]).unwrap(), root_hash);
+    assert_eq!(bonsai_storage.get(&[], &key(2)).unwrap(), None);
+}
+
+#[test]
+fn disjoint_commits_merge() {
+    let (mut bonsai_storage, mut id_builder) = storage(config());
+    let mut txn = bonsai_storage
+        .get_transactional_state(BasicId::new(0), config())
+        .unwrap()
+        .unwrap();
+    txn.insert(&[], &key(1), &Felt::from(2u64)).unwrap();
+    bonsai_storage
+        .insert(&[], &key(2), &Felt::from(3u64))
+        .unwrap();
+    bonsai_storage.commit(id_builder.new_id()).unwrap();
+
+    bonsai_storage.merge(txn, MergePolicy::Fail).unwrap();
+    assert_eq!(
+        bonsai_storage.get(&[], &key(1)).unwrap(),
+        Some(Felt::from(2u64))
+    );
+    assert_eq!(
+        bonsai_storage.get(&[], &key(2)).unwrap(),
+        Some(Felt::from(3u64))
+    );
+}
+
+#[test]
+fn transaction_commits_must_be_newer() {
+    let (mut bonsai_storage, mut id_builder) = storage(config());
+    let mut txn = bonsai_storage
+        .get_transactional_state(BasicId::new(0), config())
+        .unwrap()
+        .unwrap();
+    let id1 = id_builder.new_id();
+    txn.insert(&[], &key(1), &Felt::from(2u64)).unwrap();
+    txn.transactional_commit(id1).unwrap();
+    bonsai_storage
+        .insert(&[], &key(2), &Felt::from(3u64))
+        .unwrap();
+    bonsai_storage.commit(id1).unwrap();
+
+    assert!(matches!(
+        merge_error(&mut bonsai_storage, txn, MergePolicy::Fail),
+        MergeError::CommitOrder {
+            id: 1,
+            latest_id: 1
+        }
+    ));
+}
+
+#[test]
+fn conflicts_need_trie_logs() {
+    let config = BonsaiStorageConfig {
+        max_saved_trie_logs: Some(0),
+        ..config()
+    };
+    let (mut bonsai_storage, mut id_builder) = storage(config.clone());
+    let txn = bonsai_storage
+        .get_transactional_state(BasicId::new(0), config)
+        .unwrap()
+        .unwrap();
+    bonsai_storage
+        .insert(&[], &key(2), &Felt::from(3u64))
+        .unwrap();
+    bonsai_storage.commit(id_builder.new_id()).unwrap();
+
+    assert!(matches!(
+        merge_error(&mut bonsai_storage, txn, MergePolicy::Fail),
+        MergeError::MissingTrieLogs(1)
+    ));
+}
+
+/// A transactional state at 0 and a storage changing `key(1)` and `key(2)` on both sides, the
+/// storage committing `key(1)` and keeping `key(2)` uncommitted.
+fn overlapping_changes() -> (Storage, Storage) {
+    let (mut bonsai_storage, mut id_builder) = storage(config());
+    let mut txn = bonsai_storage
+        .get_transactional_state(BasicId::new(0), config())
+        .unwrap()
+        .unwrap();
+    txn.insert(&[], &key(1), &Felt::from(2u64)).unwrap();
+    txn.remove(&[], &key(0)).unwrap();
+    txn.insert(&[], &key(3), &Felt::from(4u64)).unwrap();
+    bonsai_storage
+        .insert(&[], &key(1), &Felt::from(5u64))
+        .unwrap();
+    bonsai_storage.commit(id_builder.new_id()).unwrap();
+    bonsai_storage
+        .insert(&[], &key(3), &Felt::from(6u64))
+        .unwrap();
+    (bonsai_storage, txn)
+}
+
+fn values(bonsai_storage: &Storage) -> Vec<Option<Felt>> {
+    (0..4)
+        .map(|n| bonsai_storage.get(&[], &key(n)).unwrap())
+        .collect()
+}
+
+#[test]
+fn fail_policy_rejects_uncommitted_overlaps() {
+    let (mut bonsai_storage, txn) = overlapping_changes();
+    let MergeError::Conflicts(conflicts) = merge_error(&mut bonsai_storage, txn, MergePolicy::Fail)
+    else {
+        panic!("expected conflicts");
+    };
+    let keys: Vec<BitVec> = conflicts.into_iter().map(|conflict| conflict.key).collect();
+    assert_eq!(keys, vec![key(1), key(3)]);
+    assert_eq!(
+        values(&bonsai_storage),
+        vec![
+            Some(Felt::from(1u64)),
+            Some(Felt::from(5u64)),
+            None,
+            Some(Felt::from(6u64))
+        ]
+    );
+}
+
+#[test]
+fn prefer_base_policy() {
+    let (mut bonsai_storage, txn) = overlapping_changes();
+    bonsai_storage.merge(txn, MergePolicy::PreferBase).unwrap();
+    assert_eq!(
+        values(&bonsai_storage),
+        vec![None, Some(Felt::from(5u64)), None, Some(Felt::from(6u64))]
+    );
+}
+
+#[test]
+fn prefer_transaction_policy() {
+    let (mut bonsai_storage, txn) = overlapping_changes();
+    bonsai_storage
+        .merge(txn, MergePolicy::PreferTransaction)
+        .unwrap();
+    assert_eq!(
+        values(&bonsai_storage),
+        vec![None, Some(Felt::from(2u64)), None, Some(Felt::from(4u64))]
+    );
+}
+
+#[test]
+fn resolver_policy() {
+    let (mut bonsai_storage, txn) = overlapping_changes();
+    let mut calls = Vec::new();
+    bonsai_storage
+        .merge(
+            txn,
+            MergePolicy::Resolve(Box::new(|identifier, key, base_value, txn_value| {
+                calls.push((identifier.to_vec(), key.to_bitvec(), base_value, txn_value));
+                Some(base_value.unwrap_or(Felt::ZERO) + txn_value.unwrap_or(Felt::ZERO))
+            })),
+        )
+        .unwrap();
+    assert_eq!(
+        calls,
+        vec![
+            (
+                vec![],
+                key(1),
+                Some(Felt::from(5u64)),
+                Some(Felt::from(2u64))
+            ),
+            (
+                vec![],
+                key(3),
+                Some(Felt::from(6u64)),
+                Some(Felt::from(4u64))
+            ),
+        ]
+    );
+    assert_eq!(
+        values(&bonsai_storage),
+        vec![None, Some(Felt::from(7u64)), None, Some(Felt::from(10u64))]
+    );
+}
@@ -0,0 +1,279 @@
+#![cfg(feature = "std")]
+use super::common::key;
+use crate::{
+    databases::HashMapDb,
+    id::{BasicId, BasicIdBuilder},
+    migration::{
+        migrate, migrate_in_chunks, schema_version, CURRENT_SCHEMA_VERSION, MIGRATION_PROGRESS_KEY,
+        SCHEMA_VERSION_KEY,
+    },
+    trie::trie_db::split_identifier_prefix,
+    BitVec, BonsaiDatabase, BonsaiStorage, BonsaiStorageConfig, BonsaiStorageError, ByteVec,
+    DatabaseKey,
+};
+use starknet_types_core::{felt::Felt, hash::Pedersen};
+
+const ID_LEN: usize = 8;
+
+/// Rewrites a database to the unversioned layout used before identifiers were length-prefixed.
+fn to_legacy_layout(db: &mut HashMapDb<BasicId>) {
+    db.remove(&DatabaseKey::Meta(SCHEMA_VERSION_KEY), None)
+        .unwrap();
+
+    fn legacy(key: &[u8]) -> ByteVec {
+        let (identifier, rest) = split_identifier_prefix(key).unwrap();
+        identifier.iter().chain(rest).copied().collect()
+    }
+
+    fn trie(key: &[u8]) -> DatabaseKey<'_> {
+        DatabaseKey::Trie(key)
+    }
+    fn flat(key: &[u8]) -> DatabaseKey<'_> {
+        DatabaseKey::Flat(key)
+    }
+    fn trie_log(key: &[u8]) -> DatabaseKey<'_> {
+        DatabaseKey::TrieLog(key)
+    }
+
+    type MakeKey = fn(&[u8]) -> DatabaseKey<'_>;
+    let mut rewrites: Vec<(MakeKey, ByteVec, ByteVec, ByteVec)> = Vec::new();
+    for make_key in [trie as MakeKey, flat] {
+        for (key, value) in db.get_by_prefix(&make_key(&[])).unwrap() {
+            rewrites.push((make_key, legacy(&key), key, value));
+        }
+    }
+    for (key, value) in db.get_by_prefix(&DatabaseKey::TrieLog(&[])).unwrap() {
+        let mut new_key = ByteVec::from(&key[..ID_LEN + 1]);
+        new_key.extend_from_slice(&legacy(&key[ID_LEN + 1..key.len() - 2]));
+        new_key.extend_from_slice(&key[key.len() - 2..]);
+        rewrites.push((trie_log, new_key, key, value));
+    }
+
+    for (make_key, _, old_key, _) in &rewrites {
+        db.remove(&make_key(old_key), None).unwrap();
+    }
+    for (make_key, new_key, _, value) in &rewrites {
+        db.insert(&make_key(new_key), value, None).unwrap();
+    }
+}
+
+#[test]
+fn identifiers_sharing_a_prefix_do_not_collide() {
+    let config = BonsaiStorageConfig::default();
+    let mut bonsai_storage: BonsaiStorage<_, _, Pedersen> =
+        BonsaiStorage::new(HashMapDb::<BasicId>::default(), config.clone(), 24).unwrap();
+    let mut id_builder = BasicIdBuilder::new();
+
+    // With a plain concatenation, a prefix scan for identifier [1] also
+    // returns the keys of identifier [1, 2].
+    bonsai_storage
+        .insert(&[1], &BitVec::from_vec(vec![2, 0, 1]), &Felt::ONE)
+        .unwrap();
+    bonsai_storage
+        .insert(&[1, 2], &BitVec::from_vec(vec![0, 1, 2]), &Felt::TWO)
+        .unwrap();
+    bonsai_storage.commit(id_builder.new_id()).unwrap();
+
+    assert_eq!(bonsai_storage.get_keys(&[1]).unwrap(), vec![vec![2, 0, 1]]);
+    assert_eq!(
+        bonsai_storage.get_keys(&[1, 2]).unwrap(),
+        vec![vec![0, 1, 2]]
+    );
+
+    let mut single: BonsaiStorage<_, _, Pedersen> =
+        BonsaiStorage::new(HashMapDb::<BasicId>::default(), config, 24).unwrap();
+    single
+        .insert(&[1], &BitVec::from_vec(vec![2, 0, 1]), &Felt::ONE)
+        .unwrap();
+    single.commit(BasicId::new(0)).unwrap();
+    assert_eq!(
+        bonsai_storage.root_hash(&[1]).unwrap(),
+        single.root_hash(&[1]).unwrap()
+    );
+}
+
+#[test]
+fn migrate_legacy_layout() {
+    let identifiers: [&[u8]; 3] = [&[], &[1], &[1, 2]];
+    let config = BonsaiStorageConfig::default();
+    let mut bonsai_storage: BonsaiStorage<_, _, Pedersen> =
+        BonsaiStorage::new(HashMapDb::<BasicId>::default(), config.clone(), 24).unwrap();
+    let mut id_builder = BasicIdBuilder::new();
+
+    // Every identifier gets its own set of keys so that the leaf changes
+    // returned by `get_changes` do not overlap.
+    let key_of = |i: usize, n: u64| key(3 * n + i as u64);
+    for (i, identifier) in identifiers.iter().enumerate() {
+        for n in 0..20 {
+            bonsai_storage
+                .insert(
+                    identifier,
+                    &key_of(i, n),
+                    &Felt::from(n + 1 + 100 * i as u64),
+                )
+                .unwrap();
+        }
+    }
+    let id1 = id_builder.new_id();
+    bonsai_storage.commit(id1).unwrap();
+    for (i, identifier) in identifiers.iter().enumerate() {
+        bonsai_storage
+            .insert(identifier, &key_of(i, 0), &Felt::from(1000))
+            .unwrap();
+        bonsai_storage.remove(identifier, &key_of(i, 1)).unwrap();
+    }
+    let id2 = id_builder.new_id();
+    bonsai_storage.commit(id2).unwrap();
+
+    let roots: Vec<_> = identifiers
+        .iter()
+        .map(|identifier| bonsai_storage.root_hash(identifier).unwrap())
+        .collect();
+    let changes = bonsai_storage.get_changes(id2).unwrap();
+
+    let mut db = bonsai_storage.tries.db_ref().db.clone();
+    to_legacy_layout(&mut db);
+    assert!(matches!(
+        BonsaiStorage::<BasicId, _, Pedersen>::new(db.clone(), config.clone(), 24),
+        Err(BonsaiStorageError::MigrationRequired {
+            from: 0,
+            to: CURRENT_SCHEMA_VERSION
+        })
+    ));
+    migrate::<_, BasicId>(&mut db, &identifiers).unwrap();
+    assert_eq!(schema_version(&db).unwrap(), Some(CURRENT_SCHEMA_VERSION));
+
+    let mut bonsai_storage: BonsaiStorage<_, _, Pedersen> =
+        BonsaiStorage::new(db, config, 24).unwrap();
+    for (i, (identifier, root)) in identifiers.iter().zip(&roots).enumerate() {
+        assert_eq!(&bonsai_storage.root_hash(identifier).unwrap(), root);
+        assert_eq!(
+            bonsai_storage.get(identifier, &key_of(i, 0)).unwrap(),
+            Some(Felt::from(1000))
+        );
+        assert_eq!(bonsai_storage.get_keys(identifier).unwrap().len(), 19);
+    }
+    assert_eq!(bonsai_storage.get_changes(id2).unwrap(), changes);
+
+    bonsai_storage.revert_to(id1, id2).unwrap();
+    for (i, identifier) in identifiers.iter().enumerate() {
+        assert_eq!(
+            bonsai_storage.get(identifier, &key_of(i, 1)).unwrap(),
+            Some(Felt::from(2 + 100 * i as u64))
+        );
+    }
+}
+
+#[test]
+fn migrate_rejects_unknown_identifier() {
+    let mut bonsai_storage: BonsaiStorage<_, _, Pedersen> = BonsaiStorage::new(
+        HashMapDb::<BasicId>::default(),
+        BonsaiStorageConfig::default(),
+        24,
+    )
+    .unwrap();
+    bonsai_storage
+        .insert(&[5, 5, 5], &key(1), &Felt::ONE)
+        .unwrap();
+    bonsai_storage.commit(BasicId::new(0)).unwrap();
+
+    let mut db = bonsai_storage.tries.db_ref().db.clone();
+    to_legacy_layout(&mut db);
+    let before = db.get_by_prefix(&DatabaseKey::Flat(&[])).unwrap();
+    assert!(migrate::<_, BasicId>(&mut db, &[&[1]]).is_err());
+    assert_eq!(db.get_by_prefix(&DatabaseKey::Flat(&[])).unwrap(), before);
+    assert_eq!(schema_version(&db).unwrap(), None);
+}
+
+#[test]
+fn interrupted_migration_resumes() {
+    let config = BonsaiStorageConfig::default();
+    let mut bonsai_storage: BonsaiStorage<_, _, Pedersen> =
+        BonsaiStorage::new(HashMapDb::<BasicId>::default(), config.clone(), 24).unwrap();
+    let mut id_builder = BasicIdBuilder::new();
+    for identifier in [[1], [2]] {
+        for n in 0..10 {
+            bonsai_storage
+                .insert(&identifier, &key(n), &Felt::from(n + 1))
+                .unwrap();
+        }
+    }
+    let id = id_builder.new_id();
+    bonsai_storage.commit(id).unwrap();
+    let roots = [[1], [2]].map(|identifier| bonsai_storage.root_hash(&identifier).unwrap());
+    let changes = bonsai_storage.get_changes(id).unwrap();
+
+    let mut db = bonsai_storage.tries.db_ref().db.clone();
+    to_legacy_layout(&mut db);
+    // The keys of [1] sort first: some batches are written before the ones of [2] fail.
+    assert!(matches!(
+        migrate_in_chunks::<_, BasicId>(&mut db, &[&[1]], 3),
+        Err(BonsaiStorageError::Migration(_))
+    ));
+    assert!(db
+        .contains(&DatabaseKey::Meta(MIGRATION_PROGRESS_KEY))
+        .unwrap());
+    assert!(matches!(
+        BonsaiStorage::<BasicId, _, Pedersen>::new(db.clone(), config.clone(), 24),
+        Err(BonsaiStorageError::MigrationRequired { from: 0, .. })
+    ));
+
+    migrate_in_chunks::<_, BasicId>(&mut db, &[&[1], &[2]], 3).unwrap();
+    assert!(!db
+        .contains(&DatabaseKey::Meta(MIGRATION_PROGRESS_KEY))
+        .unwrap());
+    assert_eq!(schema_version(&db).unwrap(), Some(CURRENT_SCHEMA_VERSION));
+    let bonsai_storage: BonsaiStorage<_, _, Pedersen> = BonsaiStorage::new(db, config, 24).unwrap();
+    for (identifier, root) in [[1], [2]].iter().zip(roots) {
+        assert_eq!(bonsai_storage.root_hash(identifier).unwrap(), root);
+        assert_eq!(bonsai_storage.get_keys(identifier).unwrap().len(), 10);
+    }
+    assert_eq!(bonsai_storage.get_changes(id).unwrap(), changes);
+}
+
+#[test]
+fn new_database_is_stamped_by_its_first_commit() {
+    let mut bonsai_storage: BonsaiStorage<_, _, Pedersen> = BonsaiStorage::new(
+        HashMapDb::<BasicId>::default(),
+        BonsaiStorageConfig::default(),
+        24,
+    )
+    .unwrap();
+    // Opening a database does not write to it.
+    assert_eq!(
+        schema_version(&bonsai_storage.tries.db_ref().db).unwrap(),
+        None
+    );
+
+    bonsai_storage.insert(&[], &key(1), &Felt::ONE).unwrap();
+    bonsai_storage.commit(BasicId::new(0)).unwrap();
+    let mut db = bonsai_storage.tries.db_ref().db.clone();
+    assert_eq!(schema_version(&db).unwrap(), Some(CURRENT_SCHEMA_VERSION));
+
+    // Migrating an up to date database is a no-op.
+    migrate::<_, BasicId>(&mut db, &[]).unwrap();
+    assert_eq!(schema_version(&db).unwrap(), Some(CURRENT_SCHEMA_VERSION));
+
+    let mut db = HashMapDb::<BasicId>::default();
+    migrate::<_, BasicId>(&mut db, &[]).unwrap();
+    assert_eq!(schema_version(&db).unwrap(), Some(CURRENT_SCHEMA_VERSION));
+}
+
+#[test]
+fn newer_schema_version_is_rejected() {
+    let mut db = HashMapDb::<BasicId>::default();
+    db.insert(
+        &DatabaseKey::Meta(SCHEMA_VERSION_KEY),
+        &(CURRENT_SCHEMA_VERSION + 1).to_le_bytes(),
+        None,
+    )
+    .unwrap();
+    assert!(matches!(
+        BonsaiStorage::<BasicId, _, Pedersen>::new(db.clone(), BonsaiStorageConfig::default(), 24),
+        Err(BonsaiStorageError::UnsupportedSchemaVersion(version)) if version == CURRENT_SCHEMA_VERSION + 1
+    ));
+    assert!(matches!(
+        migrate::<_, BasicId>(&mut db, &[]),
+        Err(BonsaiStorageError::UnsupportedSchemaVersion(_))
+    ));
+}
@@ -1,7 +1,23 @@
+mod btree_map_db;
+mod commit_info;
+mod common;
+mod corruption;
+mod file_db;
 mod madara_comparison;
-// mod merge;
+mod merge;
+mod merge_conflict;
 mod merkle_tree;
+mod migration;
+mod multi_proof;
+mod observer;
+mod prefetch;
+mod prefix_scan;
 mod proptest;
+mod reader;
+mod rocks_db;
+mod savepoint;
 mod simple;
-// mod transactional_state;
+mod snapshot;
+mod transactional_state;
 mod trie_log;
+mod trie_log_stream;
@@ -0,0 +1,60 @@
+#![cfg(feature = "std")]
+use super::common::{key, storage};
+use crate::{
+    id::{BasicId, BasicIdBuilder},
+    BitVec, BonsaiStorage, BonsaiStorageConfig, ByteVec,
+};
+use starknet_types_core::{felt::Felt, hash::Pedersen};
+use std::thread;
+
+#[test]
+fn proofs_are_generated_in_parallel_without_touching_the_tree() {
+    let mut bonsai_storage = storage();
+    let mut id_builder = BasicIdBuilder::new();
+    for n in 0..200u64 {
+        bonsai_storage
+            .insert(&[], &key(n * 7919 % 65521), &Felt::from(n + 1))
+            .unwrap();
+    }
+    bonsai_storage.commit(id_builder.new_id()).unwrap();
+
+    // Reload the storage so that most nodes are only in the database, then make a few changes.
+    let db = bonsai_storage.tries.db_ref().db.clone();
+    let mut bonsai_storage: BonsaiStorage<BasicId, _, Pedersen> =
+        BonsaiStorage::new(db, BonsaiStorageConfig::default(), 24).unwrap();
+    for n in 0..10u64 {
+        bonsai_storage
+            .insert(&[], &key(n * 7919 % 65521), &Felt::from(n + 1000))
+            .unwrap();
+    }
+    let keys: Vec<BitVec> = (0..40u64).map(|n| key(n * 7919 * 5 % 65521)).collect();
+    let nodes = bonsai_storage.tries.trees[&ByteVec::new()].nodes.len();
+
+    let proofs: Vec<_> = thread::scope(|scope| {
+        let handles: Vec<_> = (0..4)
+            .map(|_| scope.spawn(|| bonsai_storage.get_multi_proof(&[], keys.iter()).unwrap()))
+            .collect();
+        handles.into_iter().map(|h| h.join().unwrap()).collect()
+    });
+    assert_eq!(
+        bonsai_storage.tries.trees[&ByteVec::new()].nodes.len(),
+        nodes
+    );
+
+    // The proofs include the uncommitted changes.
+    bonsai_storage.commit(id_builder.new_id()).unwrap();
+    let root_hash = bonsai_storage.root_hash(&[]).unwrap();
+    let expected: Vec<Felt> = keys
+        .iter()
+        .map(|key| bonsai_storage.get(&[], key).unwrap().unwrap_or(Felt::ZERO))
+        .collect();
+    for proof in proofs {
+        assert_eq!(
+            proof
+                .verify_proof::<Pedersen>(root_hash, keys.iter(), 24)
+                .collect::<Result<Vec<_>, _>>()
+                .unwrap(),
+            expected
+        );
+    }
+}
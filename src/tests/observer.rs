@@ -0,0 +1,126 @@
+#![cfg(feature = "std")]
+use super::common::key;
+use crate::{
+    databases::BTreeMapDb,
+    id::{BasicId, BasicIdBuilder},
+    BonsaiStorage, BonsaiStorageConfig, Change, CommitEvent, CommitKind, CommitObserver,
+};
+use starknet_types_core::{felt::Felt, hash::Pedersen};
+use std::sync::{Arc, Mutex};
+
+#[derive(Clone, Default)]
+struct Recorder(Arc<Mutex<Vec<CommitEvent<BasicId>>>>);
+
+impl CommitObserver<BasicId> for Recorder {
+    fn on_commit(&mut self, event: &CommitEvent<BasicId>) {
+        self.0.lock().unwrap().push(event.clone());
+    }
+}
+
+impl Recorder {
+    fn take(&self) -> Vec<CommitEvent<BasicId>> {
+        std::mem::take(&mut self.0.lock().unwrap())
+    }
+}
+
+fn change(old_value: Option<u64>, new_value: Option<u64>) -> Change {
+    Change {
+        old_value: old_value.map(Felt::from),
+        new_value: new_value.map(Felt::from),
+    }
+}
+
+#[test]
+fn observers_see_commits_without_trie_logs() {
+    // Trie logs are disabled, the observers still see every commit.
+    let config = BonsaiStorageConfig {
+        max_saved_trie_logs: Some(0),
+        ..Default::default()
+    };
+    let mut bonsai_storage: BonsaiStorage<BasicId, _, Pedersen> =
+        BonsaiStorage::new(BTreeMapDb::<BasicId>::default(), config, 24).unwrap();
+    let recorder = Recorder::default();
+    bonsai_storage.add_commit_observer(recorder.clone());
+    let mut id_builder = BasicIdBuilder::new();
+
+    bonsai_storage
+        .insert(&[1], &key(0), &Felt::from(1u64))
+        .unwrap();
+    bonsai_storage
+        .insert(&[2], &key(1), &Felt::from(2u64))
+        .unwrap();
+    let id = id_builder.new_id();
+    bonsai_storage.commit(id).unwrap();
+
+    let events = recorder.take();
+    assert_eq!(events.len(), 1);
+    assert_eq!(events[0].kind, CommitKind::Commit);
+    assert_eq!(events[0].id, id);
+    assert_eq!(
+        events[0].roots,
+        vec![
+            (vec![1], bonsai_storage.root_hash(&[1]).unwrap()),
+            (vec![2], bonsai_storage.root_hash(&[2]).unwrap()),
+        ]
+    );
+    assert_eq!(events[0].changes[&vec![1]][&key(0)], change(None, Some(1)));
+    assert_eq!(events[0].changes[&vec![2]][&key(1)], change(None, Some(2)));
+    assert!(bonsai_storage.get_changes(id).unwrap().is_empty());
+}
+
+#[test]
+fn observers_see_reverts_and_transactional_commits() {
+    let config = BonsaiStorageConfig {
+        snapshot_interval: 1,
+        ..Default::default()
+    };
+    let mut bonsai_storage: BonsaiStorage<BasicId, _, Pedersen> =
+        BonsaiStorage::new(BTreeMapDb::<BasicId>::default(), config.clone(), 24).unwrap();
+    let mut id_builder = BasicIdBuilder::new();
+    bonsai_storage
+        .insert(&[1], &key(0), &Felt::from(1u64))
+        .unwrap();
+    let id0 = id_builder.new_id();
+    bonsai_storage.commit(id0).unwrap();
+    let root0 = bonsai_storage.root_hash(&[1]).unwrap();
+
+    let recorder = Recorder::default();
+    bonsai_storage.add_commit_observer(recorder.clone());
+    bonsai_storage
+        .insert(&[1], &key(0), &Felt::from(2u64))
+        .unwrap();
+    bonsai_storage
+        .insert(&[1], &key(1), &Felt::from(3u64))
+        .unwrap();
+    let id1 = id_builder.new_id();
+    bonsai_storage.commit(id1).unwrap();
+    bonsai_storage.remove(&[1], &key(0)).unwrap();
+    let id2 = id_builder.new_id();
+    bonsai_storage.commit(id2).unwrap();
+    assert_eq!(recorder.take().len(), 2);
+
+    bonsai_storage.revert_to(id0, id2).unwrap();
+    let events = recorder.take();
+    assert_eq!(events.len(), 1);
+    assert_eq!(events[0].kind, CommitKind::Revert);
+    assert_eq!(events[0].id, id0);
+    assert_eq!(events[0].roots, vec![(vec![1], root0)]);
+    let changes = &events[0].changes[&vec![1]];
+    assert_eq!(changes.len(), 2);
+    assert_eq!(changes[&key(0)], change(None, Some(1)));
+    assert_eq!(changes[&key(1)], change(Some(3), None));
+
+    let mut txn = bonsai_storage
+        .get_transactional_state(id0, config)
+        .unwrap()
+        .unwrap();
+    let txn_recorder = Recorder::default();
+    txn.add_commit_observer(txn_recorder.clone());
+    txn.insert(&[1], &key(2), &Felt::from(4u64)).unwrap();
+    txn.transactional_commit(id1).unwrap();
+    let events = txn_recorder.take();
+    assert_eq!(events.len(), 1);
+    assert_eq!(events[0].id, id1);
+    assert_eq!(events[0].changes[&vec![1]][&key(2)], change(None, Some(4)));
+    assert!(recorder.take().is_empty());
+}
@@ -0,0 +1,156 @@
+#![cfg(feature = "std")]
+use super::common::key;
+use crate::{
+    databases::HashMapDb,
+    id::{BasicId, BasicIdBuilder},
+    BitVec, BonsaiDatabase, BonsaiStorage, BonsaiStorageConfig, ByteVec, DatabaseKey,
+};
+use starknet_types_core::{felt::Felt, hash::Pedersen};
+use std::cell::Cell;
+
+/// Counts the reads of trie nodes made through `get` and `get_many`.
+#[derive(Debug)]
+struct CountingDb {
+    db: HashMapDb<BasicId>,
+    trie_gets: Cell<usize>,
+    get_many_calls: Cell<usize>,
+}
+
+impl CountingDb {
+    fn new(db: HashMapDb<BasicId>) -> Self {
+        Self {
+            db,
+            trie_gets: Cell::new(0),
+            get_many_calls: Cell::new(0),
+        }
+    }
+}
+
+impl BonsaiDatabase for CountingDb {
+    type Batch = <HashMapDb<BasicId> as BonsaiDatabase>::Batch;
+    type DatabaseError = <HashMapDb<BasicId> as BonsaiDatabase>::DatabaseError;
+
+    fn create_batch(&self) -> Self::Batch {
+        self.db.create_batch()
+    }
+
+    fn get(&self, key: &DatabaseKey) -> Result<Option<ByteVec>, Self::DatabaseError> {
+        if matches!(key, DatabaseKey::Trie(_)) {
+            self.trie_gets.set(self.trie_gets.get() + 1);
+        }
+        self.db.get(key)
+    }
+
+    fn get_many(&self, keys: &[DatabaseKey]) -> Result<Vec<Option<ByteVec>>, Self::DatabaseError> {
+        self.get_many_calls.set(self.get_many_calls.get() + 1);
+        keys.iter().map(|key| self.db.get(key)).collect()
+    }
+
+    fn get_by_prefix(
+        &self,
+        prefix: &DatabaseKey,
+    ) -> Result<Vec<(ByteVec, ByteVec)>, Self::DatabaseError> {
+        self.db.get_by_prefix(prefix)
+    }
+
+    fn contains(&self, key: &DatabaseKey) -> Result<bool, Self::DatabaseError> {
+        self.db.contains(key)
+    }
+
+    fn insert(
+        &mut self,
+        key: &DatabaseKey,
+        value: &[u8],
+        batch: Option<&mut Self::Batch>,
+    ) -> Result<Option<ByteVec>, Self::DatabaseError> {
+        self.db.insert(key, value, batch)
+    }
+
+    fn remove(
+        &mut self,
+        key: &DatabaseKey,
+        batch: Option<&mut Self::Batch>,
+    ) -> Result<Option<ByteVec>, Self::DatabaseError> {
+        self.db.remove(key, batch)
+    }
+
+    fn remove_by_prefix(&mut self, prefix: &DatabaseKey) -> Result<(), Self::DatabaseError> {
+        self.db.remove_by_prefix(prefix)
+    }
+
+    fn write_batch(&mut self, batch: Self::Batch) -> Result<(), Self::DatabaseError> {
+        self.db.write_batch(batch)
+    }
+
+    fn dump_database(&self) {
+        self.db.dump_database()
+    }
+}
+
+fn populated_db() -> HashMapDb<BasicId> {
+    let mut bonsai_storage: BonsaiStorage<_, _, Pedersen> = BonsaiStorage::new(
+        HashMapDb::<BasicId>::default(),
+        BonsaiStorageConfig::default(),
+        24,
+    )
+    .unwrap();
+    for n in 0..300u64 {
+        bonsai_storage
+            .insert(&[], &key(n * 7919 % 65521), &Felt::from(n + 1))
+            .unwrap();
+    }
+    bonsai_storage
+        .commit(BasicIdBuilder::new().new_id())
+        .unwrap();
+    bonsai_storage.tries.db_ref().db.clone()
+}
+
+#[test]
+fn multi_proof_reads_nodes_by_level() {
+    let db = populated_db();
+    let keys: Vec<BitVec> = (0..40u64).map(|n| key(n * 7919 * 7 % 65521)).collect();
+
+    let mut reference: BonsaiStorage<BasicId, _, Pedersen> =
+        BonsaiStorage::new(db.clone(), BonsaiStorageConfig::default(), 24).unwrap();
+    let expected = reference.get_multi_proof(&[], keys.iter()).unwrap();
+
+    let mut bonsai_storage: BonsaiStorage<BasicId, _, Pedersen> =
+        BonsaiStorage::new(CountingDb::new(db), BonsaiStorageConfig::default(), 24).unwrap();
+    let proof = bonsai_storage.get_multi_proof(&[], keys.iter()).unwrap();
+    assert_eq!(proof.0, expected.0);
+
+    let counting_db = &bonsai_storage.tries.db_ref().db;
+    // Only the root is read on its own, and there is one batched read per level of the tree.
+    assert_eq!(counting_db.trie_gets.get(), 1);
+    assert!(counting_db.get_many_calls.get() <= 24);
+}
+
+#[test]
+fn prefetch_before_updates() {
+    let db = populated_db();
+    let updates: Vec<(BitVec, Felt)> = (0..60u64)
+        .map(|n| (key(n * 7919 * 3 % 65521), Felt::from(n + 1000)))
+        .collect();
+
+    let mut reference: BonsaiStorage<BasicId, _, Pedersen> =
+        BonsaiStorage::new(db.clone(), BonsaiStorageConfig::default(), 24).unwrap();
+    let mut bonsai_storage: BonsaiStorage<BasicId, _, Pedersen> =
+        BonsaiStorage::new(CountingDb::new(db), BonsaiStorageConfig::default(), 24).unwrap();
+    bonsai_storage
+        .prefetch(&[], updates.iter().map(|(key, _)| key))
+        .unwrap();
+    let trie_gets = bonsai_storage.tries.db_ref().db.trie_gets.get();
+
+    for (key, value) in &updates {
+        reference.insert(&[], key, value).unwrap();
+        bonsai_storage.insert(&[], key, value).unwrap();
+    }
+    assert_eq!(bonsai_storage.tries.db_ref().db.trie_gets.get(), trie_gets);
+
+    reference.tries.commit().unwrap();
+    bonsai_storage.tries.commit().unwrap();
+    assert_eq!(
+        bonsai_storage.root_hash(&[]).unwrap(),
+        reference.root_hash(&[]).unwrap()
+    );
+}
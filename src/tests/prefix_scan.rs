@@ -0,0 +1,157 @@
+#![cfg(feature = "std")]
+#[cfg(feature = "rocksdb")]
+use crate::databases::{create_rocks_db, RocksDB, RocksDBConfig};
+use crate::{
+    databases::{BTreeMapDb, FileDb, HashMapDb},
+    id::BasicId,
+    trie::trie_db::identifier_prefix,
+    BonsaiDatabase, BonsaiPersistentDatabase, ByteVec, DatabaseKey,
+};
+
+fn flat_key(identifier: &[u8], path: &[u8]) -> ByteVec {
+    let mut key = identifier_prefix(identifier);
+    key.extend_from_slice(path);
+    key
+}
+
+fn fill<DB: BonsaiDatabase>(db: &mut DB) {
+    let entries: [(&[u8], &[u8]); 7] = [
+        (&[1], &[]),
+        (&[1], &[7]),
+        (&[1], &[0]),
+        (&[1], &[3, 1]),
+        (&[1], &[3]),
+        (&[1, 2], &[0]),
+        (&[2], &[0]),
+    ];
+    for (identifier, path) in entries {
+        let key = flat_key(identifier, path);
+        db.insert(&DatabaseKey::Flat(&key), &key, None).unwrap();
+    }
+    db.insert(&DatabaseKey::Trie(&flat_key(&[1], &[5])), &[5], None)
+        .unwrap();
+}
+
+fn scan<DB: BonsaiDatabase>(
+    db: &DB,
+    prefix: &[u8],
+    seek: Option<&[u8]>,
+    upper_bound: Option<&[u8]>,
+) -> Vec<ByteVec> {
+    db.iter_by_prefix(&DatabaseKey::Flat(prefix), seek, upper_bound)
+        .unwrap()
+        .map(|entry| {
+            let (key, value) = entry.unwrap();
+            assert_eq!(key, value);
+            key
+        })
+        .collect()
+}
+
+fn keys(paths: &[&[u8]]) -> Vec<ByteVec> {
+    paths.iter().map(|path| flat_key(&[1], path)).collect()
+}
+
+fn check_scans<DB: BonsaiDatabase>(db: &DB) {
+    let prefix = identifier_prefix(&[1]);
+
+    assert_eq!(
+        scan(db, &prefix, None, None),
+        keys(&[&[], &[0], &[3], &[3, 1], &[7]])
+    );
+    // The scan matches `get_by_prefix`, in key order.
+    let mut entries = db.get_by_prefix(&DatabaseKey::Flat(&prefix)).unwrap();
+    entries.sort();
+    assert_eq!(
+        entries.into_iter().map(|(key, _)| key).collect::<Vec<_>>(),
+        scan(db, &prefix, None, None)
+    );
+
+    let seek = flat_key(&[1], &[3]);
+    let upper_bound = flat_key(&[1], &[7]);
+    assert_eq!(
+        scan(db, &prefix, Some(seek.as_slice()), None),
+        keys(&[&[3], &[3, 1], &[7]])
+    );
+    assert_eq!(
+        scan(
+            db,
+            &prefix,
+            Some(seek.as_slice()),
+            Some(upper_bound.as_slice())
+        ),
+        keys(&[&[3], &[3, 1]])
+    );
+    assert_eq!(
+        scan(db, &prefix, None, Some(flat_key(&[1], &[0]).as_slice())),
+        keys(&[&[]])
+    );
+    // A seek before the prefix starts at the prefix.
+    assert_eq!(scan(db, &prefix, Some(&[0][..]), None).len(), 5);
+    // A bound before the seek gives nothing.
+    assert!(scan(
+        db,
+        &prefix,
+        Some(upper_bound.as_slice()),
+        Some(seek.as_slice())
+    )
+    .is_empty());
+
+    assert_eq!(scan(db, &seek, None, None), keys(&[&[3], &[3, 1]]));
+    assert_eq!(
+        db.iter_by_prefix(&DatabaseKey::Trie(&prefix), None, None)
+            .unwrap()
+            .count(),
+        1
+    );
+    assert_eq!(
+        db.iter_by_prefix(&DatabaseKey::TrieLog(&prefix), None, None)
+            .unwrap()
+            .count(),
+        0
+    );
+}
+
+#[test]
+fn hashmap_db_scans() {
+    let mut db = HashMapDb::<BasicId>::default();
+    fill(&mut db);
+    check_scans(&db);
+}
+
+#[test]
+fn btree_map_db_scans() {
+    let mut db = BTreeMapDb::<BasicId>::default();
+    fill(&mut db);
+    check_scans(&db);
+
+    db.snapshot(BasicId::new(0));
+    let (_, txn) = db.transaction(BasicId::new(0)).unwrap();
+    check_scans(&txn);
+}
+
+#[test]
+fn file_db_scans() {
+    let dir = tempfile::tempdir().unwrap();
+    let mut db = FileDb::<BasicId>::open(dir.path().join("db")).unwrap();
+    fill(&mut db);
+    check_scans(&db);
+
+    db.snapshot(BasicId::new(0));
+    let (_, txn) = db.transaction(BasicId::new(0)).unwrap();
+    check_scans(&txn);
+}
+
+#[cfg(feature = "rocksdb")]
+#[test]
+fn rocks_db_scans() {
+    let tempdir = tempfile::tempdir().unwrap();
+    let rocks_db = create_rocks_db(tempdir.path()).unwrap();
+    let mut db = RocksDB::<BasicId>::new(&rocks_db, RocksDBConfig::default());
+    fill(&mut db);
+    check_scans(&db);
+
+    db.snapshot(BasicId::new(0));
+    let (_, txn) = db.transaction(BasicId::new(0)).unwrap();
+    check_scans(&txn);
+}
@@ -0,0 +1,102 @@
+#![cfg(feature = "std")]
+use super::common::key;
+#[cfg(feature = "rocksdb")]
+use crate::databases::{create_rocks_db, RocksDB, RocksDBConfig};
+use crate::{
+    databases::{BTreeMapDb, FileDb, HashMapDb},
+    id::{BasicId, BasicIdBuilder},
+    BitVec, BonsaiDatabase, BonsaiPersistentDatabase, BonsaiStorage, BonsaiStorageConfig,
+};
+use starknet_types_core::{felt::Felt, hash::Pedersen};
+use std::thread;
+
+fn assert_send_sync<T: Send + Sync>(_: &T) {}
+
+/// Readers keep seeing the commit they were created at while the storage keeps committing.
+fn readers_are_pinned<DB>(db: DB)
+where
+    DB: BonsaiDatabase
+        + BonsaiPersistentDatabase<BasicId, DatabaseError = <DB as BonsaiDatabase>::DatabaseError>,
+{
+    let identifier = vec![1];
+    let mut bonsai_storage: BonsaiStorage<BasicId, _, Pedersen> =
+        BonsaiStorage::new(db, BonsaiStorageConfig::default(), 24).unwrap();
+    let mut id_builder = BasicIdBuilder::new();
+    for n in 0..10 {
+        bonsai_storage
+            .insert(&identifier, &key(n), &Felt::from(n + 1))
+            .unwrap();
+    }
+    let id = id_builder.new_id();
+    bonsai_storage.commit(id).unwrap();
+    let keys: Vec<BitVec> = (0..12).map(key).collect();
+    let root_hash = bonsai_storage.root_hash(&identifier).unwrap();
+    let proof = bonsai_storage
+        .get_multi_proof(&identifier, keys.iter())
+        .unwrap();
+
+    // Uncommitted changes are not seen by readers.
+    bonsai_storage
+        .insert(&identifier, &key(10), &Felt::from(11u64))
+        .unwrap();
+    let reader = bonsai_storage.reader();
+    assert_send_sync(&reader);
+
+    thread::scope(|scope| {
+        for _ in 0..4 {
+            scope.spawn(|| {
+                for _ in 0..10 {
+                    assert_eq!(reader.get_latest_id().unwrap(), Some(id));
+                    assert_eq!(reader.root_hash(&identifier).unwrap(), root_hash);
+                    assert_eq!(
+                        reader.get(&identifier, &key(3)).unwrap(),
+                        Some(Felt::from(4u64))
+                    );
+                    assert!(!reader.contains(&identifier, &key(10)).unwrap());
+                    assert_eq!(reader.get_keys(&identifier).unwrap().len(), 10);
+                    assert_eq!(
+                        reader.get_multi_proof(&identifier, keys.iter()).unwrap().0,
+                        proof.0
+                    );
+                }
+            });
+        }
+        for n in 0..5 {
+            bonsai_storage.remove(&identifier, &key(n)).unwrap();
+            bonsai_storage.commit(id_builder.new_id()).unwrap();
+        }
+    });
+
+    assert_eq!(reader.root_hash(&identifier).unwrap(), root_hash);
+    assert_eq!(bonsai_storage.get_keys(&identifier).unwrap().len(), 6);
+    let reader = bonsai_storage.reader();
+    assert_eq!(
+        reader.root_hash(&identifier).unwrap(),
+        bonsai_storage.root_hash(&identifier).unwrap()
+    );
+    assert_eq!(reader.get(&identifier, &key(3)).unwrap(), None);
+}
+
+#[test]
+fn hashmap_db_readers_are_pinned() {
+    readers_are_pinned(HashMapDb::<BasicId>::default());
+}
+
+#[test]
+fn btree_map_db_readers_are_pinned() {
+    readers_are_pinned(BTreeMapDb::<BasicId>::default());
+}
+
+#[test]
+fn file_db_readers_are_pinned() {
+    let dir = tempfile::tempdir().unwrap();
+    readers_are_pinned(FileDb::<BasicId>::open(dir.path().join("db")).unwrap());
+}
+
+#[cfg(feature = "rocksdb")]
+#[test]
+fn rocks_db_readers_are_pinned() {
+    let tempdir = tempfile::tempdir().unwrap();
+    let rocks_db = create_rocks_db(tempdir.path()).unwrap();
+    readers_are_pinned(RocksDB::<BasicId>::new(&rocks_db, RocksDBConfig::default()));
+}
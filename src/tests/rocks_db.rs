@@ -0,0 +1,439 @@
+#![cfg(all(feature = "std", feature = "rocksdb"))]
+use super::common::key;
+use crate::{
+    databases::{
+        create_rocks_db, open_rocks_db, open_rocks_db_read_only, RocksDB, RocksDBColumnFamilies,
+        RocksDBConfig, RocksDBError, RocksDBOpenOptions, RocksDBReadOnly,
+    },
+    id::{BasicId, BasicIdBuilder},
+    migration::CURRENT_SCHEMA_VERSION,
+    BonsaiDatabase, BonsaiStorage, BonsaiStorageConfig, BonsaiStorageError, DatabaseKey,
+};
+use rocksdb::{Options, DB};
+use starknet_types_core::{felt::Felt, hash::Pedersen};
+
+#[test]
+fn reopen_keeps_data() {
+    let identifier = vec![];
+    let tempdir = tempfile::tempdir().unwrap();
+    let root_hash = {
+        let db = open_rocks_db(tempdir.path(), &RocksDBOpenOptions::default()).unwrap();
+        let mut bonsai_storage: BonsaiStorage<BasicId, _, Pedersen> = BonsaiStorage::new(
+            RocksDB::new(&db, RocksDBConfig::default()),
+            BonsaiStorageConfig::default(),
+            24,
+        )
+        .unwrap();
+        for n in 0..20 {
+            bonsai_storage
+                .insert(&identifier, &key(n), &Felt::from(n + 1))
+                .unwrap();
+        }
+        bonsai_storage
+            .commit(BasicIdBuilder::new().new_id())
+            .unwrap();
+        bonsai_storage.root_hash(&identifier).unwrap()
+    };
+
+    let db = open_rocks_db(
+        tempdir.path(),
+        &RocksDBOpenOptions {
+            create_if_missing: false,
+            ..Default::default()
+        },
+    )
+    .unwrap();
+    let bonsai_storage: BonsaiStorage<BasicId, _, Pedersen> = BonsaiStorage::new(
+        RocksDB::new(&db, RocksDBConfig::default()),
+        BonsaiStorageConfig::default(),
+        24,
+    )
+    .unwrap();
+    assert_eq!(bonsai_storage.root_hash(&identifier).unwrap(), root_hash);
+    assert_eq!(
+        bonsai_storage.get(&identifier, &key(3)).unwrap(),
+        Some(Felt::from(4))
+    );
+    drop(bonsai_storage);
+    drop(db);
+
+    // Wiping is explicit.
+    let db = create_rocks_db(tempdir.path()).unwrap();
+    let bonsai_storage: BonsaiStorage<BasicId, _, Pedersen> = BonsaiStorage::new(
+        RocksDB::new(&db, RocksDBConfig::default()),
+        BonsaiStorageConfig::default(),
+        24,
+    )
+    .unwrap();
+    assert_eq!(bonsai_storage.get(&identifier, &key(3)).unwrap(), None);
+}
+
+#[test]
+fn missing_database_is_not_created() {
+    let tempdir = tempfile::tempdir().unwrap();
+    let result = open_rocks_db(
+        tempdir.path().join("db"),
+        &RocksDBOpenOptions {
+            create_if_missing: false,
+            ..Default::default()
+        },
+    );
+    assert!(matches!(result, Err(RocksDBError::RocksDB(_))));
+    assert!(!tempdir.path().join("db").exists());
+}
+
+#[test]
+fn missing_column_family_is_rejected() {
+    let tempdir = tempfile::tempdir().unwrap();
+    {
+        let mut opts = Options::default();
+        opts.create_if_missing(true);
+        opts.create_missing_column_families(true);
+        DB::open_cf(&opts, tempdir.path(), ["trie", "flat"]).unwrap();
+    }
+    assert!(matches!(
+        open_rocks_db(tempdir.path(), &RocksDBOpenOptions::default()),
+        Err(RocksDBError::MissingColumnFamily(cf)) if cf == "trie_log"
+    ));
+}
+
+#[test]
+fn storages_share_a_database() {
+    let identifier = vec![];
+    let tempdir = tempfile::tempdir().unwrap();
+    let contract = RocksDBConfig {
+        column_families: RocksDBColumnFamilies::with_namespace("contract"),
+        ..Default::default()
+    };
+    let class = RocksDBConfig {
+        column_families: RocksDBColumnFamilies::with_namespace("class"),
+        ..Default::default()
+    };
+    let options = RocksDBOpenOptions {
+        storages: vec![contract.clone(), class.clone()],
+        ..Default::default()
+    };
+
+    let (contract_root, class_root) = {
+        let db = open_rocks_db(tempdir.path(), &options).unwrap();
+        let mut contract_storage: BonsaiStorage<BasicId, _, Pedersen> = BonsaiStorage::new(
+            RocksDB::new(&db, contract.clone()),
+            BonsaiStorageConfig::default(),
+            24,
+        )
+        .unwrap();
+        let mut class_storage: BonsaiStorage<BasicId, _, Pedersen> = BonsaiStorage::new(
+            RocksDB::new(&db, class.clone()),
+            BonsaiStorageConfig::default(),
+            24,
+        )
+        .unwrap();
+        let mut id_builder = BasicIdBuilder::new();
+        let id1 = id_builder.new_id();
+        // The same keys with different values in both storages.
+        for n in 0..10 {
+            contract_storage
+                .insert(&identifier, &key(n), &Felt::from(n + 1))
+                .unwrap();
+            class_storage
+                .insert(&identifier, &key(n), &Felt::from(n + 100))
+                .unwrap();
+        }
+        contract_storage.commit(id1).unwrap();
+        class_storage.commit(id1).unwrap();
+
+        let id2 = id_builder.new_id();
+        class_storage.remove(&identifier, &key(0)).unwrap();
+        class_storage.commit(id2).unwrap();
+
+        // Each storage has its own trie log.
+        assert!(contract_storage.get_changes(id2).unwrap().is_empty());
+        assert_eq!(class_storage.get_changes(id2).unwrap().len(), 1);
+
+        (
+            contract_storage.root_hash(&identifier).unwrap(),
+            class_storage.root_hash(&identifier).unwrap(),
+        )
+    };
+    assert_ne!(contract_root, class_root);
+
+    let db = open_rocks_db(tempdir.path(), &options).unwrap();
+    let contract_storage: BonsaiStorage<BasicId, _, Pedersen> = BonsaiStorage::new(
+        RocksDB::new(&db, contract.clone()),
+        BonsaiStorageConfig::default(),
+        24,
+    )
+    .unwrap();
+    let class_storage: BonsaiStorage<BasicId, _, Pedersen> = BonsaiStorage::new(
+        RocksDB::new(&db, class.clone()),
+        BonsaiStorageConfig::default(),
+        24,
+    )
+    .unwrap();
+    assert_eq!(
+        contract_storage.root_hash(&identifier).unwrap(),
+        contract_root
+    );
+    assert_eq!(class_storage.root_hash(&identifier).unwrap(), class_root);
+    assert_eq!(
+        contract_storage.get(&identifier, &key(0)).unwrap(),
+        Some(Felt::ONE)
+    );
+    assert_eq!(class_storage.get(&identifier, &key(0)).unwrap(), None);
+}
+
+#[test]
+fn prefix_scans_with_tuned_options() {
+    let identifiers: [&[u8]; 4] = [&[], &[1], &[1, 2], &[2]];
+    let tempdir = tempfile::tempdir().unwrap();
+    let config = RocksDBConfig {
+        sync_writes: true,
+        ..Default::default()
+    };
+    let db = open_rocks_db(
+        tempdir.path(),
+        &RocksDBOpenOptions {
+            storages: vec![config.clone()],
+            block_cache_size: 1 << 20,
+            ..Default::default()
+        },
+    )
+    .unwrap();
+    let mut bonsai_storage: BonsaiStorage<BasicId, _, Pedersen> = BonsaiStorage::new(
+        RocksDB::new(&db, config),
+        BonsaiStorageConfig::default(),
+        24,
+    )
+    .unwrap();
+    let mut id_builder = BasicIdBuilder::new();
+
+    for (i, identifier) in identifiers.iter().enumerate() {
+        for n in 0..=i as u64 {
+            bonsai_storage
+                .insert(identifier, &key(n), &Felt::from(n + 1))
+                .unwrap();
+        }
+    }
+    let id1 = id_builder.new_id();
+    bonsai_storage.commit(id1).unwrap();
+    bonsai_storage.remove(&identifiers[2], &key(0)).unwrap();
+    let id2 = id_builder.new_id();
+    bonsai_storage.commit(id2).unwrap();
+
+    for (i, identifier) in identifiers.iter().enumerate() {
+        let expected = if i == 2 { i } else { i + 1 };
+        assert_eq!(bonsai_storage.get_keys(identifier).unwrap().len(), expected);
+    }
+    assert_eq!(bonsai_storage.get_changes(id2).unwrap().len(), 1);
+
+    bonsai_storage.revert_to(id1, id2).unwrap();
+    assert_eq!(bonsai_storage.get_keys(&identifiers[2]).unwrap().len(), 3);
+}
+
+#[test]
+fn snapshots_survive_reopen() {
+    let identifier = vec![];
+    let tempdir = tempfile::tempdir().unwrap();
+    let config = BonsaiStorageConfig {
+        snapshot_interval: 1,
+        max_saved_snapshots: Some(3),
+        ..Default::default()
+    };
+    let mut id_builder = BasicIdBuilder::new();
+    let ids: Vec<BasicId> = (0..5).map(|_| id_builder.new_id()).collect();
+
+    let root_hashes: Vec<Felt> = {
+        let db = open_rocks_db(tempdir.path(), &RocksDBOpenOptions::default()).unwrap();
+        let mut bonsai_storage: BonsaiStorage<BasicId, _, Pedersen> = BonsaiStorage::new(
+            RocksDB::new(&db, RocksDBConfig::default()),
+            config.clone(),
+            24,
+        )
+        .unwrap();
+        ids.iter()
+            .enumerate()
+            .map(|(n, id)| {
+                let n = n as u64;
+                bonsai_storage
+                    .insert(&identifier, &key(n), &Felt::from(n + 1))
+                    .unwrap();
+                if n > 0 {
+                    bonsai_storage.remove(&identifier, &key(n - 1)).unwrap();
+                }
+                bonsai_storage.commit(*id).unwrap();
+                bonsai_storage.root_hash(&identifier).unwrap()
+            })
+            .collect()
+    };
+
+    let db = open_rocks_db(tempdir.path(), &RocksDBOpenOptions::default()).unwrap();
+    let bonsai_storage: BonsaiStorage<BasicId, _, Pedersen> = BonsaiStorage::new(
+        RocksDB::new(&db, RocksDBConfig::default()),
+        config.clone(),
+        24,
+    )
+    .unwrap();
+    assert_eq!(bonsai_storage.get_latest_id().unwrap(), Some(ids[4]));
+
+    // Only the last three snapshots are kept.
+    assert!(bonsai_storage
+        .get_transactional_state(ids[1], config.clone())
+        .unwrap()
+        .is_none());
+    for (n, (id, root_hash)) in ids.iter().zip(&root_hashes).enumerate().skip(2) {
+        let n = n as u64;
+        let state = bonsai_storage
+            .get_transactional_state(*id, config.clone())
+            .unwrap()
+            .unwrap();
+        assert_eq!(state.root_hash(&identifier).unwrap(), *root_hash);
+        assert_eq!(
+            state.get(&identifier, &key(n)).unwrap(),
+            Some(Felt::from(n + 1))
+        );
+        assert_eq!(state.get(&identifier, &key(n - 1)).unwrap(), None);
+    }
+    // The head is untouched.
+    assert_eq!(
+        bonsai_storage.root_hash(&identifier).unwrap(),
+        root_hashes[4]
+    );
+}
+
+#[test]
+fn transactional_state_scans_read_its_snapshot() {
+    let identifier = vec![];
+    let tempdir = tempfile::tempdir().unwrap();
+    let db = create_rocks_db(tempdir.path()).unwrap();
+    let config = BonsaiStorageConfig {
+        snapshot_interval: 1,
+        ..Default::default()
+    };
+    let mut bonsai_storage: BonsaiStorage<BasicId, _, Pedersen> = BonsaiStorage::new(
+        RocksDB::new(&db, RocksDBConfig::default()),
+        config.clone(),
+        24,
+    )
+    .unwrap();
+    let mut id_builder = BasicIdBuilder::new();
+    for n in 0..3 {
+        bonsai_storage
+            .insert(&identifier, &key(n), &Felt::from(n + 1))
+            .unwrap();
+    }
+    let id = id_builder.new_id();
+    bonsai_storage.commit(id).unwrap();
+    let keys = bonsai_storage.get_keys(&identifier).unwrap();
+
+    for n in 3..10 {
+        bonsai_storage
+            .insert(&identifier, &key(n), &Felt::from(n + 1))
+            .unwrap();
+        bonsai_storage.commit(id_builder.new_id()).unwrap();
+    }
+
+    // The state is built from the snapshot of `id`, the later keys are not scanned.
+    let state = bonsai_storage
+        .get_transactional_state(id, config)
+        .unwrap()
+        .unwrap();
+    assert_eq!(state.get_keys(&identifier).unwrap(), keys);
+    assert_eq!(bonsai_storage.get_keys(&identifier).unwrap().len(), 10);
+}
+
+#[test]
+fn read_only_inspection() {
+    let identifiers: [&[u8]; 3] = [&[], &[1], &[0xff]];
+    let tempdir = tempfile::tempdir().unwrap();
+    let roots = {
+        let db = open_rocks_db(tempdir.path(), &RocksDBOpenOptions::default()).unwrap();
+        let mut bonsai_storage: BonsaiStorage<BasicId, _, Pedersen> = BonsaiStorage::new(
+            RocksDB::new(&db, RocksDBConfig::default()),
+            BonsaiStorageConfig {
+                max_saved_trie_logs: Some(2),
+                ..Default::default()
+            },
+            24,
+        )
+        .unwrap();
+        let mut id_builder = BasicIdBuilder::new();
+        for n in 0..4 {
+            for identifier in identifiers {
+                bonsai_storage
+                    .insert(identifier, &key(n), &Felt::from(n + 1))
+                    .unwrap();
+            }
+            bonsai_storage.commit(id_builder.new_id()).unwrap();
+        }
+        identifiers.map(|identifier| bonsai_storage.root_hash(identifier).unwrap())
+    };
+
+    // A writer keeps the database open while it is inspected.
+    let _writer = open_rocks_db(tempdir.path(), &RocksDBOpenOptions::default()).unwrap();
+    let db = open_rocks_db_read_only(tempdir.path(), &RocksDBOpenOptions::default()).unwrap();
+    let bonsai_storage: BonsaiStorage<BasicId, _, Pedersen> = BonsaiStorage::new(
+        RocksDBReadOnly::new(&db, RocksDBConfig::default()),
+        BonsaiStorageConfig::default(),
+        24,
+    )
+    .unwrap();
+    assert_eq!(
+        bonsai_storage.identifiers().unwrap(),
+        identifiers.map(<[u8]>::to_vec)
+    );
+    for (identifier, root) in identifiers.iter().zip(roots) {
+        assert_eq!(bonsai_storage.root_hash(identifier).unwrap(), root);
+    }
+    assert_eq!(
+        bonsai_storage.get(&[1], &key(2)).unwrap(),
+        Some(Felt::from(3))
+    );
+    // The trie logs of the first two commits were pruned.
+    let mut id_builder = BasicIdBuilder::new();
+    let ids: Vec<BasicId> = (0..4).map(|_| id_builder.new_id()).collect();
+    assert_eq!(bonsai_storage.trie_log_ids().unwrap(), ids[2..]);
+    assert_eq!(bonsai_storage.get_changes(ids[3]).unwrap().len(), 1);
+
+    let mut read_only = RocksDBReadOnly::new(&db, RocksDBConfig::default());
+    assert!(matches!(
+        read_only.insert(&DatabaseKey::Meta(b"key"), &[], None),
+        Err(RocksDBError::Custom(_))
+    ));
+}
+
+#[test]
+fn read_only_open_does_not_write() {
+    let tempdir = tempfile::tempdir().unwrap();
+    drop(open_rocks_db(tempdir.path(), &RocksDBOpenOptions::default()).unwrap());
+    let db = open_rocks_db_read_only(tempdir.path(), &RocksDBOpenOptions::default()).unwrap();
+    let bonsai_storage: BonsaiStorage<BasicId, _, Pedersen> = BonsaiStorage::new(
+        RocksDBReadOnly::new(&db, RocksDBConfig::default()),
+        BonsaiStorageConfig::default(),
+        24,
+    )
+    .unwrap();
+    assert_eq!(bonsai_storage.root_hash(&[]).unwrap(), Felt::ZERO);
+
+    // Written before the meta column family and the schema version record existed.
+    let legacy = tempfile::tempdir().unwrap();
+    {
+        let mut opts = Options::default();
+        opts.create_if_missing(true);
+        opts.create_missing_column_families(true);
+        let db = DB::open_cf(&opts, legacy.path(), ["trie", "flat", "trie_log"]).unwrap();
+        let flat = db.cf_handle("flat").unwrap();
+        db.put_cf(&flat, [1, 24, 0, 0, 1], [1]).unwrap();
+    }
+    let db = open_rocks_db_read_only(legacy.path(), &RocksDBOpenOptions::default()).unwrap();
+    assert!(matches!(
+        BonsaiStorage::<BasicId, _, Pedersen>::new(
+            RocksDBReadOnly::new(&db, RocksDBConfig::default()),
+            BonsaiStorageConfig::default(),
+            24,
+        ),
+        Err(BonsaiStorageError::MigrationRequired {
+            from: 0,
+            to: CURRENT_SCHEMA_VERSION
+        })
+    ));
+}
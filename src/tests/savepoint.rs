@@ -0,0 +1,192 @@
+#![cfg(feature = "std")]
+use super::common::{key, storage, Storage};
+use crate::{
+    id::{BasicId, BasicIdBuilder},
+    BonsaiStorageError,
+};
+use starknet_types_core::felt::Felt;
+
+fn values(bonsai_storage: &Storage, identifier: &[u8]) -> Vec<Option<Felt>> {
+    (0..4)
+        .map(|n| bonsai_storage.get(identifier, &key(n)).unwrap())
+        .collect()
+}
+
+#[test]
+fn rollback_undoes_changes_in_all_tries() {
+    let mut bonsai_storage = storage();
+    let mut id_builder = BasicIdBuilder::new();
+    bonsai_storage
+        .insert(&[1], &key(0), &Felt::from(1u64))
+        .unwrap();
+    bonsai_storage.commit(id_builder.new_id()).unwrap();
+    let committed_root = bonsai_storage.root_hash(&[1]).unwrap();
+
+    bonsai_storage
+        .insert(&[1], &key(1), &Felt::from(2u64))
+        .unwrap();
+    let savepoint = bonsai_storage.savepoint();
+
+    bonsai_storage.remove(&[1], &key(0)).unwrap();
+    bonsai_storage
+        .insert(&[1], &key(1), &Felt::from(3u64))
+        .unwrap();
+    bonsai_storage
+        .insert(&[2], &key(2), &Felt::from(4u64))
+        .unwrap();
+    let inner_savepoint = bonsai_storage.savepoint();
+    bonsai_storage
+        .insert(&[2], &key(3), &Felt::from(5u64))
+        .unwrap();
+
+    bonsai_storage.rollback_to(&inner_savepoint).unwrap();
+    assert_eq!(
+        values(&bonsai_storage, &[2]),
+        vec![None, None, Some(Felt::from(4u64)), None]
+    );
+
+    // A savepoint can be rolled back to again, and after a later savepoint.
+    for _ in 0..2 {
+        bonsai_storage.rollback_to(&savepoint).unwrap();
+        assert_eq!(
+            values(&bonsai_storage, &[1]),
+            vec![Some(Felt::from(1u64)), Some(Felt::from(2u64)), None, None]
+        );
+        assert_eq!(values(&bonsai_storage, &[2]), vec![None; 4]);
+        bonsai_storage.remove(&[1], &key(1)).unwrap();
+    }
+
+    bonsai_storage.rollback_to(&savepoint).unwrap();
+    bonsai_storage.commit(id_builder.new_id()).unwrap();
+    let mut expected = storage();
+    for n in 0..2 {
+        expected.insert(&[1], &key(n), &Felt::from(n + 1)).unwrap();
+    }
+    expected.commit(BasicId::new(0)).unwrap();
+    assert_eq!(
+        bonsai_storage.root_hash(&[1]).unwrap(),
+        expected.root_hash(&[1]).unwrap()
+    );
+    assert_ne!(bonsai_storage.root_hash(&[1]).unwrap(), committed_root);
+    assert_eq!(bonsai_storage.root_hash(&[2]).unwrap(), Felt::ZERO);
+    assert_eq!(
+        bonsai_storage.get(&[1], &key(1)).unwrap(),
+        Some(Felt::from(2u64))
+    );
+
+    // Savepoints do not survive a commit.
+    assert!(matches!(
+        bonsai_storage.rollback_to(&savepoint),
+        Err(BonsaiStorageError::StaleSavepoint)
+    ));
+}
+
+#[test]
+fn discard_pending_restores_last_commit() {
+    let mut bonsai_storage = storage();
+    let mut id_builder = BasicIdBuilder::new();
+    bonsai_storage
+        .insert(&[1], &key(0), &Felt::from(1u64))
+        .unwrap();
+    bonsai_storage.commit(id_builder.new_id()).unwrap();
+    let committed_root = bonsai_storage.root_hash(&[1]).unwrap();
+
+    bonsai_storage.remove(&[1], &key(0)).unwrap();
+    bonsai_storage
+        .insert(&[2], &key(1), &Felt::from(2u64))
+        .unwrap();
+    let savepoint = bonsai_storage.savepoint();
+    bonsai_storage.discard_pending().unwrap();
+
+    assert_eq!(
+        values(&bonsai_storage, &[1]),
+        vec![Some(Felt::from(1u64)), None, None, None]
+    );
+    assert_eq!(values(&bonsai_storage, &[2]), vec![None; 4]);
+    assert_eq!(bonsai_storage.root_hash(&[1]).unwrap(), committed_root);
+    assert!(bonsai_storage.rollback_to(&savepoint).is_err());
+
+    // The next commit only holds the changes made after the discard.
+    bonsai_storage
+        .insert(&[1], &key(2), &Felt::from(3u64))
+        .unwrap();
+    let id = id_builder.new_id();
+    bonsai_storage.commit(id).unwrap();
+    let changes = bonsai_storage.get_changes(id).unwrap();
+    assert_eq!(changes.len(), 1);
+    assert_eq!(changes[&key(2)].new_value, Some(Felt::from(3u64)));
+    assert_eq!(bonsai_storage.get_keys(&[2]).unwrap().len(), 0);
+}
+
+#[test]
+fn nested_savepoints_after_many_inserts() {
+    let mut bonsai_storage = storage();
+    let mut id_builder = BasicIdBuilder::new();
+    for n in 0..200 {
+        bonsai_storage
+            .insert(&[1], &key(n), &Felt::from(n + 1))
+            .unwrap();
+    }
+    bonsai_storage.commit(id_builder.new_id()).unwrap();
+    for n in 200..400 {
+        bonsai_storage
+            .insert(&[1], &key(n), &Felt::from(n + 1))
+            .unwrap();
+    }
+
+    // One savepoint per transaction, each overwriting, removing and adding a leaf.
+    let mut savepoints = Vec::new();
+    for n in 0..50 {
+        savepoints.push(bonsai_storage.savepoint());
+        bonsai_storage
+            .insert(&[1], &key(n), &Felt::from(1000 + n))
+            .unwrap();
+        bonsai_storage.remove(&[1], &key(200 + n)).unwrap();
+        bonsai_storage
+            .insert(&[2], &key(n), &Felt::from(n + 1))
+            .unwrap();
+    }
+    // The journal only grows with the keys set, not with the nodes loaded.
+    assert_eq!(bonsai_storage.tries.journal_len(), 200 + 50 * 3);
+
+    for n in [49u64, 48, 30, 12, 1] {
+        bonsai_storage.rollback_to(&savepoints[n as usize]).unwrap();
+        assert_eq!(
+            bonsai_storage.get(&[1], &key(n)).unwrap(),
+            Some(Felt::from(n + 1))
+        );
+        assert_eq!(
+            bonsai_storage.get(&[1], &key(200 + n)).unwrap(),
+            Some(Felt::from(201 + n))
+        );
+        assert_eq!(bonsai_storage.get(&[2], &key(n)).unwrap(), None);
+        assert_eq!(
+            bonsai_storage.get(&[1], &key(n - 1)).unwrap(),
+            Some(Felt::from(999 + n))
+        );
+    }
+    // The savepoints after the last one rolled back to were undone with their changes.
+    assert!(matches!(
+        bonsai_storage.rollback_to(&savepoints[49]),
+        Err(BonsaiStorageError::StaleSavepoint)
+    ));
+
+    // Rolled back to the savepoint of transaction 1: only transaction 0 is kept.
+    bonsai_storage.commit(id_builder.new_id()).unwrap();
+    let mut expected = storage();
+    for n in 0..400 {
+        expected.insert(&[1], &key(n), &Felt::from(n + 1)).unwrap();
+    }
+    expected
+        .insert(&[1], &key(0), &Felt::from(1000u64))
+        .unwrap();
+    expected.remove(&[1], &key(200)).unwrap();
+    expected.insert(&[2], &key(0), &Felt::from(1u64)).unwrap();
+    expected.commit(BasicId::new(0)).unwrap();
+    for identifier in [[1], [2]] {
+        assert_eq!(
+            bonsai_storage.root_hash(&identifier).unwrap(),
+            expected.root_hash(&identifier).unwrap()
+        );
+    }
+}
@@ -0,0 +1,130 @@
+#![cfg(feature = "std")]
+use super::common::key;
+use crate::{
+    databases::{BTreeMapDb, HashMapDb},
+    id::{BasicId, BasicIdBuilder},
+    BonsaiDatabase, BonsaiPersistentDatabase, BonsaiStorage, BonsaiStorageConfig,
+};
+use starknet_types_core::{felt::Felt, hash::Pedersen};
+
+/// Builds the state at every commit, either from a snapshot or from the head.
+fn states_at_every_commit<DB>(db: DB)
+where
+    DB: BonsaiDatabase
+        + BonsaiPersistentDatabase<BasicId, DatabaseError = <DB as BonsaiDatabase>::DatabaseError>,
+{
+    let identifier = vec![];
+    let config = BonsaiStorageConfig {
+        snapshot_interval: 5,
+        ..Default::default()
+    };
+    let mut bonsai_storage: BonsaiStorage<BasicId, _, Pedersen> =
+        BonsaiStorage::new(db, config.clone(), 24).unwrap();
+    let mut id_builder = BasicIdBuilder::new();
+    let mut commits = Vec::new();
+    for n in 0..13 {
+        bonsai_storage
+            .insert(&identifier, &key(n), &Felt::from(n + 1))
+            .unwrap();
+        if n % 3 == 0 && n > 0 {
+            bonsai_storage.remove(&identifier, &key(n - 2)).unwrap();
+        }
+        let id = id_builder.new_id();
+        bonsai_storage.commit(id).unwrap();
+        commits.push((id, bonsai_storage.root_hash(&identifier).unwrap()));
+    }
+
+    for (n, (id, root_hash)) in commits.into_iter().enumerate() {
+        let state = bonsai_storage
+            .get_transactional_state(id, config.clone())
+            .unwrap()
+            .unwrap();
+        assert_eq!(state.root_hash(&identifier).unwrap(), root_hash, "{n}");
+        assert_eq!(
+            state.get(&identifier, &key(n as u64)).unwrap(),
+            Some(Felt::from(n as u64 + 1))
+        );
+        assert_eq!(state.get(&identifier, &key(n as u64 + 1)).unwrap(), None);
+    }
+}
+
+#[test]
+fn hashmap_db_states_at_every_commit() {
+    states_at_every_commit(HashMapDb::<BasicId>::default());
+}
+
+#[test]
+fn btree_map_db_states_at_every_commit() {
+    states_at_every_commit(BTreeMapDb::<BasicId>::default());
+}
+
+#[test]
+fn nearest_snapshot_is_picked() {
+    let mut db = BTreeMapDb::<BasicId>::default();
+    for id in [0, 5, 10] {
+        db.snapshot(BasicId::new(id));
+    }
+    assert_eq!(db.nearest_snapshot(BasicId::new(7)), Some(BasicId::new(5)));
+    assert_eq!(
+        db.nearest_snapshot(BasicId::new(10)),
+        Some(BasicId::new(10))
+    );
+    assert_eq!(
+        db.nearest_snapshot(BasicId::new(12)),
+        Some(BasicId::new(10))
+    );
+    assert_eq!(
+        db.transaction(BasicId::new(9)).map(|(id, _)| id),
+        Some(BasicId::new(5))
+    );
+}
+
+#[test]
+fn reverted_commits_have_no_state() {
+    let identifier = vec![];
+    let config = BonsaiStorageConfig {
+        snapshot_interval: 1,
+        ..Default::default()
+    };
+    let mut bonsai_storage: BonsaiStorage<BasicId, _, Pedersen> =
+        BonsaiStorage::new(HashMapDb::default(), config.clone(), 24).unwrap();
+    let mut id_builder = BasicIdBuilder::new();
+    let mut ids = Vec::new();
+    for n in 0..4 {
+        bonsai_storage
+            .insert(&identifier, &key(n), &Felt::from(n + 1))
+            .unwrap();
+        let id = id_builder.new_id();
+        bonsai_storage.commit(id).unwrap();
+        ids.push(id);
+    }
+
+    bonsai_storage.revert_to(ids[1], ids[3]).unwrap();
+    assert_eq!(bonsai_storage.get_latest_id().unwrap(), Some(ids[1]));
+    for id in &ids[2..] {
+        assert!(bonsai_storage
+            .get_transactional_state(*id, config.clone())
+            .unwrap()
+            .is_none());
+    }
+
+    // Another commit 2 replaces the reverted one, 3 stays unknown.
+    bonsai_storage
+        .insert(&identifier, &key(10), &Felt::from(11u64))
+        .unwrap();
+    bonsai_storage.commit(ids[2]).unwrap();
+    assert_eq!(bonsai_storage.get_latest_id().unwrap(), Some(ids[2]));
+    assert!(bonsai_storage
+        .get_transactional_state(ids[3], config.clone())
+        .unwrap()
+        .is_none());
+    let state = bonsai_storage
+        .get_transactional_state(ids[2], config)
+        .unwrap()
+        .unwrap();
+    assert_eq!(state.get(&identifier, &key(2)).unwrap(), None);
+    assert_eq!(
+        state.get(&identifier, &key(10)).unwrap(),
+        Some(Felt::from(11u64))
+    );
+}
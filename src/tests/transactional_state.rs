@@ -2,7 +2,7 @@
 use crate::{
     databases::{create_rocks_db, RocksDB, RocksDBConfig},
     id::BasicIdBuilder,
-    BitVec, BonsaiStorage, BonsaiStorageConfig,
+    BitVec, BonsaiStorage, BonsaiStorageConfig, MergePolicy,
 };
 use log::LevelFilter;
 use starknet_types_core::{felt::Felt, hash::Pedersen};
@@ -195,7 +195,9 @@ fn merge() {
     bonsai_at_txn
         .transactional_commit(id_builder.new_id())
         .unwrap();
-    bonsai_storage.merge(bonsai_at_txn).unwrap();
+    bonsai_storage
+        .merge(bonsai_at_txn, MergePolicy::default())
+        .unwrap();
     assert_eq!(
         bonsai_storage
             .get(&identifier, &BitVec::from_vec(vec![1, 2, 3]))
@@ -237,7 +239,9 @@ fn merge_with_uncommitted_insert() {
         .insert(&identifier, &pair2.0, &pair2.1)
         .unwrap();
 
-    bonsai_storage.merge(bonsai_at_txn).unwrap();
+    bonsai_storage
+        .merge(bonsai_at_txn, MergePolicy::default())
+        .unwrap();
 
     // commit after merge
     let revert_id = id_builder.new_id();
@@ -255,7 +259,7 @@ fn merge_with_uncommitted_insert() {
     println!("{:?}", bonsai_storage);
 
     // revert to commit
-    bonsai_storage.revert_to(revert_id).unwrap();
+    bonsai_storage.revert_to(revert_id, revert_id).unwrap();
 
     assert_eq!(
         bonsai_storage.get(&identifier, &pair2.0).unwrap(),
@@ -306,7 +310,7 @@ fn merge_with_uncommitted_remove() {
     bonsai_at_txn.remove(&identifier, &pair2.0).unwrap();
     assert!(!bonsai_at_txn.contains(&identifier, &pair2.0).unwrap());
 
-    let merge = bonsai_storage.merge(bonsai_at_txn);
+    let merge = bonsai_storage.merge(bonsai_at_txn, MergePolicy::default());
     match merge {
         Ok(_) => println!("merge succeeded"),
         Err(e) => {
@@ -331,7 +335,7 @@ fn transactional_state_after_uncommitted() {
     let db = create_rocks_db(tempdir.path()).unwrap();
     let config = BonsaiStorageConfig::default();
     let mut bonsai_storage =
-        BonsaiStorage::new(RocksDB::new(&db, RocksDBConfig::default()), config, 24);
+        BonsaiStorage::new(RocksDB::new(&db, RocksDBConfig::default()), config, 24).unwrap();
     let mut id_builder = BasicIdBuilder::new();
 
     let pair1 = (
@@ -373,7 +377,7 @@ fn merge_override() {
     let db = create_rocks_db(tempdir.path()).unwrap();
     let config = BonsaiStorageConfig::default();
     let mut bonsai_storage =
-        BonsaiStorage::new(RocksDB::new(&db, RocksDBConfig::default()), config, 24);
+        BonsaiStorage::new(RocksDB::new(&db, RocksDBConfig::default()), config, 24).unwrap();
     let mut id_builder = BasicIdBuilder::new();
 
     let pair1 = (
@@ -400,7 +404,9 @@ fn merge_override() {
     bonsai_at_txn
         .transactional_commit(id_builder.new_id())
         .unwrap();
-    bonsai_storage.merge(bonsai_at_txn).unwrap();
+    bonsai_storage
+        .merge(bonsai_at_txn, MergePolicy::default())
+        .unwrap();
     assert_eq!(
         bonsai_storage
             .get(&identifier, &BitVec::from_vec(vec![1, 2, 2]))
@@ -416,7 +422,7 @@ fn merge_remove() {
     let db = create_rocks_db(tempdir.path()).unwrap();
     let config = BonsaiStorageConfig::default();
     let mut bonsai_storage =
-        BonsaiStorage::new(RocksDB::new(&db, RocksDBConfig::default()), config, 24);
+        BonsaiStorage::new(RocksDB::new(&db, RocksDBConfig::default()), config, 24).unwrap();
     let mut id_builder = BasicIdBuilder::new();
 
     let pair1 = (
@@ -439,7 +445,9 @@ fn merge_remove() {
     bonsai_at_txn
         .transactional_commit(id_builder.new_id())
         .unwrap();
-    bonsai_storage.merge(bonsai_at_txn).unwrap();
+    bonsai_storage
+        .merge(bonsai_at_txn, MergePolicy::default())
+        .unwrap();
     assert_eq!(
         bonsai_storage
             .get(&identifier, &BitVec::from_vec(pair1.0))
@@ -455,7 +463,7 @@ fn merge_txn_revert() {
     let db = create_rocks_db(tempdir.path()).unwrap();
     let config = BonsaiStorageConfig::default();
     let mut bonsai_storage =
-        BonsaiStorage::new(RocksDB::new(&db, RocksDBConfig::default()), config, 24);
+        BonsaiStorage::new(RocksDB::new(&db, RocksDBConfig::default()), config, 24).unwrap();
     let mut id_builder = BasicIdBuilder::new();
 
     let pair1 = (
@@ -491,15 +499,17 @@ fn merge_txn_revert() {
     let id3 = id_builder.new_id();
     bonsai_at_txn.transactional_commit(id3).unwrap();
 
-    bonsai_at_txn.revert_to(id2).unwrap();
+    bonsai_at_txn.revert_to(id2, id3).unwrap();
     let revert_hash2 = bonsai_at_txn.root_hash(&identifier).unwrap();
-    bonsai_at_txn.revert_to(id1).unwrap();
+    bonsai_at_txn.revert_to(id1, id2).unwrap();
     let revert_hash1 = bonsai_at_txn.root_hash(&identifier).unwrap();
 
     assert_eq!(root_hash2, revert_hash2);
     assert_eq!(root_hash1, revert_hash1);
 
-    bonsai_storage.merge(bonsai_at_txn).unwrap();
+    bonsai_storage
+        .merge(bonsai_at_txn, MergePolicy::default())
+        .unwrap();
     assert_eq!(
         bonsai_storage
             .get(&identifier, &BitVec::from_vec(pair1.0))
@@ -515,7 +525,7 @@ fn merge_invalid() {
     let db = create_rocks_db(tempdir.path()).unwrap();
     let config = BonsaiStorageConfig::default();
     let mut bonsai_storage =
-        BonsaiStorage::new(RocksDB::new(&db, RocksDBConfig::default()), config, 24);
+        BonsaiStorage::new(RocksDB::new(&db, RocksDBConfig::default()), config, 24).unwrap();
     let mut id_builder = BasicIdBuilder::new();
 
     let pair1 = (
@@ -549,7 +559,9 @@ fn merge_invalid() {
     let id3 = id_builder.new_id();
     bonsai_storage.commit(id3).unwrap();
 
-    bonsai_storage.merge(bonsai_at_txn).unwrap_err();
+    bonsai_storage
+        .merge(bonsai_at_txn, MergePolicy::default())
+        .unwrap_err();
 }
 
 #[test]
@@ -562,7 +574,7 @@ fn many_snapshots() {
         ..Default::default()
     };
     let mut bonsai_storage: BonsaiStorage<_, _, Pedersen> =
-        BonsaiStorage::new(RocksDB::new(&db, RocksDBConfig::default()), config, 24);
+        BonsaiStorage::new(RocksDB::new(&db, RocksDBConfig::default()), config, 24).unwrap();
     let mut id_builder = BasicIdBuilder::new();
 
     let pair1 = (
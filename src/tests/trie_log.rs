@@ -2,7 +2,7 @@
 use crate::{
     databases::{create_rocks_db, RocksDB, RocksDBConfig},
     id::BasicIdBuilder,
-    BitVec, BonsaiStorage, BonsaiStorageConfig, BonsaiTrieHash,
+    BitVec, BonsaiStorage, BonsaiStorageConfig, BonsaiStorageError, BonsaiTrieHash,
 };
 use starknet_types_core::{felt::Felt, hash::Pedersen};
 
@@ -13,7 +13,7 @@ fn basics() {
     let db = create_rocks_db(tempdir.path()).unwrap();
     let config = BonsaiStorageConfig::default();
     let mut bonsai_storage: BonsaiStorage<_, _, Pedersen> =
-        BonsaiStorage::new(RocksDB::new(&db, RocksDBConfig::default()), config, 24);
+        BonsaiStorage::new(RocksDB::new(&db, RocksDBConfig::default()), config, 24).unwrap();
     let mut id_builder = BasicIdBuilder::new();
 
     let pair1 = (
@@ -64,7 +64,7 @@ fn revert_across_value_change() {
     let db = create_rocks_db(tempdir.path()).unwrap();
     let config = BonsaiStorageConfig::default();
     let mut bonsai_storage: BonsaiStorage<_, _, Pedersen> =
-        BonsaiStorage::new(RocksDB::new(&db, RocksDBConfig::default()), config, 24);
+        BonsaiStorage::new(RocksDB::new(&db, RocksDBConfig::default()), config, 24).unwrap();
     let mut id_builder = BasicIdBuilder::new();
 
     let key = vec![1, 2, 1];
@@ -103,7 +103,7 @@ fn unrecorded_revert() {
     let db = create_rocks_db(tempdir.path()).unwrap();
     let config = BonsaiStorageConfig::default();
     let mut bonsai_storage: BonsaiStorage<_, _, Pedersen> =
-        BonsaiStorage::new(RocksDB::new(&db, RocksDBConfig::default()), config, 24);
+        BonsaiStorage::new(RocksDB::new(&db, RocksDBConfig::default()), config, 24).unwrap();
     let mut id_builder = BasicIdBuilder::new();
 
     let pair1 = (
@@ -118,7 +118,40 @@ fn unrecorded_revert() {
     bonsai_storage.commit(id1).unwrap();
 
     let uncommited_id = id_builder.new_id();
-    bonsai_storage.revert_to(uncommited_id, id1).unwrap_err();
+    assert!(matches!(
+        bonsai_storage.revert_to(uncommited_id, id1),
+        Err(BonsaiStorageError::RevertToFuture {
+            id: 1,
+            latest_id: 0
+        })
+    ));
+}
+
+#[test]
+fn revert_from_stale_id() {
+    let identifier = vec![];
+    let tempdir = tempfile::tempdir().unwrap();
+    let db = create_rocks_db(tempdir.path()).unwrap();
+    let config = BonsaiStorageConfig::default();
+    let mut bonsai_storage: BonsaiStorage<_, _, Pedersen> =
+        BonsaiStorage::new(RocksDB::new(&db, RocksDBConfig::default()), config, 24).unwrap();
+    let mut id_builder = BasicIdBuilder::new();
+
+    let id1 = id_builder.new_id();
+    bonsai_storage
+        .insert(&identifier, &BitVec::from_vec(vec![1, 2, 3]), &Felt::ONE)
+        .unwrap();
+    bonsai_storage.commit(id1).unwrap();
+    let id2 = id_builder.new_id();
+    bonsai_storage
+        .insert(&identifier, &BitVec::from_vec(vec![1, 2, 4]), &Felt::TWO)
+        .unwrap();
+    bonsai_storage.commit(id2).unwrap();
+
+    // id1 is not the latest commit anymore.
+    let err = bonsai_storage.revert_to(id1, id1).unwrap_err();
+    assert!(matches!(err, BonsaiStorageError::NotLatest { id: 0 }));
+    assert!(std::error::Error::source(&err).is_none());
 }
 
 #[test]
@@ -128,7 +161,7 @@ fn in_place_revert() {
     let db = create_rocks_db(tempdir.path()).unwrap();
     let config = BonsaiStorageConfig::default();
     let mut bonsai_storage: BonsaiStorage<_, _, Pedersen> =
-        BonsaiStorage::new(RocksDB::new(&db, RocksDBConfig::default()), config, 24);
+        BonsaiStorage::new(RocksDB::new(&db, RocksDBConfig::default()), config, 24).unwrap();
     let mut id_builder = BasicIdBuilder::new();
 
     let pair1 = (vec![1, 2, 3], &BonsaiTrieHash::default());
@@ -151,7 +184,7 @@ fn truncated_revert() {
     let db = create_rocks_db(tempdir.path()).unwrap();
     let config = BonsaiStorageConfig::default();
     let mut bonsai_storage: BonsaiStorage<_, _, Pedersen> =
-        BonsaiStorage::new(RocksDB::new(&db, RocksDBConfig::default()), config, 24);
+        BonsaiStorage::new(RocksDB::new(&db, RocksDBConfig::default()), config, 24).unwrap();
     let mut id_builder = BasicIdBuilder::new();
 
     let pair1 = (
@@ -191,7 +224,7 @@ fn double_revert() {
     let db = create_rocks_db(tempdir.path()).unwrap();
     let config = BonsaiStorageConfig::default();
     let mut bonsai_storage: BonsaiStorage<_, _, Pedersen> =
-        BonsaiStorage::new(RocksDB::new(&db, RocksDBConfig::default()), config, 24);
+        BonsaiStorage::new(RocksDB::new(&db, RocksDBConfig::default()), config, 24).unwrap();
     let mut id_builder = BasicIdBuilder::new();
 
     let pair1 = (
@@ -233,7 +266,7 @@ fn remove_and_reinsert() {
     let db = create_rocks_db(tempdir.path()).unwrap();
     let config = BonsaiStorageConfig::default();
     let mut bonsai_storage: BonsaiStorage<_, _, Pedersen> =
-        BonsaiStorage::new(RocksDB::new(&db, RocksDBConfig::default()), config, 24);
+        BonsaiStorage::new(RocksDB::new(&db, RocksDBConfig::default()), config, 24).unwrap();
     let mut id_builder = BasicIdBuilder::new();
 
     let pair1 = (
@@ -266,7 +299,7 @@ fn revert_old_new_id() {
     let db = create_rocks_db(tempdir.path()).unwrap();
     let config = BonsaiStorageConfig::default();
     let mut bonsai_storage: BonsaiStorage<_, _, Pedersen> =
-        BonsaiStorage::new(RocksDB::new(&db, RocksDBConfig::default()), config, 24);
+        BonsaiStorage::new(RocksDB::new(&db, RocksDBConfig::default()), config, 24).unwrap();
     let mut id_builder = BasicIdBuilder::new();
 
     let pair1 = (
@@ -0,0 +1,299 @@
+#![cfg(feature = "std")]
+use super::common::{key, storage, Storage};
+use crate::{
+    databases::HashMapDb,
+    id::{BasicId, BasicIdBuilder},
+    trie::{merkle_node::Node, trie_db::TrieKeyType, TrieKey},
+    trie_log_stream::{decode_trie_logs, encode_trie_logs},
+    BonsaiDatabase, BonsaiStorageConfig, BonsaiStorageError, DatabaseKey, TrieLogError,
+};
+use starknet_types_core::felt::Felt;
+
+fn sorted<T: Ord>(mut values: Vec<T>) -> Vec<T> {
+    values.sort();
+    values
+}
+
+/// Commits 0, 1 and 2 on a new storage, returning it with the roots of trie `[1]` after each.
+fn sequencer() -> (Storage, Vec<Felt>) {
+    let mut bonsai_storage = storage();
+    let mut id_builder = BasicIdBuilder::new();
+    let mut roots = Vec::new();
+    for n in 0..3u64 {
+        for m in 0..5 {
+            bonsai_storage
+                .insert(&[1], &key(n * 3 + m), &Felt::from(n * 10 + m + 1))
+                .unwrap();
+        }
+        bonsai_storage
+            .insert(&[2], &key(n), &Felt::from(n + 100))
+            .unwrap();
+        if n > 0 {
+            bonsai_storage.remove(&[1], &key(n)).unwrap();
+        }
+        bonsai_storage
+            .commit_with_metadata(id_builder.new_id(), &n.to_be_bytes())
+            .unwrap();
+        roots.push(bonsai_storage.root_hash(&[1]).unwrap());
+    }
+    (bonsai_storage, roots)
+}
+
+#[test]
+fn followers_replay_exported_commits() {
+    let (sequencer, roots) = sequencer();
+    let mut follower = storage();
+    follower
+        .apply_trie_log(
+            &sequencer
+                .export_trie_logs(BasicId::new(0)..=BasicId::new(0))
+                .unwrap(),
+        )
+        .unwrap();
+    assert_eq!(follower.root_hash(&[1]).unwrap(), roots[0]);
+    follower
+        .apply_trie_log(
+            &sequencer
+                .export_trie_logs(BasicId::new(1)..=BasicId::new(2))
+                .unwrap(),
+        )
+        .unwrap();
+
+    for identifier in [[1], [2]] {
+        assert_eq!(
+            follower.root_hash(&identifier).unwrap(),
+            sequencer.root_hash(&identifier).unwrap()
+        );
+        assert_eq!(
+            sorted(follower.get_key_value_pairs(&identifier).unwrap()),
+            sorted(sequencer.get_key_value_pairs(&identifier).unwrap())
+        );
+    }
+    assert_eq!(follower.get_latest_id().unwrap(), Some(BasicId::new(2)));
+    for id in 0..3 {
+        let id = BasicId::new(id);
+        assert_eq!(
+            follower.commit_info(id).unwrap(),
+            sequencer.commit_info(id).unwrap()
+        );
+        assert_eq!(
+            follower.get_changes(id).unwrap(),
+            sequencer.get_changes(id).unwrap()
+        );
+    }
+
+    // The trie logs were recorded, so the follower can revert.
+    follower
+        .revert_to(BasicId::new(0), BasicId::new(2))
+        .unwrap();
+    assert_eq!(follower.root_hash(&[1]).unwrap(), roots[0]);
+}
+
+#[test]
+fn followers_apply_commits_after_a_reorg() {
+    let (mut sequencer, _) = sequencer();
+    let mut follower = storage();
+    follower
+        .apply_trie_log(
+            &sequencer
+                .export_trie_logs(BasicId::new(0)..=BasicId::new(2))
+                .unwrap(),
+        )
+        .unwrap();
+
+    // Both sides drop commits 1 and 2, then the sequencer commits another 1.
+    for bonsai_storage in [&mut sequencer, &mut follower] {
+        bonsai_storage
+            .revert_to(BasicId::new(0), BasicId::new(2))
+            .unwrap();
+        assert_eq!(
+            bonsai_storage.get_latest_id().unwrap(),
+            Some(BasicId::new(0))
+        );
+    }
+    sequencer
+        .insert(&[1], &key(20), &Felt::from(21u64))
+        .unwrap();
+    sequencer.commit(BasicId::new(1)).unwrap();
+    follower
+        .apply_trie_log(
+            &sequencer
+                .export_trie_logs(BasicId::new(1)..=BasicId::new(1))
+                .unwrap(),
+        )
+        .unwrap();
+
+    assert_eq!(
+        follower.root_hash(&[1]).unwrap(),
+        sequencer.root_hash(&[1]).unwrap()
+    );
+    assert!(follower
+        .get_transactional_state(BasicId::new(2), follower.get_config())
+        .unwrap()
+        .is_none());
+}
+
+#[test]
+fn legacy_encoded_nodes_are_not_compared() {
+    let (sequencer, _) = sequencer();
+    let mut follower = storage();
+    follower
+        .apply_trie_log(
+            &sequencer
+                .export_trie_logs(BasicId::new(0)..=BasicId::new(2))
+                .unwrap(),
+        )
+        .unwrap();
+
+    // A leader with the same state, its trie nodes still in the legacy encoding.
+    let mut db = sequencer.tries.db_ref().db.clone();
+    for (node_key, value) in db.get_by_prefix(&DatabaseKey::Trie(&[])).unwrap() {
+        let legacy = Node::decode_at_height(&value, 0).unwrap().encode_legacy();
+        db.insert(&DatabaseKey::Trie(&node_key), &legacy, None)
+            .unwrap();
+    }
+    let mut leader = Storage::new(db, BonsaiStorageConfig::default(), 24).unwrap();
+    leader.insert(&[1], &key(20), &Felt::from(21u64)).unwrap();
+    leader.remove(&[1], &key(0)).unwrap();
+    leader.commit(BasicId::new(3)).unwrap();
+
+    // The old values of the trie nodes in the log are legacy encoded, the follower's compact.
+    let stream = leader
+        .export_trie_logs(BasicId::new(3)..=BasicId::new(3))
+        .unwrap();
+    follower.apply_trie_log(&stream).unwrap();
+    assert_eq!(
+        follower.root_hash(&[1]).unwrap(),
+        leader.root_hash(&[1]).unwrap()
+    );
+}
+
+#[test]
+fn invalid_trie_logs_are_rejected() {
+    let (sequencer, _) = sequencer();
+    let stream = sequencer
+        .export_trie_logs(BasicId::new(0)..=BasicId::new(1))
+        .unwrap();
+
+    let mut follower = storage();
+    assert!(matches!(
+        follower.apply_trie_log(&stream[1..]),
+        Err(BonsaiStorageError::TrieLog(TrieLogError::InvalidStream))
+    ));
+    // Commit 1 does not apply without commit 0.
+    let stream_1 = sequencer
+        .export_trie_logs(BasicId::new(1)..=BasicId::new(1))
+        .unwrap();
+    assert!(matches!(
+        follower.apply_trie_log(&stream_1),
+        Err(BonsaiStorageError::TrieLog(TrieLogError::StateMismatch {
+            id: 1,
+            ..
+        }))
+    ));
+    assert_eq!(follower.get_latest_id().unwrap(), None);
+
+    follower.apply_trie_log(&stream).unwrap();
+    assert!(matches!(
+        follower.apply_trie_log(&stream_1),
+        Err(BonsaiStorageError::TrieLog(TrieLogError::CommitOrder {
+            id: 1,
+            latest_id: 1
+        }))
+    ));
+}
+
+#[test]
+fn exports_skip_missing_ids() {
+    let mut sequencer = storage();
+    let ids = [0, 5, 9].map(BasicId::new);
+    for (n, id) in ids.into_iter().enumerate() {
+        sequencer
+            .insert(&[1], &key(n as u64), &Felt::from(n as u64 + 1))
+            .unwrap();
+        sequencer.commit(id).unwrap();
+    }
+
+    let mut follower = storage();
+    follower
+        .apply_trie_log(
+            &sequencer
+                .export_trie_logs(BasicId::new(0)..=BasicId::new(20))
+                .unwrap(),
+        )
+        .unwrap();
+    assert_eq!(follower.trie_log_ids().unwrap(), ids);
+    assert_eq!(
+        follower.root_hash(&[1]).unwrap(),
+        sequencer.root_hash(&[1]).unwrap()
+    );
+    assert_eq!(
+        sequencer
+            .export_trie_logs(BasicId::new(6)..=BasicId::new(9))
+            .unwrap(),
+        sequencer
+            .export_trie_logs(BasicId::new(9)..=BasicId::new(9))
+            .unwrap()
+    );
+}
+
+#[test]
+fn exports_fail_on_pruned_ids() {
+    let config = BonsaiStorageConfig {
+        max_saved_trie_logs: Some(1),
+        ..Default::default()
+    };
+    let mut sequencer = Storage::new(HashMapDb::default(), config, 24).unwrap();
+    for n in 0..3 {
+        sequencer.insert(&[1], &key(n), &Felt::from(n + 1)).unwrap();
+        sequencer.commit(BasicId::new(n)).unwrap();
+    }
+
+    assert!(matches!(
+        sequencer.export_trie_logs(BasicId::new(1)..=BasicId::new(2)),
+        Err(BonsaiStorageError::TrieLog(TrieLogError::Missing(1)))
+    ));
+    assert!(sequencer
+        .export_trie_logs(BasicId::new(2)..=BasicId::new(3))
+        .is_ok());
+}
+
+#[test]
+fn roots_are_recomputed_from_the_leaves() {
+    let (sequencer, roots) = sequencer();
+    let stream = sequencer
+        .export_trie_logs(BasicId::new(0)..=BasicId::new(0))
+        .unwrap();
+    let mut commits =
+        decode_trie_logs::<BasicId, <HashMapDb<BasicId> as BonsaiDatabase>::DatabaseError>(&stream)
+            .unwrap();
+
+    // The root node and the header agree on a root the leaves do not lead to.
+    let forged = Felt::from(42u64);
+    let (info, changes) = &mut commits[0];
+    let root_node = changes
+        .0
+        .get_mut(&TrieKey::new(&[1], TrieKeyType::Trie, &[0]))
+        .and_then(|change| change.new_value.as_mut())
+        .unwrap();
+    // The node hash follows the tag byte.
+    root_node[1..33].copy_from_slice(&forged.to_bytes_be());
+    info.roots
+        .iter_mut()
+        .find(|(identifier, _)| identifier[..] == [1])
+        .unwrap()
+        .1 = forged;
+
+    let mut follower = storage();
+    assert!(matches!(
+        follower.apply_trie_log(&encode_trie_logs(&commits)),
+        Err(BonsaiStorageError::TrieLog(TrieLogError::RootMismatch {
+            id: 0,
+            identifier,
+            expected,
+            got,
+        })) if identifier == [1] && expected == forged && got == roots[0]
+    ));
+    assert_eq!(follower.get_latest_id().unwrap(), None);
+    assert_eq!(follower.root_hash(&[1]).unwrap(), Felt::ZERO);
+}
@@ -1,21 +1,24 @@
 use super::{
-    merkle_node::{Direction, Node, NodeHandle},
+    merkle_node::{hash_binary_node, hash_edge_node, Direction, Node, NodeHandle},
     path::Path,
-    tree::{MerkleTree, NodeKey},
+    tree::{MerkleTree, NodeKey, RootHandle},
+    trie_db::TrieKeyType,
+    TrieKey,
 };
 use crate::{
-    hasher::BonsaiHasher, id::Id, key_value_db::KeyValueDB, BitSlice, BonsaiDatabase,
-    BonsaiStorageError, Vec,
+    hasher::BonsaiHasher, id::Id, key_value_db::KeyValueDB, vec, BitSlice, BonsaiDatabase,
+    BonsaiStorageError, ByteVec, HashMap, Vec,
 };
 use core::{fmt, marker::PhantomData};
+use slotmap::SlotMap;
 use starknet_types_core::felt::Felt;
 
 /// This trait's function will be called on every node visited during a seek operation.
 pub trait NodeVisitor<H: BonsaiHasher> {
     fn visit_node<DB: BonsaiDatabase>(
         &mut self,
-        tree: &mut MerkleTree<H>,
-        node_id: NodeKey,
+        nodes: &mut NodeArena<'_, H>,
+        node_id: WalkNodeKey,
         prev_height: usize,
     ) -> Result<(), BonsaiStorageError<DB::DatabaseError>>;
 }
@@ -24,22 +27,273 @@ pub struct NoopVisitor<H>(PhantomData<H>);
 impl<H: BonsaiHasher> NodeVisitor<H> for NoopVisitor<H> {
     fn visit_node<DB: BonsaiDatabase>(
         &mut self,
-        _tree: &mut MerkleTree<H>,
-        _node_id: NodeKey,
+        _nodes: &mut NodeArena<'_, H>,
+        _node_id: WalkNodeKey,
         _prev_height: usize,
     ) -> Result<(), BonsaiStorageError<DB::DatabaseError>> {
         Ok(())
     }
 }
 
+/// A node reached by a walk: either an in-memory node of the tree, or a node the walk loaded from
+/// the database into its own [`NodeArena`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum WalkNodeKey {
+    Tree(NodeKey),
+    Loaded(NodeKey),
+}
+
+/// The nodes seen by a read-only walk of a [`MerkleTree`].
+///
+/// Nodes that are not in memory in the tree are loaded into the arena instead of the tree, and
+/// hashes the tree has not computed yet are cached here. A walk thus only borrows the tree, several
+/// of them can run in parallel, and the tree is left untouched once they are dropped.
+pub struct NodeArena<'a, H: BonsaiHasher> {
+    tree: &'a MerkleTree<H>,
+    /// Nodes loaded from the database. Their children are always [`NodeHandle::Hash`], which means
+    /// an [`NodeHandle::InMemory`] handle always points to a node of the tree.
+    loaded: SlotMap<NodeKey, Node>,
+    /// Loaded nodes by the encoded path they are stored at.
+    paths: HashMap<ByteVec, NodeKey>,
+    /// Hashes computed by the walk.
+    hashes: HashMap<WalkNodeKey, Felt>,
+}
+
+impl<'a, H: BonsaiHasher> NodeArena<'a, H> {
+    pub fn new(tree: &'a MerkleTree<H>) -> Self {
+        Self {
+            tree,
+            loaded: SlotMap::default(),
+            paths: HashMap::new(),
+            hashes: HashMap::new(),
+        }
+    }
+
+    pub(crate) fn get_node<DB: BonsaiDatabase>(
+        &self,
+        node_id: WalkNodeKey,
+    ) -> Result<&Node, BonsaiStorageError<DB::DatabaseError>> {
+        match node_id {
+            WalkNodeKey::Tree(key) => self.tree.nodes.get(key),
+            WalkNodeKey::Loaded(key) => self.loaded.get(key),
+        }
+        .ok_or_else(|| BonsaiStorageError::DanglingNode {
+            identifier: self.tree.identifier.clone(),
+        })
+    }
+
+    /// The node behind `handle`, stored at `path`, if it is already in memory.
+    fn in_memory(&self, handle: NodeHandle, path: &Path) -> Option<WalkNodeKey> {
+        match handle {
+            NodeHandle::InMemory(key) => Some(WalkNodeKey::Tree(key)),
+            NodeHandle::Hash(_) => self
+                .paths
+                .get(&ByteVec::from(path))
+                .map(|key| WalkNodeKey::Loaded(*key)),
+        }
+    }
+
+    /// Loads the root node or returns None if the tree is empty.
+    pub(crate) fn load_root_node<DB: BonsaiDatabase, ID: Id>(
+        &mut self,
+        db: &KeyValueDB<DB, ID>,
+    ) -> Result<Option<WalkNodeKey>, BonsaiStorageError<DB::DatabaseError>> {
+        match self.tree.root_node {
+            Some(RootHandle::Loaded(key)) => Ok(Some(WalkNodeKey::Tree(key))),
+            Some(RootHandle::Empty) => Ok(None),
+            None => {
+                let path = Path::default();
+                match self.paths.get(&ByteVec::from(&path)) {
+                    Some(key) => Ok(Some(WalkNodeKey::Loaded(*key))),
+                    None => self.load_db_node(db, &path),
+                }
+            }
+        }
+    }
+
+    fn load_db_node<DB: BonsaiDatabase, ID: Id>(
+        &mut self,
+        db: &KeyValueDB<DB, ID>,
+        path: &Path,
+    ) -> Result<Option<WalkNodeKey>, BonsaiStorageError<DB::DatabaseError>> {
+        let path_bytes = ByteVec::from(path);
+        let key = TrieKey::new(&self.tree.identifier, TrieKeyType::Trie, &path_bytes);
+        if self.tree.death_row.contains(&key) {
+            return Ok(None);
+        }
+        let Some(node) = db.get(&key)? else {
+            return Ok(None);
+        };
+        let node = Node::decode_at_height(&node, path.len() as u64)?;
+        Ok(Some(self.insert_loaded(path_bytes, node)))
+    }
+
+    fn insert_loaded(&mut self, path: ByteVec, node: Node) -> WalkNodeKey {
+        let key = self.loaded.insert(node);
+        self.paths.insert(path, key);
+        WalkNodeKey::Loaded(key)
+    }
+
+    pub(crate) fn load_node_handle<DB: BonsaiDatabase, ID: Id>(
+        &mut self,
+        db: &KeyValueDB<DB, ID>,
+        handle: NodeHandle,
+        path: &Path,
+    ) -> Result<WalkNodeKey, BonsaiStorageError<DB::DatabaseError>> {
+        if let Some(node_id) = self.in_memory(handle, path) {
+            return Ok(node_id);
+        }
+        log::trace!("Visiting db node {:?}", path);
+        // Dangling node id in db
+        self.load_db_node(db, path)?
+            .ok_or_else(|| BonsaiStorageError::NodeNotFound {
+                identifier: self.tree.identifier.clone(),
+                path: path.clone(),
+            })
+    }
+
+    /// Get or compute the hash of the node behind a handle.
+    pub(crate) fn get_or_compute_node_hash<DB: BonsaiDatabase>(
+        &mut self,
+        handle: NodeHandle,
+    ) -> Result<Felt, BonsaiStorageError<DB::DatabaseError>> {
+        match handle {
+            NodeHandle::Hash(felt) => Ok(felt),
+            NodeHandle::InMemory(key) => self.node_hash::<DB>(WalkNodeKey::Tree(key)),
+        }
+    }
+
+    /// Get or compute the hash of a node.
+    pub(crate) fn node_hash<DB: BonsaiDatabase>(
+        &mut self,
+        node_id: WalkNodeKey,
+    ) -> Result<Felt, BonsaiStorageError<DB::DatabaseError>> {
+        if let Some(hash) = self.hashes.get(&node_id) {
+            return Ok(*hash);
+        }
+        let computed_hash = match self.get_node::<DB>(node_id)? {
+            Node::Binary(binary_node) => {
+                if let Some(hash) = binary_node.hash {
+                    return Ok(hash);
+                }
+                let (left, right) = (binary_node.left, binary_node.right);
+                let left_hash = self.get_or_compute_node_hash::<DB>(left)?;
+                let right_hash = self.get_or_compute_node_hash::<DB>(right)?;
+                hash_binary_node::<H>(left_hash, right_hash)
+            }
+            Node::Edge(edge_node) => {
+                if let Some(hash) = edge_node.hash {
+                    return Ok(hash);
+                }
+                let (path, child) = (edge_node.path.clone(), edge_node.child);
+                let child_hash = self.get_or_compute_node_hash::<DB>(child)?;
+                hash_edge_node::<H>(&path, child_hash)
+            }
+        };
+        self.hashes.insert(node_id, computed_hash);
+        Ok(computed_hash)
+    }
+
+    /// Loads every stored node on the paths to `keys` that is not in memory yet, one level of the
+    /// tree at a time with a single [`BonsaiDatabase::get_many`] call per level. This is the
+    /// read-only counterpart of [`MerkleTree::prefetch`].
+    pub(crate) fn prefetch<DB: BonsaiDatabase, ID: Id>(
+        &mut self,
+        db: &KeyValueDB<DB, ID>,
+        keys: &[&BitSlice],
+    ) -> Result<(), BonsaiStorageError<DB::DatabaseError>> {
+        let max_height = self.tree.max_height as usize;
+        let Some(root) = self.load_root_node(db)? else {
+            return Ok(());
+        };
+        let keys: Vec<&BitSlice> = keys
+            .iter()
+            .copied()
+            .filter(|key| key.len() == max_height)
+            .collect();
+
+        // Nodes of the current level, with their path and the keys going through them.
+        let mut level = vec![(root, Path::default(), keys)];
+        while !level.is_empty() {
+            let mut next_level = Vec::new();
+            // Children stored in the database, with their path and keys.
+            let mut to_load = Vec::new();
+            for (node_id, path, keys) in level {
+                let height = path.len();
+                // Children at the maximum height are leaves.
+                let children = match self.get_node::<DB>(node_id)? {
+                    Node::Binary(binary) if height + 1 < max_height => {
+                        [Direction::Left, Direction::Right]
+                            .into_iter()
+                            .map(|direction| {
+                                let mut child_path = path.clone();
+                                child_path.push(bool::from(direction));
+                                let child_keys: Vec<&BitSlice> = keys
+                                    .iter()
+                                    .copied()
+                                    .filter(|key| Direction::from(key[height]) == direction)
+                                    .collect();
+                                (binary.get_child(direction), child_path, child_keys)
+                            })
+                            .collect()
+                    }
+                    Node::Edge(edge) if height + edge.path.len() < max_height => {
+                        let mut child_path = path.clone();
+                        child_path.extend_from_bitslice(&edge.path);
+                        let child_keys: Vec<&BitSlice> = keys
+                            .iter()
+                            .copied()
+                            .filter(|key| edge.path_matches(key, height))
+                            .collect();
+                        vec![(edge.child, child_path, child_keys)]
+                    }
+                    _ => Vec::new(),
+                };
+                for (handle, child_path, child_keys) in children {
+                    if child_keys.is_empty() {
+                        continue;
+                    }
+                    match self.in_memory(handle, &child_path) {
+                        Some(child) => next_level.push((child, child_path, child_keys)),
+                        None => to_load.push((child_path, child_keys)),
+                    }
+                }
+            }
+
+            let paths: Vec<ByteVec> = to_load.iter().map(|(path, _)| path.into()).collect();
+            let db_keys: Vec<TrieKey> = paths
+                .iter()
+                .map(|path| TrieKey::new(&self.tree.identifier, TrieKeyType::Trie, path))
+                .collect();
+            let values = db.get_many(&db_keys)?;
+            for (((path, keys), path_bytes), (db_key, value)) in to_load
+                .into_iter()
+                .zip(paths)
+                .zip(db_keys.iter().zip(values))
+            {
+                // Missing nodes are left for the traversal to report.
+                if self.tree.death_row.contains(db_key) {
+                    continue;
+                }
+                let Some(value) = value else { continue };
+                let node = Node::decode_at_height(&value, path.len() as u64)?;
+                next_level.push((self.insert_loaded(path_bytes, node), path, keys));
+            }
+            level = next_level;
+        }
+        Ok(())
+    }
+}
+
+/// A read-only walk of a [`MerkleTree`], loading the nodes it needs into its own [`NodeArena`].
 pub struct MerkleTreeIterator<'a, H: BonsaiHasher, DB: BonsaiDatabase, ID: Id> {
-    pub(crate) tree: &'a mut MerkleTree<H>,
+    pub(crate) nodes: NodeArena<'a, H>,
     pub(crate) db: &'a KeyValueDB<DB, ID>,
     /// Current iteration path.
     pub(crate) current_path: Path,
     /// The loaded nodes in the current path with their corresponding heights. Height is at the base of the node, meaning
     /// the first node here will always have height 0.
-    pub(crate) current_nodes_heights: Vec<(NodeKey, usize)>,
+    pub(crate) current_nodes_heights: Vec<(WalkNodeKey, usize)>,
     /// Current leaf hash. Note that partial traversal (traversal that stops midway through the tree) will
     /// also update this field if an exact match for the key is found, even though we may not have reached a leaf.
     pub(crate) leaf_hash: Option<Felt>,
@@ -60,9 +314,9 @@ impl<'a, H: BonsaiHasher, DB: BonsaiDatabase, ID: Id> fmt::Debug
 impl<'a, H: BonsaiHasher + Send + Sync, DB: BonsaiDatabase, ID: Id>
     MerkleTreeIterator<'a, H, DB, ID>
 {
-    pub fn new(tree: &'a mut MerkleTree<H>, db: &'a KeyValueDB<DB, ID>) -> Self {
+    pub fn new(tree: &'a MerkleTree<H>, db: &'a KeyValueDB<DB, ID>) -> Self {
         Self {
-            tree,
+            nodes: NodeArena::new(tree),
             db,
             current_path: Default::default(),
             current_nodes_heights: Vec::with_capacity(251),
@@ -76,7 +330,10 @@ impl<'a, H: BonsaiHasher + Send + Sync, DB: BonsaiDatabase, ID: Id>
         use slotmap::Key;
         self.current_nodes_heights
             .iter()
-            .map(|n| n.0.data().as_ffi() & !(1 << 32))
+            .map(|(node_id, _)| {
+                let (WalkNodeKey::Tree(key) | WalkNodeKey::Loaded(key)) = node_id;
+                key.data().as_ffi() & !(1 << 32)
+            })
             .collect::<Vec<_>>()
     }
 
@@ -86,14 +343,14 @@ impl<'a, H: BonsaiHasher + Send + Sync, DB: BonsaiDatabase, ID: Id>
 
     fn traverse_one(
         &mut self,
-        node_id: NodeKey,
+        node_id: WalkNodeKey,
         height: usize,
         key: &BitSlice,
-    ) -> Result<Option<NodeKey>, BonsaiStorageError<DB::DatabaseError>> {
+    ) -> Result<Option<WalkNodeKey>, BonsaiStorageError<DB::DatabaseError>> {
         self.current_nodes_heights
             .push((node_id, self.current_path.len()));
 
-        let node = self.tree.get_node_mut::<DB>(node_id)?;
+        let node = self.nodes.get_node::<DB>(node_id)?;
         let (node_handle, path_matches) = match node {
             Node::Binary(binary_node) => {
                 log::trace!(
@@ -126,25 +383,11 @@ impl<'a, H: BonsaiHasher + Send + Sync, DB: BonsaiDatabase, ID: Id>
             return Ok(None); // end of traversal
         }
 
+        // The parent keeps its handle: the arena finds the child again by its path.
         let child_key = self
-            .tree
+            .nodes
             .load_node_handle(self.db, node_handle, &self.current_path)?;
 
-        // update parent ref
-        match self.tree.get_node_mut::<DB>(node_id)? {
-            Node::Binary(binary_node) => {
-                *binary_node.get_child_mut(Direction::from(
-                    *self
-                        .current_path
-                        .last()
-                        .expect("current path should have a length > 0 at this point"),
-                )) = NodeHandle::InMemory(child_key);
-            }
-            Node::Edge(edge_node) => {
-                edge_node.child = NodeHandle::InMemory(child_key);
-            }
-        };
-
         Ok(Some(child_key))
     }
 
@@ -196,7 +439,7 @@ impl<'a, H: BonsaiHasher + Send + Sync, DB: BonsaiDatabase, ID: Id>
         } else {
             // Start from tree root.
             self.current_path.clear();
-            let Some(node_id) = self.tree.load_root_node(self.db)? else {
+            let Some(node_id) = self.nodes.load_root_node(self.db)? else {
                 // empty tree, not found
                 self.leaf_hash = None;
                 return Ok(());
@@ -219,7 +462,7 @@ impl<'a, H: BonsaiHasher + Send + Sync, DB: BonsaiDatabase, ID: Id>
                 return Ok(());
             };
 
-            visitor.visit_node::<DB>(self.tree, node_id, self.current_path.len())?;
+            visitor.visit_node::<DB>(&mut self.nodes, node_id, self.current_path.len())?;
             next_to_visit = self.traverse_one(node_id, self.current_path.len(), key)?;
 
             log::trace!(
@@ -287,7 +530,8 @@ mod tests {
             RocksDB::<BasicId>::new(&db, RocksDBConfig::default()),
             BonsaiStorageConfig::default(),
             8,
-        );
+        )
+        .unwrap();
 
         bonsai_storage
             .insert(&[], bits![u8, Msb0; 0,0,0,1,0,0,0,0], &ONE)
@@ -309,7 +553,7 @@ mod tests {
         let tree = bonsai_storage
             .tries
             .trees
-            .get_mut(&smallvec::smallvec![])
+            .get(&smallvec::smallvec![])
             .unwrap();
         let mut iter = MerkleTreeIterator::new(tree, &bonsai_storage.tries.db);
 
@@ -5,16 +5,22 @@
 //! [`MerkleTree`](super::merkle_tree::MerkleTree).
 
 use crate::hasher::BonsaiHasher;
-use crate::BitSlice;
+use crate::{BitSlice, ByteVec};
 use bitvec::view::BitView;
 use core::fmt;
-use parity_scale_codec::{Decode, Encode};
+use parity_scale_codec::{Decode, Encode, Error, Input};
 use starknet_types_core::felt::Felt;
 
-use super::{path::Path, tree::NodeKey};
+use super::path::Path;
+#[cfg(feature = "storage")]
+use super::tree::NodeKey;
 
 /// A node in a Binary Merkle-Patricia Tree graph.
-#[derive(Clone, Debug, PartialEq, Eq, PartialOrd, Ord, Hash, Encode, Decode)]
+///
+/// Committed nodes are stored with a hand-written compact encoding. Their height is not part of
+/// it as it is the length of the path the node is stored at.
+#[cfg(feature = "storage")]
+#[derive(Clone, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
 pub enum Node {
     /// A branch node with exactly two children.
     Binary(BinaryNode),
@@ -22,11 +28,13 @@ pub enum Node {
     Edge(EdgeNode),
 }
 
-#[derive(Copy, Clone, PartialEq, Eq, PartialOrd, Ord, Hash, Encode, Decode)]
+#[cfg(feature = "storage")]
+#[derive(Copy, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
 pub enum NodeHandle {
     Hash(Felt),
     InMemory(NodeKey),
 }
+#[cfg(feature = "storage")]
 impl NodeHandle {
     pub fn as_hash(self) -> Option<Felt> {
         match self {
@@ -36,6 +44,7 @@ impl NodeHandle {
     }
 }
 
+#[cfg(feature = "storage")]
 impl fmt::Debug for NodeHandle {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         match self {
@@ -46,13 +55,14 @@ impl fmt::Debug for NodeHandle {
 }
 
 /// Describes the [Node::Binary] variant.
-#[derive(Clone, Debug, PartialEq, Eq, PartialOrd, Ord, Hash, Encode, Decode)]
+#[cfg(feature = "storage")]
+#[derive(Clone, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
 pub struct BinaryNode {
     /// The hash of this node. Is [None] if the node
     /// has not yet been committed.
     pub hash: Option<Felt>,
     /// The height of this node in the tree.
-    // TODO: this field will be removed in the future.
+    /// Not stored in the database, it is derived from the path of the node.
     pub height: u64,
     /// [Left](Direction::Left) child.
     pub left: NodeHandle,
@@ -61,13 +71,14 @@ pub struct BinaryNode {
 }
 
 /// Node that is an edge.
-#[derive(Clone, Debug, PartialEq, Eq, PartialOrd, Ord, Hash, Encode, Decode)]
+#[cfg(feature = "storage")]
+#[derive(Clone, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
 pub struct EdgeNode {
     /// The hash of this node. Is [None] if the node
     /// has not yet been committed.
     pub hash: Option<Felt>,
     /// The starting height of this node in the tree.
-    // TODO: this field will be removed in the future.
+    /// Not stored in the database, it is derived from the path of the node.
     pub height: u64,
     /// The path this edge takes.
     pub path: Path,
@@ -119,6 +130,7 @@ impl From<Direction> for bool {
     }
 }
 
+#[cfg(feature = "storage")]
 impl BinaryNode {
     /// Maps the key's bit at the binary node's height to a [Direction].
     ///
@@ -176,7 +188,110 @@ impl BinaryNode {
     }
 }
 
+// Variant indices of the SCALE-derived encoding used before schema version 2.
+const LEGACY_BINARY_NODE: u8 = 0x00;
+const LEGACY_EDGE_NODE: u8 = 0x01;
+const LEGACY_HASH_HANDLE: u8 = 0x00;
+
+const BINARY_NODE: u8 = 0x02;
+const EDGE_NODE: u8 = 0x03;
+
+#[cfg(feature = "storage")]
 impl Node {
+    /// Encodes a committed binary node as `tag ++ hash ++ left hash ++ right hash`.
+    pub(crate) fn encode_binary(hash: Felt, left: Felt, right: Felt) -> ByteVec {
+        let mut bytes = ByteVec::with_capacity(1 + 3 * 32);
+        bytes.push(BINARY_NODE);
+        bytes.extend_from_slice(&hash.to_bytes_be());
+        bytes.extend_from_slice(&left.to_bytes_be());
+        bytes.extend_from_slice(&right.to_bytes_be());
+        bytes
+    }
+
+    /// Encodes a committed edge node as `tag ++ hash ++ child hash ++ path`.
+    pub(crate) fn encode_edge(hash: Felt, child: Felt, path: &Path) -> ByteVec {
+        let mut bytes = ByteVec::with_capacity(1 + 2 * 32 + path.size_hint());
+        bytes.push(EDGE_NODE);
+        bytes.extend_from_slice(&hash.to_bytes_be());
+        bytes.extend_from_slice(&child.to_bytes_be());
+        bytes.extend_from_slice(&ByteVec::from(path));
+        bytes
+    }
+
+    /// Encodes a committed node in the legacy SCALE encoding, to write databases of older versions.
+    #[cfg(test)]
+    pub(crate) fn encode_legacy(&self) -> ByteVec {
+        fn push_handle(bytes: &mut ByteVec, handle: &NodeHandle) {
+            let NodeHandle::Hash(hash) = handle else {
+                panic!("in-memory handles are not stored");
+            };
+            bytes.push(LEGACY_HASH_HANDLE);
+            bytes.extend_from_slice(&hash.to_bytes_be());
+        }
+
+        let (tag, hash, height) = match self {
+            Node::Binary(binary) => (LEGACY_BINARY_NODE, binary.hash, binary.height),
+            Node::Edge(edge) => (LEGACY_EDGE_NODE, edge.hash, edge.height),
+        };
+        let mut bytes = ByteVec::from_slice(&[tag]);
+        bytes.extend_from_slice(&hash.encode());
+        bytes.extend_from_slice(&height.to_le_bytes());
+        match self {
+            Node::Binary(binary) => {
+                push_handle(&mut bytes, &binary.left);
+                push_handle(&mut bytes, &binary.right);
+            }
+            Node::Edge(edge) => {
+                bytes.extend_from_slice(&ByteVec::from(&edge.path));
+                push_handle(&mut bytes, &edge.child);
+            }
+        }
+        bytes
+    }
+
+    /// Decodes a node stored at a path of length `height`.
+    ///
+    /// Both the compact encoding and the legacy SCALE encoding, which still carries the height,
+    /// are accepted.
+    pub(crate) fn decode_at_height(mut input: &[u8], height: u64) -> Result<Self, Error> {
+        let input = &mut input;
+        match input.read_byte()? {
+            BINARY_NODE => Ok(Node::Binary(BinaryNode {
+                hash: Some(Felt::decode(input)?),
+                height,
+                left: NodeHandle::Hash(Felt::decode(input)?),
+                right: NodeHandle::Hash(Felt::decode(input)?),
+            })),
+            EDGE_NODE => Ok(Node::Edge(EdgeNode {
+                hash: Some(Felt::decode(input)?),
+                height,
+                child: NodeHandle::Hash(Felt::decode(input)?),
+                path: Path::decode(input)?,
+            })),
+            LEGACY_BINARY_NODE => {
+                let hash = Option::<Felt>::decode(input)?;
+                let _height = u64::decode(input)?;
+                Ok(Node::Binary(BinaryNode {
+                    hash,
+                    height,
+                    left: decode_legacy_handle(input)?,
+                    right: decode_legacy_handle(input)?,
+                }))
+            }
+            LEGACY_EDGE_NODE => {
+                let hash = Option::<Felt>::decode(input)?;
+                let _height = u64::decode(input)?;
+                Ok(Node::Edge(EdgeNode {
+                    hash,
+                    height,
+                    path: Path::decode(input)?,
+                    child: decode_legacy_handle(input)?,
+                }))
+            }
+            _ => Err("Unknown node tag".into()),
+        }
+    }
+
     /// Convert to node to binary node type (returns None if it's not a binary node).
     pub fn as_binary(&self) -> Option<&BinaryNode> {
         match self {
@@ -193,6 +308,7 @@ impl Node {
     }
 }
 
+#[cfg(feature = "storage")]
 impl EdgeNode {
     /// Returns true if the edge node's path matches the same path given by the key.
     ///
@@ -232,6 +348,15 @@ impl EdgeNode {
     }
 }
 
+#[cfg(feature = "storage")]
+fn decode_legacy_handle<I: Input>(input: &mut I) -> Result<NodeHandle, Error> {
+    match input.read_byte()? {
+        LEGACY_HASH_HANDLE => Ok(NodeHandle::Hash(Felt::decode(input)?)),
+        // In-memory handles were never meant to reach the database.
+        _ => Err("Invalid node handle in stored node".into()),
+    }
+}
+
 pub fn hash_binary_node<H: BonsaiHasher>(left_hash: Felt, right_hash: Felt) -> Felt {
     H::hash(&left_hash, &right_hash)
 }
@@ -377,3 +502,78 @@ fn test_no_common_path() {
     let key = BitSlice::from_slice(&[0b01010101, 0b10101010]);
     assert_eq!(edge.common_path(key), BitSlice::empty());
 }
+
+#[test]
+fn test_compact_node_roundtrip() {
+    let binary = Node::encode_binary(Felt::ONE, Felt::TWO, Felt::THREE);
+    assert_eq!(binary.len(), 97);
+    assert_eq!(
+        Node::decode_at_height(&binary, 12).unwrap(),
+        Node::Binary(BinaryNode {
+            hash: Some(Felt::ONE),
+            height: 12,
+            left: NodeHandle::Hash(Felt::TWO),
+            right: NodeHandle::Hash(Felt::THREE),
+        })
+    );
+
+    let path = Path(BitSlice::from_slice(&[0b10101010, 0b01010101])[..13].to_bitvec());
+    let edge = Node::encode_edge(Felt::ONE, Felt::TWO, &path);
+    assert_eq!(edge.len(), 1 + 64 + 3);
+    assert_eq!(
+        Node::decode_at_height(&edge, 4).unwrap(),
+        Node::Edge(EdgeNode {
+            hash: Some(Felt::ONE),
+            height: 4,
+            path,
+            child: NodeHandle::Hash(Felt::TWO),
+        })
+    );
+}
+
+#[test]
+fn test_decode_legacy_node() {
+    let mut binary = vec![LEGACY_BINARY_NODE, 1];
+    binary.extend_from_slice(&Felt::ONE.to_bytes_be());
+    binary.extend_from_slice(&12u64.to_le_bytes());
+    binary.push(LEGACY_HASH_HANDLE);
+    binary.extend_from_slice(&Felt::TWO.to_bytes_be());
+    binary.push(LEGACY_HASH_HANDLE);
+    binary.extend_from_slice(&Felt::THREE.to_bytes_be());
+    assert_eq!(
+        Node::decode_at_height(&binary, 12).unwrap(),
+        Node::Binary(BinaryNode {
+            hash: Some(Felt::ONE),
+            height: 12,
+            left: NodeHandle::Hash(Felt::TWO),
+            right: NodeHandle::Hash(Felt::THREE),
+        })
+    );
+
+    let path = Path(BitSlice::from_slice(&[0b10100000])[..3].to_bitvec());
+    let mut edge = vec![LEGACY_EDGE_NODE, 0];
+    edge.extend_from_slice(&4u64.to_le_bytes());
+    edge.extend_from_slice(&[3, 0b10100000]);
+    edge.push(LEGACY_HASH_HANDLE);
+    edge.extend_from_slice(&Felt::TWO.to_bytes_be());
+    assert_eq!(
+        Node::decode_at_height(&edge, 4).unwrap(),
+        Node::Edge(EdgeNode {
+            hash: None,
+            height: 4,
+            path,
+            child: NodeHandle::Hash(Felt::TWO),
+        })
+    );
+
+    for (bytes, height) in [(binary, 12), (edge, 4)] {
+        let node = Node::decode_at_height(&bytes, height).unwrap();
+        assert_eq!(&node.encode_legacy()[..], &bytes[..]);
+    }
+
+    // Legacy in-memory handles cannot be decoded.
+    let mut in_memory = vec![LEGACY_EDGE_NODE, 0];
+    in_memory.extend_from_slice(&4u64.to_le_bytes());
+    in_memory.extend_from_slice(&[3, 0b10100000, 1]);
+    assert!(Node::decode_at_height(&in_memory, 4).is_err());
+}
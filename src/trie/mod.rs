@@ -1,9 +1,14 @@
+#[cfg(feature = "storage")]
 pub(crate) mod iterator;
-mod merkle_node;
+pub(crate) mod merkle_node;
 pub(crate) mod path;
 pub(crate) mod proof;
+#[cfg(feature = "storage")]
 pub mod tree;
+#[cfg(feature = "storage")]
 pub(crate) mod trees;
+#[cfg(feature = "storage")]
 pub(crate) mod trie_db;
 
+#[cfg(feature = "storage")]
 pub(crate) use trie_db::TrieKey;
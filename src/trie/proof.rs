@@ -1,22 +1,26 @@
 use super::{
     merkle_node::{hash_binary_node, hash_edge_node, Direction},
     path::Path,
-    tree::MerkleTree,
 };
+use crate::{hasher::BonsaiHasher, BitSlice, BitVec, HashMap, HashSet};
+use core::mem;
+use hashbrown::hash_set;
+use starknet_types_core::felt::Felt;
+
+#[cfg(feature = "storage")]
+use super::tree::MerkleTree;
+#[cfg(feature = "storage")]
 use crate::{
-    hasher::BonsaiHasher,
     id::Id,
     key_value_db::KeyValueDB,
     trie::{
-        iterator::NodeVisitor,
-        merkle_node::{Node, NodeHandle},
-        tree::NodeKey,
+        iterator::{NodeArena, NodeVisitor, WalkNodeKey},
+        merkle_node::Node,
     },
-    BitSlice, BitVec, BonsaiDatabase, BonsaiStorageError, HashMap, HashSet,
+    BonsaiDatabase, BonsaiStorageError, Vec,
 };
-use core::{marker::PhantomData, mem};
-use hashbrown::hash_set;
-use starknet_types_core::felt::Felt;
+#[cfg(feature = "storage")]
+use core::marker::PhantomData;
 
 #[derive(Debug, thiserror::Error)]
 pub enum ProofVerificationError {
@@ -158,11 +162,12 @@ impl MultiProof {
     }
 }
 
+#[cfg(feature = "storage")]
 impl<H: BonsaiHasher + Send + Sync> MerkleTree<H> {
     /// This function is designed to be very efficient if the `keys` are sorted - this allows for
     /// the minimal amount of backtracking when switching from one key to the next.
     pub fn get_multi_proof<DB: BonsaiDatabase, ID: Id>(
-        &mut self,
+        &self,
         db: &KeyValueDB<DB, ID>,
         keys: impl IntoIterator<Item = impl AsRef<BitSlice>>,
     ) -> Result<MultiProof, BonsaiStorageError<DB::DatabaseError>> {
@@ -172,42 +177,45 @@ impl<H: BonsaiHasher + Send + Sync> MerkleTree<H> {
         impl<H: BonsaiHasher + Send + Sync> NodeVisitor<H> for ProofVisitor<H> {
             fn visit_node<DB: BonsaiDatabase>(
                 &mut self,
-                tree: &mut MerkleTree<H>,
-                node_id: NodeKey,
+                nodes: &mut NodeArena<'_, H>,
+                node_id: WalkNodeKey,
                 _prev_height: usize,
             ) -> Result<(), BonsaiStorageError<DB::DatabaseError>> {
-                let proof_node = match tree.get_node_mut::<DB>(node_id)? {
+                let proof_node = match nodes.get_node::<DB>(node_id)? {
                     Node::Binary(binary_node) => {
                         let (left, right) = (binary_node.left, binary_node.right);
                         ProofNode::Binary {
-                            left: tree.get_or_compute_node_hash::<DB>(left)?,
-                            right: tree.get_or_compute_node_hash::<DB>(right)?,
+                            left: nodes.get_or_compute_node_hash::<DB>(left)?,
+                            right: nodes.get_or_compute_node_hash::<DB>(right)?,
                         }
                     }
                     Node::Edge(edge_node) => {
                         let (child, path) = (edge_node.child, edge_node.path.clone());
                         ProofNode::Edge {
-                            child: tree.get_or_compute_node_hash::<DB>(child)?,
+                            child: nodes.get_or_compute_node_hash::<DB>(child)?,
                             path,
                         }
                     }
                 };
-                let hash = tree.get_or_compute_node_hash::<DB>(NodeHandle::InMemory(node_id))?;
+                let hash = nodes.node_hash::<DB>(node_id)?;
                 self.0 .0.insert(hash, proof_node);
                 Ok(())
             }
         }
         let mut visitor = ProofVisitor::<H>(MultiProof(Default::default()), PhantomData);
 
+        let keys: Vec<_> = keys.into_iter().collect();
+        let keys: Vec<&BitSlice> = keys.iter().map(AsRef::as_ref).collect();
+        if let Some(key) = keys.iter().find(|key| key.len() != max_height as usize) {
+            return Err(BonsaiStorageError::KeyLength {
+                expected: self.max_height as _,
+                got: key.len(),
+            });
+        }
+
         let mut iter = self.iter(db);
+        iter.nodes.prefetch(db, &keys)?;
         for key in keys {
-            let key = key.as_ref();
-            if key.len() != max_height as usize {
-                return Err(BonsaiStorageError::KeyLength {
-                    expected: self.max_height as _,
-                    got: key.len(),
-                });
-            }
             log::debug!("go to = {key:b}");
             iter.traverse_to(&mut visitor, key)?;
 
@@ -246,7 +254,8 @@ mod tests {
             RocksDB::<BasicId>::new(&db, RocksDBConfig::default()),
             BonsaiStorageConfig::default(),
             8,
-        );
+        )
+        .unwrap();
 
         let key_values = [
             (bits![u8, Msb0; 0,0,0,1,0,0,0,0], ONE),
@@ -268,7 +277,7 @@ mod tests {
         let tree = bonsai_storage
             .tries
             .trees
-            .get_mut(&smallvec::smallvec![])
+            .get(&smallvec::smallvec![])
             .unwrap();
 
         let proof = tree
@@ -1,15 +1,15 @@
 use core::{fmt, marker::PhantomData};
 use core::{iter, mem};
-use parity_scale_codec::Decode;
 use slotmap::SlotMap;
 use starknet_types_core::felt::Felt;
 
 use crate::hasher::BonsaiHasher;
+use crate::key_value_db::decode_felt;
 use crate::trie::merkle_node::{edge_hash_inputs, hash_binary_node, hash_edge_node};
 use crate::BitVec;
 use crate::{
-    error::BonsaiStorageError, format, hash_map, id::Id, vec, BitSlice, BonsaiDatabase, ByteVec,
-    EncodeExt, HashMap, HashSet, KeyValueDB, ToString, Vec,
+    error::BonsaiStorageError, hash_map, id::Id, vec, BitSlice, BonsaiDatabase, ByteVec, EncodeExt,
+    HashMap, HashSet, KeyValueDB, Vec,
 };
 
 use super::iterator::MerkleTreeIterator;
@@ -28,21 +28,6 @@ slotmap::new_key_type! {
     pub struct NodeKey;
 }
 
-// TODO: implement encode and decode by hand in Node
-// these cases should never happen, otherwise that would mean we are saving in-memory node keys to the db, which would be very bad.
-impl parity_scale_codec::Encode for NodeKey {
-    fn using_encoded<R, F: FnOnce(&[u8]) -> R>(&self, _f: F) -> R {
-        unreachable!("Cannot encode NodeKey")
-    }
-}
-impl parity_scale_codec::Decode for NodeKey {
-    fn decode<I: parity_scale_codec::Input>(
-        _input: &mut I,
-    ) -> Result<Self, parity_scale_codec::Error> {
-        unreachable!("Cannot decode NodeKey")
-    }
-}
-
 #[derive(Debug, Clone, Copy)]
 pub(crate) enum RootHandle {
     Empty,
@@ -85,7 +70,6 @@ impl<H: BonsaiHasher> fmt::Debug for MerkleTree<H> {
 }
 
 // NB: #[derive(Clone)] does not work because it expands to an impl block which forces H: Clone, which Pedersen/Poseidon aren't.
-#[cfg(feature = "bench")]
 impl<H: BonsaiHasher> Clone for MerkleTree<H> {
     fn clone(&self) -> Self {
         Self {
@@ -134,8 +118,11 @@ impl<H: BonsaiHasher + Send + Sync> MerkleTree<H> {
             Some(RootHandle::Empty) => Ok(None),
             None => {
                 // load the node
-                let id = self
-                    .load_db_node(db, &TrieKey::new(&self.identifier, TrieKeyType::Trie, &[0]))?;
+                let id = self.load_db_node(
+                    db,
+                    &TrieKey::new(&self.identifier, TrieKeyType::Trie, &[0]),
+                    0,
+                )?;
 
                 match id {
                     Some(id) => {
@@ -152,10 +139,12 @@ impl<H: BonsaiHasher + Send + Sync> MerkleTree<H> {
     }
 
     /// First step of two phase init.
+    /// `height` is the length of the path the node is stored at.
     pub(crate) fn load_db_node<DB: BonsaiDatabase, ID: Id>(
         &mut self,
         db: &KeyValueDB<DB, ID>,
         key: &TrieKey,
+        height: u64,
     ) -> Result<Option<NodeKey>, BonsaiStorageError<DB::DatabaseError>> {
         if self.death_row.contains(key) {
             return Ok(None);
@@ -163,89 +152,136 @@ impl<H: BonsaiHasher + Send + Sync> MerkleTree<H> {
         let node = db.get(key)?;
         let Some(node) = node else { return Ok(None) };
 
-        let node = Node::decode(&mut node.as_slice())?;
+        let node = Node::decode_at_height(&node, height)?;
         let key = self.nodes.insert(node);
 
         Ok(Some(key))
     }
 
-    pub(crate) fn get_node_mut<DB: BonsaiDatabase>(
-        &mut self,
-        node_key: NodeKey,
-    ) -> Result<&mut Node, BonsaiStorageError<DB::DatabaseError>> {
-        self.nodes.get_mut(node_key).ok_or_else(|| {
-            BonsaiStorageError::Trie(format!("Dangling in-memory node key: {node_key:?}"))
-        })
-    }
-
-    pub(crate) fn load_node_handle<DB: BonsaiDatabase, ID: Id>(
+    /// Loads every stored node on the paths to `keys` that is not in memory yet, one level of the
+    /// tree at a time with a single [`BonsaiDatabase::get_many`] call per level.
+    ///
+    /// Traversals of these keys, such as the ones done by [`MerkleTree::set`], then no longer go
+    /// to the database. Keys of the wrong length are ignored.
+    pub(crate) fn prefetch<DB: BonsaiDatabase, ID: Id>(
         &mut self,
         db: &KeyValueDB<DB, ID>,
-        handle: NodeHandle,
-        path: &Path,
-    ) -> Result<NodeKey, BonsaiStorageError<DB::DatabaseError>> {
-        match handle {
-            NodeHandle::Hash(_) => {
-                // TODO(perf): useless allocs everywhere here...
-                let path: ByteVec = path.clone().into();
-                log::trace!("Visiting db node {:?}", path);
-                let key = TrieKey::new(&self.identifier, TrieKeyType::Trie, &path);
-                let Some(node_key) = self.load_db_node(db, &key)? else {
-                    // Dangling node id in db
-                    return Err(BonsaiStorageError::Trie(
-                        "Could not get node from db".to_string(),
-                    ));
-                };
-                Ok(node_key)
-            }
-            NodeHandle::InMemory(node_key) => Ok(node_key),
-        }
-    }
-
-    /// Get or compute the hash of a node.
-    pub(crate) fn get_or_compute_node_hash<DB: BonsaiDatabase>(
-        &mut self,
-        node: NodeHandle,
-    ) -> Result<Felt, BonsaiStorageError<DB::DatabaseError>> {
-        match node {
-            NodeHandle::Hash(felt) => Ok(felt),
-            NodeHandle::InMemory(node_key) => {
-                let computed_hash = match self.get_node_mut::<DB>(node_key)? {
-                    Node::Binary(binary_node) => {
-                        if let Some(hash) = binary_node.hash {
-                            return Ok(hash);
+        keys: &[&BitSlice],
+    ) -> Result<(), BonsaiStorageError<DB::DatabaseError>> {
+        let max_height = self.max_height as usize;
+        let Some(root) = self.load_root_node(db)? else {
+            return Ok(());
+        };
+        let keys: Vec<&BitSlice> = keys
+            .iter()
+            .copied()
+            .filter(|key| key.len() == max_height)
+            .collect();
+
+        // Nodes of the current level, with their path and the keys going through them.
+        let mut level = vec![(root, Path::default(), keys)];
+        while !level.is_empty() {
+            let mut next_level = Vec::new();
+            // Children stored in the database: parent, direction from a binary parent, path and keys.
+            let mut to_load = Vec::new();
+            for (node_id, path, keys) in level {
+                let height = path.len();
+                match self.get_node_mut::<DB>(node_id)? {
+                    Node::Binary(binary) => {
+                        for direction in [Direction::Left, Direction::Right] {
+                            let child_keys: Vec<&BitSlice> = keys
+                                .iter()
+                                .copied()
+                                .filter(|key| Direction::from(key[height]) == direction)
+                                .collect();
+                            // Children at the maximum height are leaves.
+                            if child_keys.is_empty() || height + 1 >= max_height {
+                                continue;
+                            }
+                            let mut child_path = path.clone();
+                            child_path.push(bool::from(direction));
+                            match binary.get_child(direction) {
+                                NodeHandle::InMemory(child) => {
+                                    next_level.push((child, child_path, child_keys))
+                                }
+                                NodeHandle::Hash(_) => {
+                                    to_load.push((node_id, Some(direction), child_path, child_keys))
+                                }
+                            }
                         }
-                        let (left, right) = (binary_node.left, binary_node.right);
-                        let left_hash = self.get_or_compute_node_hash::<DB>(left)?;
-                        let right_hash = self.get_or_compute_node_hash::<DB>(right)?;
-                        hash_binary_node::<H>(left_hash, right_hash)
                     }
-                    Node::Edge(edge_node) => {
-                        if let Some(hash) = edge_node.hash {
-                            return Ok(hash);
+                    Node::Edge(edge) => {
+                        if height + edge.path.len() >= max_height {
+                            continue;
+                        }
+                        let child_keys: Vec<&BitSlice> = keys
+                            .into_iter()
+                            .filter(|key| edge.path_matches(key, height))
+                            .collect();
+                        if child_keys.is_empty() {
+                            continue;
+                        }
+                        let mut child_path = path;
+                        child_path.extend_from_bitslice(&edge.path);
+                        match edge.child {
+                            NodeHandle::InMemory(child) => {
+                                next_level.push((child, child_path, child_keys))
+                            }
+                            NodeHandle::Hash(_) => {
+                                to_load.push((node_id, None, child_path, child_keys))
+                            }
                         }
-                        let (path, child) = (edge_node.path.clone(), edge_node.child);
-                        // edge_node borrow ends here
-                        let child_hash = self.get_or_compute_node_hash::<DB>(child)?;
-                        hash_edge_node::<H>(&path, child_hash)
                     }
-                };
-
-                // reborrow, for lifetime reasons (can't go into children if a borrow is alive)
-                match self.get_node_mut::<DB>(node_key)? {
-                    Node::Binary(binary_node) => binary_node.hash = Some(computed_hash),
-                    Node::Edge(edge_node) => edge_node.hash = Some(computed_hash),
                 }
+            }
 
-                Ok(computed_hash)
+            let db_keys: Vec<TrieKey> = to_load
+                .iter()
+                .map(|(_, _, path, _)| {
+                    TrieKey::new(&self.identifier, TrieKeyType::Trie, &ByteVec::from(path))
+                })
+                .collect();
+            let values = db.get_many(&db_keys)?;
+            for ((parent, direction, path, keys), (db_key, value)) in
+                to_load.into_iter().zip(db_keys.iter().zip(values))
+            {
+                // Missing nodes are left for the traversal to report.
+                if self.death_row.contains(db_key) {
+                    continue;
+                }
+                let Some(value) = value else { continue };
+                let child = self
+                    .nodes
+                    .insert(Node::decode_at_height(&value, path.len() as u64)?);
+                match (self.get_node_mut::<DB>(parent)?, direction) {
+                    (Node::Binary(binary), Some(direction)) => {
+                        *binary.get_child_mut(direction) = NodeHandle::InMemory(child)
+                    }
+                    (Node::Edge(edge), None) => edge.child = NodeHandle::InMemory(child),
+                    _ => unreachable!("the parent of a prefetched node does not change"),
+                }
+                next_level.push((child, path, keys));
             }
+            level = next_level;
         }
+        Ok(())
     }
 
-    /// Note: as iterators load nodes from the database, this takes an &mut self. However,
-    /// note that it will not modify anything in the database - hence the &db.
+    pub(crate) fn get_node_mut<DB: BonsaiDatabase>(
+        &mut self,
+        node_key: NodeKey,
+    ) -> Result<&mut Node, BonsaiStorageError<DB::DatabaseError>> {
+        self.nodes
+            .get_mut(node_key)
+            .ok_or_else(|| BonsaiStorageError::DanglingNode {
+                identifier: self.identifier.clone(),
+            })
+    }
+
+    /// Note: iterators load the nodes they need from the database into their own arena, so that
+    /// several of them can walk the tree at once and the tree is left untouched.
     pub fn iter<'a, DB: BonsaiDatabase, ID: Id>(
-        &'a mut self,
+        &'a self,
         db: &'a KeyValueDB<DB, ID>,
     ) -> MerkleTreeIterator<'a, H, DB, ID> {
         MerkleTreeIterator::new(self, db)
@@ -261,12 +297,16 @@ impl<H: BonsaiHasher + Send + Sync> MerkleTree<H> {
         match self.root_node {
             Some(RootHandle::Empty) => Ok(Felt::ZERO),
             Some(RootHandle::Loaded(node_id)) => {
-                let node = self.nodes.get(node_id).ok_or_else(|| {
-                    BonsaiStorageError::Trie("Could not fetch root node from storage".into())
-                })?;
-                node.get_hash().ok_or_else(|| {
-                    BonsaiStorageError::Trie("The tree has uncommited changes".into())
-                })
+                let node =
+                    self.nodes
+                        .get(node_id)
+                        .ok_or_else(|| BonsaiStorageError::DanglingNode {
+                            identifier: self.identifier.clone(),
+                        })?;
+                node.get_hash()
+                    .ok_or_else(|| BonsaiStorageError::UncommittedChanges {
+                        identifier: self.identifier.clone(),
+                    })
             }
             None => {
                 let Some(node) = Self::get_trie_branch_in_db_from_path(
@@ -278,13 +318,28 @@ impl<H: BonsaiHasher + Send + Sync> MerkleTree<H> {
                 else {
                     return Ok(Felt::ZERO);
                 };
-                Ok(node
-                    .get_hash()
-                    .expect("The fetched node has no computed hash"))
+                node.get_hash().ok_or_else(|| {
+                    BonsaiStorageError::MissingNodeHash(
+                        TrieKey::new(&self.identifier, TrieKeyType::Trie, &[0])
+                            .as_slice()
+                            .to_vec(),
+                    )
+                })
             }
         }
     }
 
+    /// Same as [`MerkleTree::root_hash`], hashing the uncommitted changes instead of failing.
+    pub(crate) fn uncommitted_root_hash<DB: BonsaiDatabase, ID: Id>(
+        &self,
+        db: &KeyValueDB<DB, ID>,
+    ) -> Result<Felt, BonsaiStorageError<DB::DatabaseError>> {
+        match self.root_node {
+            Some(RootHandle::Loaded(_)) => self.compute_root_hash::<DB>(&mut Vec::new()),
+            _ => self.root_hash(db),
+        }
+    }
+
     pub fn cache_leaf_modified(&self) -> &HashMap<ByteVec, InsertOrRemove<Felt>> {
         &self.cache_leaf_modified
     }
@@ -376,9 +431,12 @@ impl<H: BonsaiHasher + Send + Sync> MerkleTree<H> {
             NodeHandle::Hash(hash) => return Ok(NodeOrFelt::Felt(*hash)),
             NodeHandle::InMemory(node_id) => *node_id,
         };
-        let node = self.nodes.get(node_id).ok_or(BonsaiStorageError::Trie(
-            "Couldn't fetch node in the temporary storage".to_string(),
-        ))?;
+        let node = self
+            .nodes
+            .get(node_id)
+            .ok_or_else(|| BonsaiStorageError::DanglingNode {
+                identifier: self.identifier.clone(),
+            })?;
         Ok(NodeOrFelt::Node(node))
     }
 
@@ -388,9 +446,12 @@ impl<H: BonsaiHasher + Send + Sync> MerkleTree<H> {
         order: &mut Vec<NodeKey>,
         heights: &mut HashMap<NodeKey, usize>,
     ) -> Result<usize, BonsaiStorageError<DB::DatabaseError>> {
-        let node = self.nodes.get(node_id).ok_or(BonsaiStorageError::Trie(
-            "Couldn't fetch node in the temporary storage".to_string(),
-        ))?;
+        let node = self
+            .nodes
+            .get(node_id)
+            .ok_or_else(|| BonsaiStorageError::DanglingNode {
+                identifier: self.identifier.clone(),
+            })?;
 
         let height = match node {
             Node::Binary(binary) => {
@@ -426,15 +487,16 @@ impl<H: BonsaiHasher + Send + Sync> MerkleTree<H> {
         root_id: NodeKey,
         hashes: &mut Vec<Felt>,
     ) -> Result<Felt, BonsaiStorageError<DB::DatabaseError>> {
+        let unhashed = || BonsaiStorageError::UnhashedNode {
+            identifier: self.identifier.clone(),
+        };
         let mut order: Vec<NodeKey> = Vec::new();
         let mut heights: HashMap<NodeKey, usize> = HashMap::new();
         let max_height = self.collect_hash_order::<DB>(root_id, &mut order, &mut heights)?;
 
         let mut levels: Vec<Vec<NodeKey>> = vec![Vec::new(); max_height + 1];
         for node_id in &order {
-            let height = *heights.get(node_id).ok_or(BonsaiStorageError::Trie(
-                "Missing node height during hash computation".to_string(),
-            ))?;
+            let height = *heights.get(node_id).ok_or_else(unhashed)?;
             levels[height].push(*node_id);
         }
 
@@ -449,26 +511,25 @@ impl<H: BonsaiHasher + Send + Sync> MerkleTree<H> {
             let mut edge_add: Vec<Option<Felt>> = Vec::with_capacity(nodes.len());
 
             for node_id in nodes {
-                let node = self.nodes.get(*node_id).ok_or(BonsaiStorageError::Trie(
-                    "Couldn't fetch node in the temporary storage".to_string(),
-                ))?;
+                let node =
+                    self.nodes
+                        .get(*node_id)
+                        .ok_or_else(|| BonsaiStorageError::DanglingNode {
+                            identifier: self.identifier.clone(),
+                        })?;
 
                 match node {
                     Node::Binary(binary) => {
                         let left_hash = match binary.left {
                             NodeHandle::Hash(felt) => felt,
                             NodeHandle::InMemory(child_id) => {
-                                *hash_cache.get(&child_id).ok_or(BonsaiStorageError::Trie(
-                                    "Missing left child hash during batch computation".to_string(),
-                                ))?
+                                *hash_cache.get(&child_id).ok_or_else(unhashed)?
                             }
                         };
                         let right_hash = match binary.right {
                             NodeHandle::Hash(felt) => felt,
                             NodeHandle::InMemory(child_id) => {
-                                *hash_cache.get(&child_id).ok_or(BonsaiStorageError::Trie(
-                                    "Missing right child hash during batch computation".to_string(),
-                                ))?
+                                *hash_cache.get(&child_id).ok_or_else(unhashed)?
                             }
                         };
                         pairs.push((left_hash, right_hash));
@@ -478,9 +539,7 @@ impl<H: BonsaiHasher + Send + Sync> MerkleTree<H> {
                         let child_hash = match edge.child {
                             NodeHandle::Hash(felt) => felt,
                             NodeHandle::InMemory(child_id) => {
-                                *hash_cache.get(&child_id).ok_or(BonsaiStorageError::Trie(
-                                    "Missing edge child hash during batch computation".to_string(),
-                                ))?
+                                *hash_cache.get(&child_id).ok_or_else(unhashed)?
                             }
                         };
                         let (felt_path, length) = edge_hash_inputs(&edge.path);
@@ -492,9 +551,10 @@ impl<H: BonsaiHasher + Send + Sync> MerkleTree<H> {
 
             let results = H::hash_pairs(&pairs);
             if results.len() != nodes.len() {
-                return Err(BonsaiStorageError::Trie(
-                    "Hasher returned incorrect number of results".to_string(),
-                ));
+                return Err(BonsaiStorageError::HashCount {
+                    expected: nodes.len(),
+                    got: results.len(),
+                });
             }
 
             for (idx, node_id) in nodes.iter().enumerate() {
@@ -509,18 +569,11 @@ impl<H: BonsaiHasher + Send + Sync> MerkleTree<H> {
         hashes.clear();
         hashes.reserve(order.len());
         for node_id in &order {
-            let hash = *hash_cache.get(node_id).ok_or(BonsaiStorageError::Trie(
-                "Missing computed hash during output ordering".to_string(),
-            ))?;
+            let hash = *hash_cache.get(node_id).ok_or_else(unhashed)?;
             hashes.push(hash);
         }
 
-        hash_cache
-            .get(&root_id)
-            .copied()
-            .ok_or(BonsaiStorageError::Trie(
-                "Missing root hash after batch computation".to_string(),
-            ))
+        hash_cache.get(&root_id).copied().ok_or_else(unhashed)
     }
 
     fn compute_root_hash<DB: BonsaiDatabase>(
@@ -531,18 +584,18 @@ impl<H: BonsaiHasher + Send + Sync> MerkleTree<H> {
             Some(RootHandle::Loaded(node_id)) => *node_id,
             Some(RootHandle::Empty) => return Ok(Felt::ZERO),
             None => {
-                return Err(BonsaiStorageError::Trie(
-                    "Root node is not loaded".to_string(),
-                ))
+                return Err(BonsaiStorageError::RootNotLoaded {
+                    identifier: self.identifier.clone(),
+                })
             }
         };
         if H::prefers_batched() {
             return self.compute_hashes_batched::<DB>(handle, hashes);
         }
         let Some(node) = self.nodes.get(handle) else {
-            return Err(BonsaiStorageError::Trie(
-                "Could not fetch root node from storage".to_string(),
-            ));
+            return Err(BonsaiStorageError::DanglingNode {
+                identifier: self.identifier.clone(),
+            });
         };
         self.compute_hashes_sequential::<DB>(node, Path::default(), hashes)
     }
@@ -654,10 +707,13 @@ impl<H: BonsaiHasher + Send + Sync> MerkleTree<H> {
         path: Path,
         hashes: &mut impl Iterator<Item = Felt>,
     ) -> Result<Felt, BonsaiStorageError<DB::DatabaseError>> {
-        match self.nodes.remove(node_id).ok_or(BonsaiStorageError::Trie(
-            "Couldn't fetch node in the temporary storage".to_string(),
-        ))? {
-            Node::Binary(mut binary) => {
+        match self
+            .nodes
+            .remove(node_id)
+            .ok_or_else(|| BonsaiStorageError::DanglingNode {
+                identifier: self.identifier.clone(),
+            })? {
+            Node::Binary(binary) => {
                 let left_path = path.new_with_direction(Direction::Left);
                 let left_hash = match binary.left {
                     NodeHandle::Hash(left_hash) => left_hash,
@@ -675,17 +731,14 @@ impl<H: BonsaiHasher + Send + Sync> MerkleTree<H> {
 
                 let hash = hashes.next().expect("mismatched hash state");
 
-                binary.hash = Some(hash);
-                binary.left = NodeHandle::Hash(left_hash);
-                binary.right = NodeHandle::Hash(right_hash);
                 let key_bytes: ByteVec = path.into();
                 updates.insert(
                     TrieKey::new(&self.identifier, TrieKeyType::Trie, &key_bytes),
-                    InsertOrRemove::Insert(Node::Binary(binary).encode_bytevec()),
+                    InsertOrRemove::Insert(Node::encode_binary(hash, left_hash, right_hash)),
                 );
                 Ok(hash)
             }
-            Node::Edge(mut edge) => {
+            Node::Edge(edge) => {
                 let mut child_path = path.clone();
                 child_path.0.extend(&edge.path.0);
                 let child_hash = match edge.child {
@@ -695,12 +748,10 @@ impl<H: BonsaiHasher + Send + Sync> MerkleTree<H> {
                     }
                 };
                 let hash = hashes.next().expect("mismatched hash state");
-                edge.hash = Some(hash);
-                edge.child = NodeHandle::Hash(child_hash);
                 let key_bytes: ByteVec = path.into();
                 updates.insert(
                     TrieKey::new(&self.identifier, TrieKeyType::Trie, &key_bytes),
-                    InsertOrRemove::Insert(Node::Edge(edge).encode_bytevec()),
+                    InsertOrRemove::Insert(Node::encode_edge(hash, child_hash, &edge.path)),
                 );
                 Ok(hash)
             }
@@ -741,12 +792,9 @@ impl<H: BonsaiHasher + Send + Sync> MerkleTree<H> {
             }
         }
 
-        if let Some(value_db) = db.get(&TrieKey::new(
-            &self.identifier,
-            TrieKeyType::Flat,
-            &key_bytes,
-        ))? {
-            if value == Felt::decode(&mut value_db.as_slice()).unwrap() {
+        let flat_key = TrieKey::new(&self.identifier, TrieKeyType::Flat, &key_bytes);
+        if let Some(value_db) = db.get(&flat_key)? {
+            if value == decode_felt::<DB::DatabaseError>(flat_key.as_slice(), &value_db)? {
                 return Ok(());
             }
         }
@@ -862,7 +910,11 @@ impl<H: BonsaiHasher + Send + Sync> MerkleTree<H> {
                         };
                         let key_bytes = bitslice_to_bytes(&key[..edge.height as usize]);
                         log::trace!("2 death row add ({:?})", key_bytes);
-                        self.death_row.insert(TrieKey::Trie(key_bytes));
+                        self.death_row.insert(TrieKey::new(
+                            &self.identifier,
+                            TrieKeyType::Trie,
+                            &key_bytes,
+                        ));
                         node = new_node;
                     }
                     Binary(binary) => {
@@ -1102,8 +1154,10 @@ impl<H: BonsaiHasher + Send + Sync> MerkleTree<H> {
             "get from db with key {:?}",
             &TrieKey::new(&self.identifier, TrieKeyType::Flat, &key)
         );
-        db.get(&TrieKey::new(&self.identifier, TrieKeyType::Flat, &key))
-            .map(|r| r.map(|opt| Felt::decode(&mut opt.as_slice()).unwrap()))
+        let flat_key = TrieKey::new(&self.identifier, TrieKeyType::Flat, &key);
+        db.get(&flat_key)?
+            .map(|value| decode_felt(flat_key.as_slice(), &value))
+            .transpose()
     }
 
     pub fn get_at<DB: BonsaiDatabase, ID: Id>(
@@ -1113,8 +1167,10 @@ impl<H: BonsaiHasher + Send + Sync> MerkleTree<H> {
         id: ID,
     ) -> Result<Option<Felt>, BonsaiStorageError<DB::DatabaseError>> {
         let key = bitslice_to_bytes(key);
-        db.get_at(&TrieKey::new(&self.identifier, TrieKeyType::Flat, &key), id)
-            .map(|r| r.map(|opt| Felt::decode(&mut opt.as_slice()).unwrap()))
+        let flat_key = TrieKey::new(&self.identifier, TrieKeyType::Flat, &key);
+        db.get_at(&flat_key, id)?
+            .map(|value| decode_felt(flat_key.as_slice(), &value))
+            .transpose()
     }
 
     pub fn contains<DB: BonsaiDatabase, ID: Id>(
@@ -1141,6 +1197,7 @@ impl<H: BonsaiHasher + Send + Sync> MerkleTree<H> {
     ) -> Result<Option<Node>, BonsaiStorageError<DB::DatabaseError>> {
         log::trace!("getting: {:b}", path.0);
 
+        let height = path.len() as u64;
         let path: ByteVec = path.into();
         let key = TrieKey::new(identifier, TrieKeyType::Trie, &path);
 
@@ -1151,8 +1208,11 @@ impl<H: BonsaiHasher + Send + Sync> MerkleTree<H> {
         db.get(&key)?
             .map(|node| {
                 log::trace!("got: {:?}", node);
-                Node::decode(&mut node.as_slice()).map_err(|err| {
-                    BonsaiStorageError::Trie(format!("Couldn't decode node: {}", err))
+                Node::decode_at_height(&node, height).map_err(|error| {
+                    BonsaiStorageError::ValueDecode {
+                        key: key.as_slice().to_vec(),
+                        error,
+                    }
                 })
             })
             .map_or(Ok(None), |r| r.map(Some))
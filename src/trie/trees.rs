@@ -1,16 +1,51 @@
-use super::{proof::MultiProof, tree::MerkleTree};
+use super::{
+    proof::MultiProof,
+    tree::{bitslice_to_bytes, bytes_to_bitvec, MerkleTree},
+    trie_db::{identifier_prefix, split_identifier_prefix, TrieKeyType},
+    TrieKey,
+};
 use crate::hasher::BonsaiHasher;
 use crate::{
-    id::Id, key_value_db::KeyValueDB, trie::tree::InsertOrRemove, BitSlice, BonsaiDatabase,
-    BonsaiStorageError, ByteVec, HashMap, Vec,
+    changes::ChangeBatch,
+    commit_info::CommitInfo,
+    id::Id,
+    key_value_db::{decode_felt, leaf_changes_by_identifier, KeyValueDB, LeafChanges},
+    observer::{CommitEvent, CommitKind},
+    trie::tree::InsertOrRemove,
+    BitSlice, BonsaiDatabase, BonsaiStorageError, ByteVec, HashMap, TrieLogError, Vec,
 };
-use core::fmt;
+use core::{fmt, mem};
 use starknet_types_core::felt::Felt;
 
 pub(crate) struct MerkleTrees<H: BonsaiHasher + Send + Sync, DB: BonsaiDatabase, CommitID: Id> {
     pub db: KeyValueDB<DB, CommitID>,
     pub trees: HashMap<ByteVec, MerkleTree<H>>,
     pub max_height: u8,
+    /// Bumped on every commit or reset, to reject savepoints taken before.
+    pub generation: u64,
+    /// The leaves set since the last commit, in order, with the change they had before.
+    journal: Vec<LeafUndo>,
+    /// Number of entries ever pushed to the journal, to number them.
+    journal_serial: u64,
+}
+
+/// A leaf set since the last commit, with its uncommitted change before that.
+#[derive(Clone)]
+struct LeafUndo {
+    identifier: ByteVec,
+    key: ByteVec,
+    previous: Option<InsertOrRemove<Felt>>,
+    serial: u64,
+}
+
+/// A point in the uncommitted changes of all the tries, see [`crate::BonsaiStorage::savepoint`].
+#[derive(Clone, Debug)]
+pub struct Savepoint {
+    generation: u64,
+    /// Length of the journal when the savepoint was taken.
+    position: usize,
+    /// Serial of the last journal entry then, which is gone once rolled back past.
+    serial: u64,
 }
 
 impl<H: BonsaiHasher + Send + Sync, DB: BonsaiDatabase + fmt::Debug, CommitID: Id> fmt::Debug
@@ -33,6 +68,9 @@ impl<H: BonsaiHasher + Send + Sync, DB: BonsaiDatabase + Clone, CommitID: Id> Cl
             db: self.db.clone(),
             trees: self.trees.clone(),
             max_height: self.max_height,
+            generation: self.generation,
+            journal: self.journal.clone(),
+            journal_serial: self.journal_serial,
         }
     }
 }
@@ -43,6 +81,9 @@ impl<H: BonsaiHasher + Send + Sync, DB: BonsaiDatabase, CommitID: Id> MerkleTree
             db,
             trees: HashMap::new(),
             max_height: tree_height,
+            generation: 0,
+            journal: Vec::new(),
+            journal_serial: 0,
         }
     }
 
@@ -57,6 +98,14 @@ impl<H: BonsaiHasher + Send + Sync, DB: BonsaiDatabase, CommitID: Id> MerkleTree
             .entry_ref(identifier)
             .or_insert_with(|| MerkleTree::new(identifier.into(), self.max_height));
 
+        let key_bytes = bitslice_to_bytes(key);
+        self.journal_serial += 1;
+        self.journal.push(LeafUndo {
+            identifier: identifier.into(),
+            previous: tree.cache_leaf_modified.get(&key_bytes).cloned(),
+            key: key_bytes,
+            serial: self.journal_serial,
+        });
         tree.set(&self.db, key, value)
     }
 
@@ -105,9 +154,197 @@ impl<H: BonsaiHasher + Send + Sync, DB: BonsaiDatabase, CommitID: Id> MerkleTree
         &mut self,
     ) -> Result<(), BonsaiStorageError<DB::DatabaseError>> {
         self.trees.clear(); // just clear the map
+        self.db.changes_store.current_changes = ChangeBatch::default();
+        self.generation += 1;
+        self.journal.clear();
+        Ok(())
+    }
+
+    pub(crate) fn savepoint(&self) -> Savepoint {
+        Savepoint {
+            generation: self.generation,
+            position: self.journal.len(),
+            serial: self.journal.last().map_or(0, |undo| undo.serial),
+        }
+    }
+
+    #[cfg(test)]
+    pub(crate) fn journal_len(&self) -> usize {
+        self.journal.len()
+    }
+
+    /// Restores the leaf changes the keys set since `savepoint` had, then rebuilds the nodes of
+    /// the tries they belong to from the database and these changes.
+    pub(crate) fn rollback_to(
+        &mut self,
+        savepoint: &Savepoint,
+    ) -> Result<(), BonsaiStorageError<DB::DatabaseError>> {
+        let taken_since_rollback = match savepoint.position.checked_sub(1) {
+            Some(last) => self
+                .journal
+                .get(last)
+                .is_some_and(|undo| undo.serial == savepoint.serial),
+            None => true,
+        };
+        if savepoint.generation != self.generation || !taken_since_rollback {
+            return Err(BonsaiStorageError::StaleSavepoint);
+        }
+
+        let mut touched: Vec<ByteVec> = Vec::new();
+        for undo in self.journal.drain(savepoint.position..).rev() {
+            let Some(tree) = self.trees.get_mut(&undo.identifier) else {
+                continue;
+            };
+            match undo.previous {
+                Some(change) => {
+                    tree.cache_leaf_modified.insert(undo.key, change);
+                }
+                None => {
+                    tree.cache_leaf_modified.remove(&undo.key);
+                }
+            }
+            if !touched.contains(&undo.identifier) {
+                touched.push(undo.identifier);
+            }
+        }
+
+        for identifier in touched {
+            let Some(tree) = self.trees.get_mut(&identifier) else {
+                continue;
+            };
+            let changes = mem::take(&mut tree.cache_leaf_modified);
+            *tree = MerkleTree::new(identifier, self.max_height);
+            for (key, change) in &changes {
+                let mut key = bytes_to_bitvec(key);
+                key.truncate(self.max_height as usize);
+                let value = match change {
+                    InsertOrRemove::Insert(value) => *value,
+                    InsertOrRemove::Remove => Felt::ZERO,
+                };
+                tree.set(&self.db, &key, value)?;
+            }
+            tree.cache_leaf_modified = changes;
+        }
         Ok(())
     }
 
+    /// Leaf changes since the last commit, keyed by flat key.
+    pub(crate) fn uncommitted_leaf_changes(&self) -> LeafChanges {
+        self.trees
+            .iter()
+            .flat_map(|(identifier, tree)| {
+                tree.cache_leaf_modified().iter().map(move |(key, value)| {
+                    let key = TrieKey::new(identifier, TrieKeyType::Flat, key);
+                    let value = match value {
+                        InsertOrRemove::Insert(value) => Some(*value),
+                        InsertOrRemove::Remove => None,
+                    };
+                    (key.as_slice().into(), value)
+                })
+            })
+            .collect()
+    }
+
+    /// Commits the trees, then writes the trie log of `id` and its [`crate::CommitInfo`]. An empty
+    /// database is stamped with its schema version first.
+    ///
+    /// The event for the [`crate::CommitObserver`]s is only built when `observed` is set.
+    pub(crate) fn commit_with_info(
+        &mut self,
+        id: CommitID,
+        metadata: Option<&[u8]>,
+        observed: bool,
+    ) -> Result<Option<CommitEvent<CommitID>>, BonsaiStorageError<DB::DatabaseError>> {
+        self.db.write_schema_version()?;
+        self.commit()?;
+        let mut identifiers: Vec<&[u8]> = self
+            .db
+            .changes_store
+            .current_changes
+            .0
+            .keys()
+            .filter_map(|key| match key {
+                TrieKey::Flat(key) => {
+                    split_identifier_prefix(key).map(|(identifier, _)| identifier)
+                }
+                _ => None,
+            })
+            .collect();
+        identifiers.sort_unstable();
+        identifiers.dedup();
+        let roots: Vec<(Vec<u8>, Felt)> = identifiers
+            .into_iter()
+            .map(|identifier| Ok((identifier.to_vec(), self.root_hash(identifier)?)))
+            .collect::<Result<_, BonsaiStorageError<DB::DatabaseError>>>()?;
+        let event = if observed {
+            Some(CommitEvent {
+                kind: CommitKind::Commit,
+                id,
+                changes: leaf_changes_by_identifier::<DB::DatabaseError>(
+                    &self.db.changes_store.current_changes,
+                )?,
+                roots: roots.clone(),
+            })
+        } else {
+            None
+        };
+        self.db.commit(id, roots, metadata)?;
+        Ok(event)
+    }
+
+    /// Sets the leaf at a flat key, removing it when `value` is `None`.
+    pub(crate) fn set_flat(
+        &mut self,
+        key: &[u8],
+        value: Option<Felt>,
+    ) -> Result<(), BonsaiStorageError<DB::DatabaseError>> {
+        let Some((identifier, path)) = split_identifier_prefix(key) else {
+            return Err(BonsaiStorageError::InvalidFlatKey(key.to_vec()));
+        };
+        self.set(
+            identifier,
+            &bytes_to_bitvec(path),
+            value.unwrap_or(Felt::ZERO),
+        )
+    }
+
+    /// Replays a commit exported from another storage and commits it as `info.id`.
+    ///
+    /// Only its leaves are replayed: the roots they lead to are recomputed and must be the ones
+    /// of `info` before anything is written, otherwise the leaves are discarded.
+    pub(crate) fn apply_trie_log(
+        &mut self,
+        info: CommitInfo<CommitID>,
+        changes: &ChangeBatch,
+        observed: bool,
+    ) -> Result<Option<CommitEvent<CommitID>>, BonsaiStorageError<DB::DatabaseError>> {
+        self.db.check_trie_log(&info, changes)?;
+        for (key, change) in &changes.0 {
+            let TrieKey::Flat(key) = key else {
+                continue;
+            };
+            let value = change
+                .new_value
+                .as_ref()
+                .map(|value| decode_felt::<DB::DatabaseError>(key, value))
+                .transpose()?;
+            self.set_flat(key, value)?;
+        }
+        for (identifier, expected) in &info.roots {
+            let got = self.uncommitted_root_hash(identifier)?;
+            if got != *expected {
+                self.reset_to_last_commit()?;
+                return Err(BonsaiStorageError::TrieLog(TrieLogError::RootMismatch {
+                    id: info.id.as_u64(),
+                    identifier: identifier.clone(),
+                    expected: *expected,
+                    got,
+                }));
+            }
+        }
+        self.commit_with_info(info.id, info.metadata.as_deref(), observed)
+    }
+
     pub(crate) fn db_ref(&self) -> &KeyValueDB<DB, CommitID> {
         &self.db
     }
@@ -132,29 +369,36 @@ impl<H: BonsaiHasher + Send + Sync, DB: BonsaiDatabase, CommitID: Id> MerkleTree
         }
     }
 
+    /// Root hash of a trie with its uncommitted changes, hashed without committing them.
+    pub(crate) fn uncommitted_root_hash(
+        &self,
+        identifier: &[u8],
+    ) -> Result<Felt, BonsaiStorageError<DB::DatabaseError>> {
+        match self.trees.get(identifier) {
+            Some(tree) => tree.uncommitted_root_hash(&self.db),
+            None => self.root_hash(identifier),
+        }
+    }
+
     pub(crate) fn get_keys(
         &self,
         identifier: &[u8],
     ) -> Result<Vec<Vec<u8>>, BonsaiStorageError<DB::DatabaseError>> {
+        let prefix = identifier_prefix(identifier);
         self.db
             .db
-            .get_by_prefix(&crate::DatabaseKey::Flat(identifier))
-            .map(|key_value_pairs| {
-                // Remove the identifier from the key
-                key_value_pairs
-                    .into_iter()
-                    // FIXME: this does not filter out keys values correctly for `HashMapDb` due
-                    // to branches and leafs not being differenciated
-                    .filter_map(|(key, _value)| {
-                        if key.len() > identifier.len() {
-                            Some(key[identifier.len() + 1..].into())
-                        } else {
-                            None
-                        }
-                    })
-                    .collect()
+            .iter_by_prefix(&crate::DatabaseKey::Flat(&prefix), None, None)?
+            // Remove the identifier from the key
+            // FIXME: this does not filter out keys values correctly for `HashMapDb` due
+            // to branches and leafs not being differenciated
+            .filter_map(|entry| match entry {
+                Ok((key, _value)) if key.len() > prefix.len() => {
+                    Some(Ok(key[prefix.len() + 1..].into()))
+                }
+                Ok(_) => None,
+                Err(e) => Some(Err(e.into())),
             })
-            .map_err(|e| e.into())
+            .collect()
     }
 
     #[allow(clippy::type_complexity)]
@@ -162,30 +406,29 @@ impl<H: BonsaiHasher + Send + Sync, DB: BonsaiDatabase, CommitID: Id> MerkleTree
         &self,
         identifier: &[u8],
     ) -> Result<Vec<(Vec<u8>, Vec<u8>)>, BonsaiStorageError<DB::DatabaseError>> {
+        let prefix = identifier_prefix(identifier);
         self.db
             .db
-            .get_by_prefix(&crate::DatabaseKey::Flat(identifier))
-            .map(|key_value_pairs| {
-                key_value_pairs
-                    .into_iter()
-                    // FIXME: this does not filter out keys values correctly for `HashMapDb` due
-                    // to branches and leafs not being differenciated
-                    .filter_map(|(key, value)| {
-                        if key.len() > identifier.len() {
-                            Some((key[identifier.len() + 1..].into(), value.into_vec()))
-                        } else {
-                            None
-                        }
-                    })
-                    .collect()
+            .iter_by_prefix(&crate::DatabaseKey::Flat(&prefix), None, None)?
+            // FIXME: this does not filter out keys values correctly for `HashMapDb` due
+            // to branches and leafs not being differenciated
+            .filter_map(|entry| match entry {
+                Ok((key, value)) if key.len() > prefix.len() => {
+                    Some(Ok((key[prefix.len() + 1..].into(), value.into_vec())))
+                }
+                Ok(_) => None,
+                Err(e) => Some(Err(e.into())),
             })
-            .map_err(|e| e.into())
+            .collect()
     }
 
     pub(crate) fn commit(&mut self) -> Result<(), BonsaiStorageError<DB::DatabaseError>> {
         #[cfg(feature = "std")]
         use rayon::prelude::*;
 
+        self.generation += 1;
+        self.journal.clear();
+
         #[cfg(not(feature = "std"))]
         let db_changes = self
             .trees
@@ -229,16 +472,28 @@ impl<H: BonsaiHasher + Send + Sync, DB: BonsaiDatabase, CommitID: Id> MerkleTree
     //     }
     // }
 
-    pub fn get_multi_proof(
+    pub(crate) fn prefetch(
         &mut self,
         identifier: &[u8],
-        keys: impl IntoIterator<Item = impl AsRef<BitSlice>>,
-    ) -> Result<MultiProof, BonsaiStorageError<DB::DatabaseError>> {
+        keys: &[&BitSlice],
+    ) -> Result<(), BonsaiStorageError<DB::DatabaseError>> {
         let tree = self
             .trees
             .entry_ref(identifier)
             .or_insert_with(|| MerkleTree::new(identifier.into(), self.max_height));
 
-        tree.get_multi_proof(&self.db, keys)
+        tree.prefetch(&self.db, keys)
+    }
+
+    pub fn get_multi_proof(
+        &self,
+        identifier: &[u8],
+        keys: impl IntoIterator<Item = impl AsRef<BitSlice>>,
+    ) -> Result<MultiProof, BonsaiStorageError<DB::DatabaseError>> {
+        match self.trees.get(identifier) {
+            Some(tree) => tree.get_multi_proof(&self.db, keys),
+            None => MerkleTree::<H>::new(identifier.into(), self.max_height)
+                .get_multi_proof(&self.db, keys),
+        }
     }
 }
@@ -1,4 +1,5 @@
-use crate::{bonsai_database::DatabaseKey, ByteVec};
+use crate::{bonsai_database::DatabaseKey, ByteVec, EncodeExt};
+use parity_scale_codec::{Compact, Decode};
 
 /// Key in the database of the different elements that are used in the storage of the trie data.
 /// Use `new` function to create a new key.
@@ -31,9 +32,31 @@ impl From<&TrieKey> for u8 {
     }
 }
 
+/// Encoding of an identifier as it appears at the start of every trie and flat key: its SCALE
+/// compact length followed by its bytes. The length prefix makes identifiers prefix-free, so
+/// `[1]` and `[1, 2]` can never produce the same key and a prefix scan on this value only
+/// returns the entries of a single trie.
+pub(crate) fn identifier_prefix(identifier: &[u8]) -> ByteVec {
+    let mut prefix = Compact(identifier.len() as u32).encode_bytevec();
+    prefix.extend_from_slice(identifier);
+    prefix
+}
+
+/// Splits a trie or flat key into its identifier and the remaining path bytes.
+/// Returns `None` if the key does not start with a valid [`identifier_prefix`].
+pub(crate) fn split_identifier_prefix(key: &[u8]) -> Option<(&[u8], &[u8])> {
+    let mut input = key;
+    let Compact(len) = Compact::<u32>::decode(&mut input).ok()?;
+    let len = len as usize;
+    if input.len() < len {
+        return None;
+    }
+    Some(input.split_at(len))
+}
+
 impl TrieKey {
     pub fn new(identifier: &[u8], key_type: TrieKeyType, key: &[u8]) -> Self {
-        let mut final_key = ByteVec::from(identifier);
+        let mut final_key = identifier_prefix(identifier);
         final_key.extend_from_slice(key);
         match key_type {
             TrieKeyType::Trie => TrieKey::Trie(final_key),
@@ -41,11 +64,12 @@ impl TrieKey {
         }
     }
 
-    pub fn from_variant_and_bytes(variant: u8, bytes: ByteVec) -> Self {
+    /// Returns `None` for an unknown key type.
+    pub fn from_variant_and_bytes(variant: u8, bytes: ByteVec) -> Option<Self> {
         match variant {
-            x if x == TrieKeyType::Trie as u8 => TrieKey::Trie(bytes),
-            x if x == TrieKeyType::Flat as u8 => TrieKey::Flat(bytes),
-            _ => panic!("Invalid trie key type"),
+            x if x == TrieKeyType::Trie as u8 => Some(TrieKey::Trie(bytes)),
+            x if x == TrieKeyType::Flat as u8 => Some(TrieKey::Flat(bytes)),
+            _ => None,
         }
     }
 
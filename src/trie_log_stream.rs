@@ -0,0 +1,85 @@
+use parity_scale_codec::{Compact, Decode, Encode};
+
+use crate::{
+    changes::{Change, ChangeBatch},
+    commit_info::CommitInfo,
+    id::Id,
+    trie::TrieKey,
+    BonsaiStorageError, DBError, HashMap, TrieLogError, Vec,
+};
+
+/// Version of the encoding of the streams written by
+/// [`crate::BonsaiStorage::export_trie_logs`].
+pub const TRIE_LOG_STREAM_VERSION: u32 = 1;
+
+/// First bytes of every stream.
+const TRIE_LOG_STREAM_MAGIC: &[u8] = b"BTLS";
+
+/// A change of a trie log: key type, key, old value and new value.
+type Entry = (u8, Vec<u8>, Option<Vec<u8>>, Option<Vec<u8>>);
+
+/// Encodes the trie logs of commits, in order, with their records.
+///
+/// The stream is the magic bytes, the version, then the SCALE encoding of the commits: each one
+/// is its [`CommitInfo`] followed by its changes sorted by key.
+pub(crate) fn encode_trie_logs<ID: Id>(commits: &[(CommitInfo<ID>, ChangeBatch)]) -> Vec<u8> {
+    let mut stream = TRIE_LOG_STREAM_MAGIC.to_vec();
+    TRIE_LOG_STREAM_VERSION.encode_to(&mut stream);
+    Compact(commits.len() as u32).encode_to(&mut stream);
+    for (info, changes) in commits {
+        info.encode_to(&mut stream);
+        let mut entries: Vec<_> = changes
+            .0
+            .iter()
+            .map(|(key, change)| {
+                (
+                    u8::from(key),
+                    key.as_slice(),
+                    change.old_value.as_deref(),
+                    change.new_value.as_deref(),
+                )
+            })
+            .collect();
+        entries.sort_unstable();
+        entries.encode_to(&mut stream);
+    }
+    stream
+}
+
+/// Decodes a stream written by [`encode_trie_logs`].
+#[allow(clippy::type_complexity)]
+pub(crate) fn decode_trie_logs<ID: Id, E: DBError>(
+    stream: &[u8],
+) -> Result<Vec<(CommitInfo<ID>, ChangeBatch)>, BonsaiStorageError<E>> {
+    let Some(mut input) = stream.strip_prefix(TRIE_LOG_STREAM_MAGIC) else {
+        return Err(BonsaiStorageError::TrieLog(TrieLogError::InvalidStream));
+    };
+    let input = &mut input;
+    let version = u32::decode(input)?;
+    if version != TRIE_LOG_STREAM_VERSION {
+        return Err(BonsaiStorageError::TrieLog(
+            TrieLogError::UnsupportedVersion(version),
+        ));
+    }
+    let Compact(len) = Compact::<u32>::decode(input)?;
+    let mut commits = Vec::new();
+    for _ in 0..len {
+        let info = CommitInfo::decode(input)?;
+        let mut changes = HashMap::new();
+        for (key_type, key, old_value, new_value) in Vec::<Entry>::decode(input)? {
+            let Some(key) = TrieKey::from_variant_and_bytes(key_type, key.into()) else {
+                return Err(parity_scale_codec::Error::from("Invalid trie key type").into());
+            };
+            let change = Change {
+                old_value: old_value.map(Into::into),
+                new_value: new_value.map(Into::into),
+            };
+            changes.insert(key, change);
+        }
+        commits.push((info, ChangeBatch(changes)));
+    }
+    if !input.is_empty() {
+        return Err(parity_scale_codec::Error::from("Trailing bytes in trie log stream").into());
+    }
+    Ok(commits)
+}